@@ -1,7 +1,8 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs::{create_dir_all, read_dir, File};
-use std::io::{BufReader, Write};
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
 
@@ -11,11 +12,24 @@ use html2text::render::text_renderer::{
 };
 use html2text::Colour;
 
+use crossterm::style::Attribute;
+
 use toiletcli::colors::{Color, Style};
 use toiletcli::flags::{FlagError, FlagErrorType};
 
+use regex::Regex;
+
 use serde::{Deserialize, Serialize};
 
+use crate::config::{get_config, Config, DEFAULT_DOCS_JSON_MAX_AGE, DEFAULT_MAX_WIDTH};
+use crate::highlight::highlight_page;
+use crate::json::{is_json_output, print_json, PageJson};
+use crate::markdown::html_to_markdown;
+use crate::pack::{is_docset_file, open_docset_file};
+use crate::prerender::read_prerendered_page;
+use crate::theme::get_theme;
+use crate::zeal::{get_zeal_documents_path, is_zeal_docset};
+
 pub(crate) type ResultS = Result<(), String>;
 
 pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -30,17 +44,17 @@ pub(crate) const DEFAULT_DOCS_JSON_LINK: &str = "https://devdocs.io/docs.json";
 
 pub(crate) const DEFAULT_USER_AGENT: &str = "dedoc";
 
+pub(crate) const HOME_ENV: &str = "DEDOC_HOME";
+
 pub(crate) const DOC_PAGE_EXTENSION: &str = "html";
+pub(crate) const PRERENDERED_PAGE_EXTENSION: &str = "dmd";
 
 pub(crate) const DEFAULT_WIDTH: usize = 80;
 
 pub(crate) const RED: Color = Color::Red;
 pub(crate) const GREEN: Color = Color::Green;
 pub(crate) const YELLOW: Color = Color::Yellow;
-pub(crate) const LIGHT_GRAY: Color = Color::Byte(248);
 pub(crate) const GRAY: Color = Color::BrightBlack;
-pub(crate) const GRAYER: Color = Color::Byte(240);
-pub(crate) const GRAYEST: Color = Color::Byte(234);
 pub(crate) const BOLD: Style = Style::Bold;
 pub(crate) const UNDERLINE: Style = Style::Underlined;
 pub(crate) const RESET: Style = Style::Reset;
@@ -97,8 +111,8 @@ pub(crate) struct Docs {
     links: Links,
     #[serde(default = "unknown_version")]
     pub version: String,
-    #[serde(skip)]
-    release: String,
+    #[serde(default)]
+    pub release: String,
     pub mtime: u64,
     db_size: usize,
     #[serde(skip)]
@@ -121,6 +135,97 @@ pub(crate) struct Docs {
 //     "attribution": "whatever"
 // }
 
+// manifest.json, written to the docset directory by `download`. Local docsets are outdated when
+// their `mtime` is older than the one in `docs.json`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DocsetManifest {
+    pub slug: String,
+    pub mtime: u64,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub release: String,
+    // Seconds since the Unix epoch.
+    pub downloaded_at: u64,
+    // Whether pages were pre-rendered with `download --prerender`.
+    #[serde(default)]
+    pub prerendered: bool,
+    // Whether pages are in a pack, with `download --pack`.
+    #[serde(default)]
+    pub packed: bool,
+}
+
+impl DocsetManifest {
+    pub(crate) fn from_docs(entry: &Docs, prerendered: bool, packed: bool) -> Self {
+        let downloaded_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        DocsetManifest {
+            slug: entry.slug.clone(),
+            mtime: entry.mtime,
+            version: entry.version.clone(),
+            release: entry.release.clone(),
+            downloaded_at,
+            prerendered,
+            packed,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_outdated(&self, entry: &Docs) -> bool {
+        self.mtime < entry.mtime
+    }
+}
+
+// Human-readable version of a docset, e.g. `1.72.0`. `release` is more precise than `version`,
+// which is often empty.
+pub(crate) fn format_docset_version(version: &str, release: &str) -> String {
+    if !release.is_empty() {
+        release.to_owned()
+    } else if !version.is_empty() {
+        version.to_owned()
+    } else {
+        unknown_version()
+    }
+}
+
+#[inline]
+pub(crate) fn read_docset_manifest(docset_name: &str) -> Result<Option<DocsetManifest>, String> {
+    read_docset_manifest_at(&get_docset_path(docset_name)?)
+}
+
+pub(crate) fn read_docset_manifest_at(
+    docset_path: &Path,
+) -> Result<Option<DocsetManifest>, String> {
+    let manifest_path = docset_path.join("manifest.json");
+
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(&manifest_path)
+        .map_err(|err| format!("Could not open `{}`: {err}", manifest_path.display()))?;
+    let reader = BufReader::new(file);
+
+    let manifest = serde_json::from_reader(reader)
+        .map_err(|err| format!("Could not deserialize `{}`: {err}", manifest_path.display()))?;
+
+    Ok(Some(manifest))
+}
+
+pub(crate) fn write_docset_manifest(docset_path: &Path, manifest: &DocsetManifest) -> ResultS {
+    let manifest_path = docset_path.join("manifest.json");
+
+    let file = File::create(&manifest_path)
+        .map_err(|err| format!("Could not create `{}`: {err}", manifest_path.display()))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, manifest)
+        .map_err(|err| format!("Could not write `{}`: {err}", manifest_path.display()))
+}
+
 pub(crate) fn deserialize_docs_json() -> Result<Vec<Docs>, String> {
     let docs_json_path = get_program_directory()?.join("docs.json");
     let file = File::open(&docs_json_path)
@@ -138,7 +243,7 @@ pub(crate) fn deserialize_docs_json() -> Result<Vec<Docs>, String> {
 macro_rules! print_warning {
     ($($e:expr),+) => {
         {
-            eprint!("{}WARNING{}: ", toiletcli::colors::Color::Yellow, toiletcli::colors::Style::Reset);
+            eprint!("{}WARNING{}: ", $crate::theme::get_theme().warning, toiletcli::colors::Style::Reset);
             eprintln!($($e),+);
         }
     };
@@ -154,17 +259,140 @@ pub(crate) fn get_flag_error(flag_error: &FlagError) -> String {
     }
 }
 
-pub(crate) fn get_terminal_width() -> usize {
-    let result = terminal_size::terminal_size();
+// Page width from the config, or the width of the terminal up to `max_width`.
+pub(crate) fn get_default_width() -> Result<usize, String> {
+    let config = get_config()?;
+
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(w), _)) => Ok(get_width_for_terminal(&config, w as usize)),
+        None => Ok(config.width.unwrap_or(DEFAULT_WIDTH)),
+    }
+}
+
+// Same as `get_default_width`, for a terminal that is `terminal_width` columns wide.
+pub(crate) fn get_width_for_terminal(config: &Config, terminal_width: usize) -> usize {
+    let max_width = config.max_width.unwrap_or(DEFAULT_MAX_WIDTH);
+    config
+        .width
+        .unwrap_or_else(|| std::cmp::min(terminal_width, max_width))
+}
+
+// Line numbers have to be the same on every terminal, so pages with them are as wide as the config
+// says, or `DEFAULT_WIDTH`, but never as wide as the terminal.
+pub(crate) fn get_page_width(page_options: &PageOptions) -> Result<usize, String> {
+    if page_options.has_line_options() {
+        return Ok(get_config()?.width.unwrap_or(DEFAULT_WIDTH));
+    }
+    get_default_width()
+}
+
+pub(crate) fn parse_follow_flag(flag_follow: &str) -> Result<Option<usize>, String> {
+    if flag_follow.is_empty() {
+        return Ok(None);
+    }
 
-    if let Some((terminal_size::Width(w), _)) = result {
-        if w < 120 {
-            return w as usize;
+    match flag_follow.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(Some(number)),
+        _ => Err(format!(
+            "`--follow` requires a link number, got `{flag_follow}`."
+        )),
+    }
+}
+
+pub(crate) const DEFAULT_GREP_CONTEXT: usize = 2;
+
+// How `open` and `search --open` print pages. Line numbers count from 1 and are the same with and
+// without a fragment, so they can be used to find the same place again.
+#[derive(Default)]
+pub(crate) struct PageOptions {
+    pub(crate) markdown: bool,
+    pub(crate) line_numbers: bool,
+    // First and last line, inclusive.
+    pub(crate) line_range: Option<(usize, Option<usize>)>,
+    pub(crate) grep: Option<Regex>,
+    // Lines around matches of `grep`.
+    pub(crate) grep_context: usize,
+}
+
+impl PageOptions {
+    #[inline]
+    pub(crate) fn has_line_options(&self) -> bool {
+        self.line_numbers || self.line_range.is_some() || self.grep.is_some()
+    }
+}
+
+// Parses `A:B`, `A:`, `:B` or `A`.
+fn parse_line_range(flag_lines: &str) -> Result<(usize, Option<usize>), String> {
+    let error = || format!("`--lines` requires a range like `10:20`, got `{flag_lines}`.");
+
+    let parse_number = |number: &str| match number.trim().parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(error()),
+    };
+
+    let (first, last) = match flag_lines.split_once(':') {
+        Some(("", "")) => return Err(error()),
+        Some(("", last)) => (1, Some(parse_number(last)?)),
+        Some((first, "")) => (parse_number(first)?, None),
+        Some((first, last)) => (parse_number(first)?, Some(parse_number(last)?)),
+        None => {
+            let line = parse_number(flag_lines)?;
+            (line, Some(line))
         }
-        return 120;
+    };
+
+    if matches!(last, Some(last) if last < first) {
+        return Err(error());
+    }
+
+    Ok((first, last))
+}
+
+pub(crate) fn parse_page_options(
+    markdown: bool,
+    line_numbers: bool,
+    flag_lines: &str,
+    flag_grep: &str,
+    flag_context: &str,
+) -> Result<PageOptions, String> {
+    let line_range = if flag_lines.is_empty() {
+        None
+    } else {
+        Some(parse_line_range(flag_lines)?)
+    };
+
+    let grep = if flag_grep.is_empty() {
+        None
+    } else {
+        let regex = Regex::new(flag_grep)
+            .map_err(|err| format!("Invalid regular expression `{flag_grep}`: {err}"))?;
+        Some(regex)
+    };
+
+    let grep_context = if flag_context.is_empty() {
+        DEFAULT_GREP_CONTEXT
+    } else {
+        flag_context
+            .parse::<usize>()
+            .map_err(|_| format!("`--context` requires a number of lines, got `{flag_context}`."))?
+    };
+
+    let page_options = PageOptions {
+        markdown,
+        line_numbers,
+        line_range,
+        grep,
+        grep_context,
+    };
+
+    if markdown && page_options.has_line_options() {
+        return Err(
+            "`--line-numbers`, `--lines` and `--grep` cannot be used with `--markdown`."
+                .to_string(),
+        );
     }
 
-    DEFAULT_WIDTH
+    Ok(page_options)
 }
 
 #[inline]
@@ -183,7 +411,123 @@ pub(crate) fn split_to_item_and_fragment(path: String) -> Result<(String, Option
     Ok((item, fragment))
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ColorDepth {
+    Colors16,
+    Colors256,
+    TrueColor,
+}
+
+// Terminals don't have a reliable way to tell how many colors they have, so this goes by the
+// environment variables that most of them set.
+fn get_color_depth() -> ColorDepth {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    let term = std::env::var("TERM").unwrap_or_default();
+
+    if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+        ColorDepth::TrueColor
+    } else if std::env::var_os("WT_SESSION").is_some() {
+        // Windows Terminal.
+        ColorDepth::TrueColor
+    } else if term.contains("256") {
+        ColorDepth::Colors256
+    } else {
+        ColorDepth::Colors16
+    }
+}
+
+// Nearest color of the xterm palette: the 6x6x6 cube or the gray ramp.
+fn get_color_256(r: u8, g: u8, b: u8) -> Color {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    let get_nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|i| (CUBE_LEVELS[*i] as i32 - value as i32).abs())
+            .unwrap_or(0)
+    };
+
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let (dr, dg, db) = (
+            r as i32 - r2 as i32,
+            g as i32 - g2 as i32,
+            b as i32 - b2 as i32,
+        );
+        dr * dr + dg * dg + db * db
+    };
+
+    let (ri, gi, bi) = (
+        get_nearest_level(r),
+        get_nearest_level(g),
+        get_nearest_level(b),
+    );
+    let cube_color = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (average.saturating_sub(8) / 10).min(23) as u8;
+    let gray = 8 + gray_index * 10;
+
+    if distance((gray, gray, gray)) < distance(cube_color) {
+        Color::Byte(232 + gray_index)
+    } else {
+        Color::Byte(16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8)
+    }
+}
+
+// Basic colors look different in every terminal, so colors are matched by hue, and grays by
+// lightness.
+fn get_color_16(r: u8, g: u8, b: u8) -> Color {
+    let max = r.max(g).max(b) as i32;
+    let min = r.min(g).min(b) as i32;
+
+    if max - min < 24 {
+        return match max {
+            0..=63 => Color::Black,
+            64..=159 => Color::BrightBlack,
+            160..=223 => Color::White,
+            _ => Color::BrightWhite,
+        };
+    }
+
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let chroma = max - min;
+
+    let hue = if max == r {
+        (60 * (g - b) / chroma).rem_euclid(360)
+    } else if max == g {
+        60 * (b - r) / chroma + 120
+    } else {
+        60 * (r - g) / chroma + 240
+    };
+
+    let is_bright = max > 200;
+
+    match (hue, is_bright) {
+        (30..=89, false) => Color::Yellow,
+        (30..=89, true) => Color::BrightYellow,
+        (90..=149, false) => Color::Green,
+        (90..=149, true) => Color::BrightGreen,
+        (150..=209, false) => Color::Cyan,
+        (150..=209, true) => Color::BrightCyan,
+        (210..=269, false) => Color::Blue,
+        (210..=269, true) => Color::BrightBlue,
+        (270..=329, false) => Color::Purple,
+        (270..=329, true) => Color::BrightPurple,
+        (_, false) => Color::Red,
+        (_, true) => Color::BrightRed,
+    }
+}
+
+// Colors of pages, like the ones of highlighted code, reduced to what the terminal has.
+pub(crate) fn get_terminal_color(r: u8, g: u8, b: u8) -> Color {
+    match get_color_depth() {
+        ColorDepth::TrueColor => Color::RGB(r, g, b),
+        ColorDepth::Colors256 => get_color_256(r, g, b),
+        ColorDepth::Colors16 => get_color_16(r, g, b),
+    }
+}
+
 fn get_tag_style(tagged_string_tags: &Vec<RichAnnotation>) -> String {
+    let theme = get_theme();
     let mut style = String::new();
     let mut temp_style;
 
@@ -191,10 +535,10 @@ fn get_tag_style(tagged_string_tags: &Vec<RichAnnotation>) -> String {
         temp_style = match *annotation {
             RichAnnotation::Default => continue,
             RichAnnotation::Link(_) => {
-                format!("{}", Color::Blue)
+                format!("{}", theme.link)
             }
             RichAnnotation::Image(_) => {
-                format!("{}", Color::BrightBlue)
+                format!("{}", theme.image)
             }
             RichAnnotation::Emphasis => {
                 format!("{}", Style::Bold)
@@ -206,16 +550,16 @@ fn get_tag_style(tagged_string_tags: &Vec<RichAnnotation>) -> String {
                 format!("{}", Style::Strikethrough)
             }
             RichAnnotation::Code => {
-                format!("{}", Color::BrightBlack)
+                format!("{}", theme.code)
             }
             RichAnnotation::Preformat(_) => {
-                format!("{}{}", LIGHT_GRAY, GRAYEST.bg())
+                format!("{}{}", theme.code_block, theme.code_block_background.bg())
             }
             RichAnnotation::Colour(Colour { r, g, b }) => {
-                format!("{}", Color::RGB(r, g, b))
+                format!("{}", get_terminal_color(r, g, b))
             }
             RichAnnotation::BgColour(Colour { r, g, b }) => {
-                Color::RGB(r, g, b).bg().to_string()
+                get_terminal_color(r, g, b).bg().to_string()
             }
             _ => continue,
         };
@@ -227,7 +571,7 @@ fn get_tag_style(tagged_string_tags: &Vec<RichAnnotation>) -> String {
 }
 
 // This function ignores fragment's character case, to support --case-insensitive
-fn get_fragment_bounds(
+pub(crate) fn get_fragment_bounds(
     tagged_lines: &[TaggedLine<Vec<RichAnnotation>>],
     fragment: &str,
 ) -> (Option<usize>, Option<usize>) {
@@ -257,123 +601,654 @@ fn get_fragment_bounds(
     (current_fragment_line, None)
 }
 
-pub(crate) fn print_docset_file(
-    path: PathBuf,
-    fragment: Option<&String>,
+pub(crate) type RichPage = Vec<TaggedLine<Vec<RichAnnotation>>>;
+
+// `docset_path` is the directory that pages of the docset are in, where its pack is looked up.
+pub(crate) fn render_docset_file(
+    docset_path: &Path,
+    path: &Path,
     width: usize,
-) -> Result<bool, String> {
-    let file =
-        File::open(&path).map_err(|err| format!("Could not open `{}`: {err}", path.display()))?;
-    let reader = BufReader::new(file);
+) -> Result<RichPage, String> {
+    // Pages from `download --prerender` only need to be wrapped. Fall back to HTML if they can't
+    // be read, e.g. when the format has changed.
+    let prerendered_path = path.with_extension(PRERENDERED_PAGE_EXTENSION);
+
+    if is_docset_file(docset_path, &prerendered_path) {
+        match read_prerendered_page(docset_path, &prerendered_path, width) {
+            Ok(rich_page) => return Ok(rich_page),
+            Err(err) => {
+                debug_println!("{err}");
+            }
+        }
+    }
+
+    let reader = BufReader::new(open_docset_file(docset_path, path)?);
 
-    let rich_page = html2text::from_read_rich(reader, width);
+    Ok(html2text::from_read_rich(reader, width))
+}
 
-    let mut current_fragment_line = 0;
-    let mut next_fragment_line = 0;
+// Returns a line with terminal styles applied and whether it has any text. Links to
+// `highlighted_href` are displayed in reverse video. `link_markers` are pairs of tagged string
+// indices and reference numbers, which are printed after these strings.
+pub(crate) fn format_rich_line(
+    rich_line: &TaggedLine<Vec<RichAnnotation>>,
+    width: usize,
+    highlighted_href: Option<&str>,
+    link_markers: &[(usize, usize)],
+) -> (String, bool) {
+    let tagged_strings: Vec<&TaggedString<Vec<RichAnnotation>>> =
+        rich_line.tagged_strings().collect();
+
+    let theme = get_theme();
+    let mut line_is_empty = true;
+
+    // Highlighted code is split into strings of each color, which are all preformatted.
+    let is_preformatted = !tagged_strings.is_empty()
+        && tagged_strings.iter().all(|tagged_string| {
+            matches!(
+                tagged_string.tag.first(),
+                Some(RichAnnotation::Preformat(_))
+            )
+        });
+    let line_length: usize = tagged_strings
+        .iter()
+        .map(|tagged_string| tagged_string.s.chars().count())
+        .sum();
+    let last_index = tagged_strings.len().saturating_sub(1);
+
+    let mut line_buffer = String::new();
+
+    for (i, tagged_string) in tagged_strings.into_iter().enumerate() {
+        let style = get_tag_style(&tagged_string.tag);
+
+        if !tagged_string.s.is_empty() {
+            line_is_empty = false;
+        }
 
-    let mut is_fragment_found = false;
-    let mut has_next_fragment = false;
+        line_buffer += style.as_str();
 
-    // If there is a fragment, determine current fragment offset and print
-    // everything until the next fragment.
-    if let Some(fragment) = fragment {
-        let (current_fragment, next_fragment) = get_fragment_bounds(&rich_page, fragment);
+        if let Some(href) = highlighted_href {
+            let is_highlighted = tagged_string
+                .tag
+                .iter()
+                .any(|tag| matches!(tag, RichAnnotation::Link(link) if link == href));
 
-        if let Some(line) = current_fragment {
-            current_fragment_line = line;
-            is_fragment_found = true;
+            if is_highlighted {
+                line_buffer += &Attribute::Reverse.to_string();
+            }
+        }
+
+        line_buffer += &tagged_string.s;
+
+        // Pad preformat to 80 characters for cool background.
+        if is_preformatted && i == last_index {
+            let padding_amount = width.saturating_sub(line_length);
+
+            for _ in 0..padding_amount {
+                line_buffer += " ";
+            }
+        }
+
+        line_buffer += &Style::Reset.to_string();
+
+        for (_, number) in link_markers.iter().filter(|(index, _)| *index == i) {
+            line_buffer += &format!("{}[{number}]{RESET}", theme.secondary);
         }
+    }
+
+    (line_buffer, line_is_empty)
+}
 
-        if let Some(line) = next_fragment {
-            next_fragment_line = line;
-            has_next_fragment = true;
+// Lines of the page that are printed. With a fragment, that's everything from the fragment to
+// the next one.
+struct PrintedLines {
+    first: usize,
+    last: Option<usize>,
+    is_fragment_found: bool,
+}
+
+fn get_printed_lines(
+    rich_page: &RichPage,
+    fragment: Option<&String>,
+) -> Result<PrintedLines, String> {
+    let mut printed_lines = PrintedLines {
+        first: 0,
+        last: None,
+        is_fragment_found: false,
+    };
+
+    if let Some(fragment) = fragment {
+        let (current_fragment, next_fragment) = get_fragment_bounds(rich_page, fragment);
+
+        if let Some(line) = current_fragment {
+            printed_lines.first = line;
+            printed_lines.last = next_fragment;
+            printed_lines.is_fragment_found = true;
         }
 
         // @@@: figure out better way to short-circuit search when it fails a test
         #[cfg(debug_assertions)]
-        if !is_fragment_found {
+        if !printed_lines.is_fragment_found {
             return Err(format!(
                 "debug: #{fragment} is specified but wasn't found in the page"
             ));
         }
     }
 
-    if is_fragment_found {
-        println!("{GRAYER}...{RESET}")
+    Ok(printed_lines)
+}
+
+// Tagged string indices and reference numbers of links that end on a line, by line number.
+type LinkMarkers = BTreeMap<usize, Vec<(usize, usize)>>;
+
+// Numbers links of the printed lines in order of appearance, giving links with the same target
+// the same number. Returns the targets and link markers for `format_rich_line`, by line.
+fn get_link_references(
+    rich_page: &RichPage,
+    printed_lines: &PrintedLines,
+) -> (Vec<String>, LinkMarkers) {
+    let mut references: Vec<String> = vec![];
+    let mut link_markers = LinkMarkers::new();
+
+    for link in get_page_links(rich_page) {
+        let is_after_last_line = matches!(printed_lines.last, Some(last) if link.first_line > last);
+
+        if link.first_line < printed_lines.first || is_after_last_line {
+            continue;
+        }
+
+        let number = if let Some(i) = references.iter().position(|href| *href == link.href) {
+            i + 1
+        } else {
+            references.push(link.href);
+            references.len()
+        };
+
+        link_markers
+            .entry(link.last_line)
+            .or_default()
+            .push((link.last_string, number));
     }
 
+    (references, link_markers)
+}
+
+// Displays where a link leads. Links that stay in the docset can be passed to `open` as is.
+fn format_link_target(current_item: Option<&str>, href: &str) -> String {
+    let resolved_link = current_item.and_then(|item| resolve_link(item, href));
+
+    match resolved_link {
+        Some((item, Some(fragment))) => format!("{item}#{fragment}"),
+        Some((item, None)) => item,
+        None => href.to_owned(),
+    }
+}
+
+// Prints the page as markdown, with links in place instead of the list of references.
+fn print_docset_file_as_markdown(
+    docset_path: &Path,
+    path: PathBuf,
+    fragment: Option<&String>,
+    current_item: Option<&str>,
+    docset_name: Option<&str>,
+) -> Result<bool, String> {
+    let mut html = String::new();
+    open_docset_file(docset_path, &path)?
+        .read_to_string(&mut html)
+        .map_err(|err| format!("Could not read `{}`: {err}", path.display()))?;
+
+    let (markdown, is_fragment_found) = html_to_markdown(&html, fragment.map(String::as_str));
+
+    if is_json_output() {
+        print_json(&PageJson {
+            docset: docset_name,
+            page: current_item,
+            fragment: fragment.map(String::as_str),
+            fragment_found: is_fragment_found,
+            path: path.display().to_string(),
+            text: markdown,
+            line_numbers: vec![],
+            links: vec![],
+        })?;
+    } else {
+        println!("{markdown}");
+    }
+
+    Ok(is_fragment_found)
+}
+
+// Lines to print, as positions in `page_lines` with whether they match `--grep`, and `None`
+// between lines that are not next to each other. `page_lines` are line numbers and text of the
+// printed part of the page.
+fn select_page_lines(
+    page_lines: &[(usize, String)],
+    page_options: &PageOptions,
+) -> Vec<Option<(usize, bool)>> {
+    let in_range: Vec<usize> = (0..page_lines.len())
+        .filter(|position| {
+            let number = page_lines[*position].0;
+            match page_options.line_range {
+                Some((first, last)) => number >= first && last.map_or(true, |last| number <= last),
+                None => true,
+            }
+        })
+        .collect();
+
+    let matches: Vec<bool> = match &page_options.grep {
+        Some(regex) => in_range
+            .iter()
+            .map(|position| regex.is_match(&page_lines[*position].1))
+            .collect(),
+        None => vec![false; in_range.len()],
+    };
+
+    let context = page_options.grep_context;
+
+    let mut selected = vec![];
+    let mut previous_number = None;
+
+    for (i, position) in in_range.iter().enumerate() {
+        let is_selected = page_options.grep.is_none()
+            || matches[i.saturating_sub(context)..=(i + context).min(in_range.len() - 1)]
+                .contains(&true);
+
+        if !is_selected {
+            continue;
+        }
+
+        let number = page_lines[*position].0;
+
+        if matches!(previous_number, Some(previous) if previous + 1 != number) {
+            selected.push(None);
+        }
+
+        selected.push(Some((*position, matches[i])));
+        previous_number = Some(number);
+    }
+
+    selected
+}
+
+// `current_item` is used to resolve relative links in the list of references. It's `None` for
+// files outside of docsets, and so is `docset_name`, which is used for JSON output and for the
+// language of code blocks. `docset_path` of such files is the directory they are in.
+pub(crate) fn print_docset_file(
+    docset_path: &Path,
+    path: PathBuf,
+    fragment: Option<&String>,
+    width: usize,
+    current_item: Option<&str>,
+    docset_name: Option<&str>,
+    page_options: &PageOptions,
+) -> Result<bool, String> {
+    if page_options.markdown {
+        return print_docset_file_as_markdown(
+            docset_path,
+            path,
+            fragment,
+            current_item,
+            docset_name,
+        );
+    }
+
+    let mut rich_page = render_docset_file(docset_path, &path, width)?;
+    highlight_page(&mut rich_page, docset_path, &path, docset_name);
+
+    let printed_lines = get_printed_lines(&rich_page, fragment)?;
+    let (references, link_markers) = get_link_references(&rich_page, &printed_lines);
+
+    let mut page_lines = vec![];
     let mut skipped_empty_lines = false;
 
     for (i, rich_line) in rich_page.iter().enumerate() {
-        if is_fragment_found && i < current_fragment_line {
+        if i < printed_lines.first {
             continue;
         }
-        if has_next_fragment && i > next_fragment_line {
+        if matches!(printed_lines.last, Some(last) if i > last) {
             break;
         }
 
-        let tagged_strings: Vec<&TaggedString<Vec<RichAnnotation>>> =
-            rich_line.tagged_strings().collect();
+        let text: String = rich_line
+            .tagged_strings()
+            .map(|tagged_string| tagged_string.s.as_str())
+            .collect();
+
+        if !text.is_empty() {
+            skipped_empty_lines = true;
+        }
+
+        if skipped_empty_lines {
+            page_lines.push((i + 1, text));
+        }
+    }
+
+    let selected_lines = select_page_lines(&page_lines, page_options);
+
+    // Line numbers, whether lines match `--grep`, and lines themselves.
+    let lines: Vec<Option<(usize, bool, String)>> = selected_lines
+        .iter()
+        .map(|selected_line| {
+            let (position, is_match) = (*selected_line)?;
+            let number = page_lines[position].0;
+
+            let markers = link_markers
+                .get(&(number - 1))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let (line_buffer, _) = format_rich_line(&rich_page[number - 1], width, None, markers);
+
+            Some((number, is_match, line_buffer))
+        })
+        .collect();
+
+    let is_filtered = page_options.line_range.is_some() || page_options.grep.is_some();
+
+    if is_filtered && lines.is_empty() {
+        match &page_options.grep {
+            Some(regex) => print_warning!("No lines match `{regex}`."),
+            None => print_warning!(
+                "No lines in this range, the page has {} lines.",
+                rich_page.len()
+            ),
+        }
+    }
+
+    let link_targets = references
+        .iter()
+        .map(|href| format_link_target(current_item, href));
+
+    if is_json_output() {
+        let printed_lines_iter = lines.iter().flatten();
+
+        print_json(&PageJson {
+            docset: docset_name,
+            page: current_item,
+            fragment: fragment.map(String::as_str),
+            fragment_found: printed_lines.is_fragment_found,
+            path: path.display().to_string(),
+            // Preformatted lines are padded for their background.
+            text: printed_lines_iter
+                .clone()
+                .map(|(_, _, line)| line.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            line_numbers: printed_lines_iter.map(|(number, _, _)| *number).collect(),
+            links: link_targets.collect(),
+        })?;
+
+        return Ok(printed_lines.is_fragment_found);
+    }
+
+    let theme = get_theme();
+
+    let show_numbers = page_options.line_numbers || page_options.grep.is_some();
+    let number_width = rich_page.len().to_string().len();
 
-        let mut line_is_empty = true;
-        let is_only_tag = tagged_strings.len() == 1;
+    if printed_lines.is_fragment_found {
+        println!("{}...{RESET}", theme.faint)
+    }
 
-        let mut line_buffer = String::new();
+    for line in lines.iter() {
+        let Some((number, is_match, line)) = line else {
+            println!("{}...{RESET}", theme.faint);
+            continue;
+        };
 
-        for tagged_string in tagged_strings {
-            let style = get_tag_style(&tagged_string.tag);
+        if show_numbers {
+            let color = if *is_match {
+                theme.matched
+            } else {
+                theme.secondary
+            };
+            print!("{color}{number:>number_width$}{RESET}  ");
+        }
 
-            if !tagged_string.s.is_empty() {
-                line_is_empty = false;
+        println!("{}", line);
+    }
+
+    if printed_lines.last.is_some() {
+        println!("{}...{RESET}", theme.faint)
+    }
+
+    // Links keep their numbers when only some lines are printed, so that `--follow` opens them.
+    let shown_references: Vec<usize> = lines
+        .iter()
+        .flatten()
+        .filter_map(|(number, _, _)| link_markers.get(&(number - 1)))
+        .flat_map(|markers| markers.iter().map(|(_, reference)| *reference))
+        .collect();
+
+    let is_reference_shown = |number: usize| !is_filtered || shown_references.contains(&number);
+
+    if references
+        .iter()
+        .enumerate()
+        .any(|(i, _)| is_reference_shown(i + 1))
+    {
+        println!();
+
+        for (i, target) in link_targets.enumerate() {
+            if is_reference_shown(i + 1) {
+                println!("{}[{}]{RESET} {target}", theme.secondary, i + 1);
             }
+        }
+    }
+
+    Ok(printed_lines.is_fragment_found)
+}
+
+// Resolves the `number`-th link from the list of references that `print_docset_file` prints for
+// the page.
+pub(crate) fn get_link_from_docset_file(
+    docset_path: &Path,
+    path: &Path,
+    current_item: &str,
+    fragment: Option<&String>,
+    width: usize,
+    number: usize,
+) -> Result<(String, Option<String>), String> {
+    let rich_page = render_docset_file(docset_path, path, width)?;
+
+    let printed_lines = get_printed_lines(&rich_page, fragment)?;
+    let (references, _) = get_link_references(&rich_page, &printed_lines);
+
+    let href = match number.checked_sub(1).and_then(|i| references.get(i)) {
+        Some(href) => href,
+        None if references.is_empty() => {
+            return Err(format!("`{current_item}` has no links to follow."));
+        }
+        None => {
+            return Err(format!(
+                "`--follow {number}` is out of bounds. `{current_item}` has {} links.",
+                references.len()
+            ));
+        }
+    };
+
+    resolve_link(current_item, href)
+        .ok_or_else(|| format!("Link {number} leads outside of the docset: {href}"))
+}
+
+// Line numbers of every fragment in the page, in order.
+pub(crate) fn get_fragment_lines(rich_page: &RichPage) -> Vec<usize> {
+    let mut lines = vec![];
+
+    for (line_number, tagged_line) in rich_page.iter().enumerate() {
+        let has_fragment = tagged_line
+            .iter()
+            .any(|element| matches!(element, FragmentStart(_)));
 
-            line_buffer += style.as_str();
-            line_buffer += &tagged_string.s;
+        if has_fragment {
+            lines.push(line_number);
+        }
+    }
+
+    lines
+}
+
+pub(crate) struct PageLink {
+    pub href: String,
+    pub first_line: usize,
+    // Links can wrap onto the following lines.
+    pub last_line: usize,
+    // Index of the tagged string of `last_line` where the link ends.
+    pub last_string: usize,
+}
+
+// Returns every link of the page in order of appearance.
+pub(crate) fn get_page_links(rich_page: &RichPage) -> Vec<PageLink> {
+    let mut links: Vec<PageLink> = vec![];
 
-            if is_only_tag {
-                // Pad preformat to 80 characters for cool background.
-                if let Some(RichAnnotation::Preformat(_)) = tagged_string.tag.first() {
-                    let padding_amount = width.saturating_sub(tagged_string.s.len());
+    // Link which ends the previous line, and may continue on the current one.
+    let mut open_href: Option<String> = None;
 
-                    for _ in 0..padding_amount {
-                        line_buffer += " ";
+    for (line_number, rich_line) in rich_page.iter().enumerate() {
+        let mut is_first_string = true;
+        let mut last_href = None;
+
+        for (string_index, tagged_string) in rich_line.tagged_strings().enumerate() {
+            let href = tagged_string.tag.iter().find_map(|tag| match tag {
+                RichAnnotation::Link(href) => Some(href),
+                _ => None,
+            });
+
+            if tagged_string.s.trim().is_empty() && is_first_string {
+                continue;
+            }
+
+            if let Some(href) = href {
+                let continues_previous_line = is_first_string && open_href.as_ref() == Some(href);
+                let continues_previous_string = last_href == Some(href);
+
+                if continues_previous_line || continues_previous_string {
+                    if let Some(link) = links.last_mut() {
+                        link.last_line = line_number;
+                        link.last_string = string_index;
                     }
+                } else {
+                    links.push(PageLink {
+                        href: href.clone(),
+                        first_line: line_number,
+                        last_line: line_number,
+                        last_string: string_index,
+                    });
                 }
             }
 
-            line_buffer += &Style::Reset.to_string();
+            last_href = href;
+            is_first_string = false;
         }
 
-        if !line_is_empty {
-            skipped_empty_lines = true;
-        }
+        open_href = last_href.cloned();
+    }
 
-        if skipped_empty_lines {
-            println!("{}", line_buffer);
+    links
+}
+
+// Resolves `href` of a link on the page `current_item` into an item and a fragment of the same
+// docset. Returns `None` for links to other websites.
+pub(crate) fn resolve_link(current_item: &str, href: &str) -> Option<(String, Option<String>)> {
+    if href.contains("://") || href.starts_with("mailto:") || href.starts_with("//") {
+        return None;
+    }
+
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment.to_owned())),
+        None => (href, None),
+    };
+    let path = path.split('?').next().unwrap_or(path);
+
+    if path.is_empty() {
+        return Some((current_item.to_owned(), fragment));
+    }
+
+    let mut segments: Vec<&str> = if path.starts_with('/') {
+        vec![]
+    } else {
+        let mut segments: Vec<&str> = current_item.split('/').collect();
+        segments.pop();
+        segments
+    };
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
         }
+    }
 
-        line_buffer.clear();
+    if path.ends_with('/') {
+        segments.push("index");
     }
 
-    if has_next_fragment {
-        println!("{GRAYER}...{RESET}")
+    let mut item = segments.join("/");
+
+    if let Some(stripped) = item.strip_suffix(&format!(".{DOC_PAGE_EXTENSION}")) {
+        item = stripped.to_owned();
     }
 
-    Ok(is_fragment_found)
+    if item.is_empty() {
+        return None;
+    }
+
+    Some((item, fragment))
+}
+
+// Wide enough to never wrap paragraphs, so each of them is rendered as a single line.
+pub(crate) const UNWRAPPED_WIDTH: usize = 10000;
+
+// Renders a page into lines of plain text, the same way it's displayed with `open`, but without
+// wrapping. Line numbers of this text are stored in the full-text index.
+pub(crate) fn render_page_text(docset_path: &Path, path: &Path) -> Result<Vec<String>, String> {
+    let rich_page = render_docset_file(docset_path, path, UNWRAPPED_WIDTH)?;
+
+    let lines = rich_page
+        .iter()
+        .map(|rich_line| {
+            rich_line
+                .tagged_strings()
+                .map(|tagged_string| tagged_string.s.as_str())
+                .collect::<String>()
+                .trim_end()
+                .to_owned()
+        })
+        .collect();
+
+    Ok(lines)
+}
+
+#[inline]
+pub(crate) fn get_page_path(docset_name: &str, page: &str) -> Result<PathBuf, String> {
+    let pages_path = get_docset_pages_path(docset_name)?;
+    let page_path_string = pages_path.join(page).display().to_string() + "." + DOC_PAGE_EXTENSION;
+    let page_path = PathBuf::from(page_path_string);
+
+    // Pages of Zeal docsets can have other extensions, which are kept in their paths.
+    if !is_docset_file(&pages_path, &page_path)
+        && is_docset_file(&pages_path, &pages_path.join(page))
+    {
+        return Ok(pages_path.join(page));
+    }
+
+    Ok(page_path)
 }
 
+// With `follow_link`, prints the page behind the n-th link of `page` instead.
 pub(crate) fn print_page_from_docset(
     docset_name: &str,
     page: &str,
     fragment: Option<&String>,
     width: usize,
+    follow_link: Option<usize>,
+    page_options: &PageOptions,
 ) -> Result<bool, String> {
-    let docset_path = get_docset_path(docset_name)?;
+    let pages_path = get_docset_pages_path(docset_name)?;
+    let page_path = get_page_path(docset_name, page)?;
 
-    let page_path_string = docset_path.join(page).display().to_string() + "." + DOC_PAGE_EXTENSION;
-    let page_path = PathBuf::from(page_path_string);
-
-    if !page_path.is_file() {
+    if !is_docset_file(&pages_path, &page_path) {
         let message = format!(
             "\
 No page matching `{page}`. Did you specify the name from `search` correctly?"
@@ -381,32 +1256,121 @@ No page matching `{page}`. Did you specify the name from `search` correctly?"
         return Err(message);
     }
 
-    print_docset_file(page_path, fragment, width)
+    if let Some(number) = follow_link {
+        let (item, fragment) =
+            get_link_from_docset_file(&pages_path, &page_path, page, fragment, width, number)?;
+
+        if !is_docset_file(&pages_path, &get_page_path(docset_name, &item)?) {
+            return Err(format!(
+                "Link {number} leads to `{item}`, which is not in `{docset_name}`."
+            ));
+        }
+
+        return print_page_from_docset(
+            docset_name,
+            &item,
+            fragment.as_ref(),
+            width,
+            None,
+            page_options,
+        );
+    }
+
+    print_docset_file(
+        &pages_path,
+        page_path,
+        fragment,
+        width,
+        Some(page),
+        Some(docset_name),
+        page_options,
+    )
+}
+
+// Item is a file path without a file extension which is relative to docset directory
+pub(crate) fn convert_path_to_item(path: PathBuf, docset_path: &Path) -> Result<String, String> {
+    let item = path
+        .strip_prefix(docset_path)
+        .map_err(|err| err.to_string())?
+        .with_extension("")
+        .display()
+        .to_string();
+
+    Ok(item)
 }
 
 #[inline]
+fn get_home_override() -> Option<PathBuf> {
+    std::env::var_os(HOME_ENV)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// Where the config is. Everything goes into `$DEDOC_HOME` when it's set.
+pub(crate) fn get_config_directory() -> Result<PathBuf, String> {
+    if let Some(home) = get_home_override() {
+        return Ok(home);
+    }
+
+    let data_dir = dirs::data_dir().ok_or_else(|| {
+        format!("Could not find the data directory of your system. Set `{HOME_ENV}` to choose where to keep docsets.")
+    })?;
+
+    Ok(data_dir.join(PROGRAM_NAME))
+}
+
+// Where `docs.json` and docsets are: `$DEDOC_HOME`, `data_dir` from the config, or the data
+// directory of your system.
 pub(crate) fn get_program_directory() -> Result<PathBuf, String> {
-    let data_dir: PathBuf = dirs::data_dir().unwrap();
-    let program_path = data_dir.join(PROGRAM_NAME);
-    Ok(program_path)
+    if let Some(home) = get_home_override() {
+        return Ok(home);
+    }
+
+    match get_config()?.data_dir {
+        Some(data_dir) => Ok(PathBuf::from(data_dir)),
+        None => get_config_directory(),
+    }
 }
 
-pub(crate) fn create_program_directory() -> ResultS {
-    let program_path = get_program_directory()?;
+// Where search results are cached: `$DEDOC_HOME/cache`, or the cache directory of your system.
+pub(crate) fn get_cache_directory() -> Result<PathBuf, String> {
+    if let Some(home) = get_home_override() {
+        return Ok(home.join("cache"));
+    }
+
+    match dirs::cache_dir() {
+        Some(cache_dir) => Ok(cache_dir.join(PROGRAM_NAME)),
+        None => Ok(get_program_directory()?.join("cache")),
+    }
+}
 
-    if !program_path.exists() {
-        create_dir_all(&program_path)
-            .map_err(|err| format!("Could not create `{}`: {err}", program_path.display()))?;
+pub(crate) fn create_dir_all_or_error(path: &Path) -> ResultS {
+    if !path.exists() {
+        create_dir_all(path)
+            .map_err(|err| format!("Could not create `{}`: {err}", path.display()))?;
     }
 
-    if program_path.is_dir() {
+    if path.is_dir() {
         Ok(())
     } else {
-        Err("Could not create {program_path:?}".to_string())
+        Err(format!("Could not create `{}`", path.display()))
     }
 }
 
-const WEEK: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+#[inline]
+pub(crate) fn create_program_directory() -> ResultS {
+    create_dir_all_or_error(&get_program_directory()?)
+}
+
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+// In days.
+#[inline]
+pub(crate) fn get_docs_json_max_age() -> Result<u64, String> {
+    Ok(get_config()?
+        .docs_json_max_age
+        .unwrap_or(DEFAULT_DOCS_JSON_MAX_AGE))
+}
 
 pub(crate) fn is_docs_json_old() -> Result<bool, String> {
     let program_path = get_program_directory()?;
@@ -422,7 +1386,9 @@ pub(crate) fn is_docs_json_old() -> Result<bool, String> {
         .duration_since(modified_time)
         .map_err(|err| err.to_string())?;
 
-    if elapsed_time > WEEK {
+    let max_age = DAY * get_docs_json_max_age()? as u32;
+
+    if elapsed_time > max_age {
         Ok(true)
     } else {
         Ok(false)
@@ -491,25 +1457,46 @@ pub(crate) fn is_docset_in_docs(docset_name: &String, docs: &[Docs]) -> SearchMa
     }
 }
 
+// Names of docsets from every root, without duplicates.
 pub(crate) fn get_local_docsets() -> Result<Vec<String>, String> {
-    let docsets_path = get_program_directory()?.join("docsets");
-    let docsets_dir_exists = docsets_path
-        .try_exists()
-        .map_err(|err| format!("Could not check `{}`: {err}", docsets_path.display()))?;
+    let mut result: Vec<String> = vec![];
 
-    let mut result = vec![];
+    for root in get_docset_roots()? {
+        for docset in get_root_docsets(&root)? {
+            if !result.contains(&docset) {
+                result.push(docset);
+            }
+        }
+    }
 
-    if !docsets_dir_exists {
+    Ok(result)
+}
+
+pub(crate) fn get_root_docsets(root: &DocsetRoot) -> Result<Vec<String>, String> {
+    let mut result: Vec<String> = vec![];
+
+    let root_exists = root
+        .path
+        .try_exists()
+        .map_err(|err| format!("Could not check `{}`: {err}", root.path.display()))?;
+
+    if !root_exists {
         return Ok(result);
     }
 
-    let docsets_dir = read_dir(docsets_path).map_err(|err| err.to_string())?;
+    let docsets_dir = read_dir(&root.path)
+        .map_err(|err| format!("Could not read `{}`: {err}", root.path.display()))?;
 
     for entry in docsets_dir {
         let entry = entry.map_err(|err| err.to_string())?;
 
         let holy_result_option_please_stop = entry.file_name().to_string_lossy().to_string();
 
+        // Staging directory.
+        if holy_result_option_please_stop.starts_with('.') {
+            continue;
+        }
+
         result.push(holy_result_option_please_stop);
     }
 
@@ -517,10 +1504,14 @@ pub(crate) fn get_local_docsets() -> Result<Vec<String>, String> {
 }
 
 #[inline]
-pub(crate) fn is_docset_downloaded(docset_name: &String) -> Result<bool, String> {
-    get_docset_path(docset_name)?
-        .try_exists()
-        .map_err(|err| format!("Could not check if `{docset_name}` exists: {err}"))
+pub(crate) fn is_docset_downloaded(docset_name: &str) -> Result<bool, String> {
+    Ok(find_docset_root(docset_name)?.is_some())
+}
+
+// Docsets that can be searched and opened: downloaded ones and ones from Zeal.
+#[inline]
+pub(crate) fn is_docset_available(docset_name: &str) -> Result<bool, String> {
+    Ok(is_docset_downloaded(docset_name)? || is_zeal_docset(docset_name)?)
 }
 
 #[inline]
@@ -529,8 +1520,304 @@ pub(crate) fn is_docs_json_exists() -> Result<bool, String> {
     Ok(docs_json_path.exists())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DocsetRootKind {
+    User,
+    System,
+}
+
+// A directory with docsets.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DocsetRoot {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: DocsetRootKind,
+}
+
+impl DocsetRoot {
+    // Looks at the permissions of the root, or of the directory it would be created in, without
+    // writing anything. Read-only mounts aren't seen here, so writing can still fail later.
+    pub(crate) fn is_writable(&self) -> bool {
+        let Some(existing_path) = self.path.ancestors().find(|path| path.exists()) else {
+            return false;
+        };
+
+        match existing_path.metadata() {
+            Ok(metadata) => metadata.is_dir() && !metadata.permissions().readonly(),
+            Err(_) => false,
+        }
+    }
+
+    // Installs are prepared here and moved into place when they're complete. It's inside of the
+    // root, so that moving is a rename.
+    #[inline]
+    pub(crate) fn get_staging_directory(&self) -> PathBuf {
+        self.path.join(STAGING_DIRECTORY_NAME)
+    }
+
+    pub(crate) fn check_writable(&self) -> ResultS {
+        if self.is_writable() {
+            Ok(())
+        } else {
+            Err(format!("`{}` is read-only.", self.path.display()))
+        }
+    }
+}
+
+pub(crate) const STAGING_DIRECTORY_NAME: &str = ".staging";
+
+#[cfg(target_family = "unix")]
+const DEFAULT_SYSTEM_ROOTS: &[&str] =
+    &["/usr/local/share/dedoc/docsets", "/usr/share/dedoc/docsets"];
+#[cfg(not(target_family = "unix"))]
+const DEFAULT_SYSTEM_ROOTS: &[&str] = &[];
+
+pub(crate) const SYSTEM_DIRS_ENV: &str = "DEDOC_SYSTEM_DIRS";
+
 #[inline]
+pub(crate) fn get_user_docsets_root() -> Result<DocsetRoot, String> {
+    Ok(DocsetRoot {
+        path: get_program_directory()?.join("docsets"),
+        kind: DocsetRootKind::User,
+    })
+}
+
+// `$DEDOC_SYSTEM_DIRS`, which is separated like `$PATH`, `system_dirs` from the config, or the
+// default ones.
+pub(crate) fn get_system_docsets_roots() -> Result<Vec<DocsetRoot>, String> {
+    let paths: Vec<PathBuf> = match std::env::var_os(SYSTEM_DIRS_ENV) {
+        Some(system_dirs) if !system_dirs.is_empty() => {
+            std::env::split_paths(&system_dirs).collect()
+        }
+        _ => match get_config()?.system_dirs {
+            Some(system_dirs) => system_dirs.iter().map(PathBuf::from).collect(),
+            None => DEFAULT_SYSTEM_ROOTS.iter().map(PathBuf::from).collect(),
+        },
+    };
+
+    let roots = paths
+        .into_iter()
+        .map(|path| DocsetRoot {
+            path,
+            kind: DocsetRootKind::System,
+        })
+        .collect();
+
+    Ok(roots)
+}
+
+// Docsets are looked up in the user root first, then in system roots.
+pub(crate) fn get_docset_roots() -> Result<Vec<DocsetRoot>, String> {
+    let mut roots = vec![get_user_docsets_root()?];
+    roots.append(&mut get_system_docsets_roots()?);
+    Ok(roots)
+}
+
+pub(crate) fn find_docset_root(docset_name: &str) -> Result<Option<DocsetRoot>, String> {
+    for root in get_docset_roots()? {
+        let docset_exists = root
+            .path
+            .join(docset_name)
+            .try_exists()
+            .map_err(|err| format!("Could not check if `{docset_name}` exists: {err}"))?;
+
+        if docset_exists {
+            return Ok(Some(root));
+        }
+    }
+
+    Ok(None)
+}
+
+// Path of the docset in the first root that has it, or in the user root.
 pub(crate) fn get_docset_path(docset_name: &str) -> Result<PathBuf, String> {
-    let docsets_path = get_program_directory()?.join("docsets");
-    Ok(docsets_path.join(docset_name))
+    let root = match find_docset_root(docset_name)? {
+        Some(root) => root,
+        None => get_user_docsets_root()?,
+    };
+
+    Ok(root.path.join(docset_name))
+}
+
+// Directory that pages of a docset are in, which is different for Zeal docsets.
+#[inline]
+pub(crate) fn get_docset_pages_path(docset_name: &str) -> Result<PathBuf, String> {
+    match get_zeal_documents_path(docset_name)? {
+        Some(documents_path) => Ok(documents_path),
+        None => get_docset_path(docset_name),
+    }
+}
+
+// Temporary directory for files of a test, which is removed when the test ends, even if it fails.
+#[cfg(test)]
+pub(crate) struct TestDirectory {
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(test)]
+impl TestDirectory {
+    // Names have to be different for tests that run at the same time.
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("dedoc_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_link() {
+        let resolve = |href| resolve_link("std/io/struct.bufreader", href);
+
+        assert_eq!(
+            resolve("#method.new"),
+            Some((
+                "std/io/struct.bufreader".to_string(),
+                Some("method.new".to_string())
+            ))
+        );
+        assert_eq!(
+            resolve("trait.read"),
+            Some(("std/io/trait.read".to_string(), None))
+        );
+        assert_eq!(
+            resolve("../vec/struct.vec.html#method.push"),
+            Some((
+                "std/vec/struct.vec".to_string(),
+                Some("method.push".to_string())
+            ))
+        );
+        assert_eq!(
+            resolve("./?search=x"),
+            Some(("std/io/index".to_string(), None))
+        );
+        assert_eq!(
+            resolve("/std/macro.vec"),
+            Some(("std/macro.vec".to_string(), None))
+        );
+        assert_eq!(resolve("https://www.rust-lang.org/"), None);
+    }
+
+    #[test]
+    fn test_page_links() {
+        let html = r#"<p><a href="first">one</a> text <a href="second">a link that is long enough to wrap</a></p>
+<p><a href="third">three</a></p>"#;
+
+        let rich_page = html2text::from_read_rich(html.as_bytes(), 24);
+        let links = get_page_links(&rich_page);
+
+        let hrefs: Vec<&str> = links.iter().map(|link| link.href.as_str()).collect();
+        assert_eq!(hrefs, ["first", "second", "third"]);
+
+        assert!(links[1].last_line > links[1].first_line);
+        assert!(links[2].first_line > links[1].last_line);
+    }
+
+    #[test]
+    fn test_link_references() {
+        let html = r#"<p><a href="a">one</a> <a href="b">two</a> <a href="a">one again</a></p>
+<p>Next:</p><h2 id="next">Next</h2><p><a href="c">three</a></p>"#;
+
+        let rich_page = html2text::from_read_rich(html.as_bytes(), 80);
+
+        let whole_page = get_printed_lines(&rich_page, None).unwrap();
+        let (references, link_markers) = get_link_references(&rich_page, &whole_page);
+
+        assert_eq!(references, ["a", "b", "c"]);
+
+        let numbers: Vec<usize> = link_markers
+            .values()
+            .flatten()
+            .map(|(_, number)| *number)
+            .collect();
+        assert_eq!(numbers, [1, 2, 1, 3]);
+
+        let fragment = "next".to_string();
+        let section = get_printed_lines(&rich_page, Some(&fragment)).unwrap();
+        let (references, _) = get_link_references(&rich_page, &section);
+
+        assert_eq!(references, ["c"]);
+    }
+
+    #[test]
+    fn test_docset_versions() {
+        assert_eq!(format_docset_version("", "1.72.0"), "1.72.0");
+        assert_eq!(format_docset_version("3.12", ""), "3.12");
+        assert_eq!(format_docset_version("", ""), "unknown");
+
+        let docs: Vec<Docs> = serde_json::from_str(
+            r#"[{"slug": "rust", "release": "1.72.0", "mtime": 200, "db_size": 0}]"#,
+        )
+        .unwrap();
+
+        let mut manifest = DocsetManifest::from_docs(&docs[0], false, false);
+        assert_eq!(manifest.release, "1.72.0");
+        assert!(!manifest.is_outdated(&docs[0]));
+
+        manifest.mtime = 100;
+        assert!(manifest.is_outdated(&docs[0]));
+    }
+
+    #[test]
+    fn test_select_page_lines() {
+        assert_eq!(parse_line_range("3:5"), Ok((3, Some(5))));
+        assert_eq!(parse_line_range("3:"), Ok((3, None)));
+        assert_eq!(parse_line_range(":5"), Ok((1, Some(5))));
+        assert_eq!(parse_line_range("4"), Ok((4, Some(4))));
+        assert!(parse_line_range("5:3").is_err());
+        assert!(parse_line_range("0:3").is_err());
+        assert!(parse_line_range(":").is_err());
+
+        let page_lines: Vec<(usize, String)> = ["a", "match", "b", "c", "d", "e", "match"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| (i + 3, text.to_string()))
+            .collect();
+
+        let grep = parse_page_options(false, false, "", "match", "1").unwrap();
+        assert_eq!(
+            select_page_lines(&page_lines, &grep),
+            [
+                Some((0, false)),
+                Some((1, true)),
+                Some((2, false)),
+                None,
+                Some((5, false)),
+                Some((6, true))
+            ]
+        );
+
+        let range = parse_page_options(false, true, "4:5", "", "").unwrap();
+        assert_eq!(
+            select_page_lines(&page_lines, &range),
+            [Some((1, false)), Some((2, false))]
+        );
+
+        assert!(parse_page_options(true, false, "", "match", "").is_err());
+    }
+
+    #[test]
+    fn test_terminal_colors() {
+        assert_eq!(get_color_256(255, 0, 0), Color::Byte(196));
+        assert_eq!(get_color_256(180, 142, 173), Color::Byte(139));
+        assert_eq!(get_color_256(128, 128, 128), Color::Byte(244));
+
+        // Muted colors of themes keep their hue.
+        assert_eq!(get_color_16(191, 97, 106), Color::Red);
+        assert_eq!(get_color_16(235, 203, 139), Color::BrightYellow);
+        assert_eq!(get_color_16(163, 190, 140), Color::Green);
+        assert_eq!(get_color_16(143, 161, 179), Color::Blue);
+        assert_eq!(get_color_16(192, 197, 206), Color::White);
+        assert_eq!(get_color_16(20, 20, 20), Color::Black);
+    }
 }
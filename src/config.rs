@@ -0,0 +1,390 @@
+use std::fs::{read_to_string, write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use toiletcli::flags;
+use toiletcli::flags::*;
+
+use crate::common::ResultS;
+use crate::common::{create_dir_all_or_error, get_config_directory, get_flag_error};
+use crate::common::{BOLD, GRAY, GREEN, PROGRAM_NAME, RESET};
+use crate::print_warning;
+use crate::theme::read_theme;
+
+pub(crate) const CONFIG_FILE_NAME: &str = "config.toml";
+
+pub(crate) const DEFAULT_MAX_WIDTH: usize = 120;
+pub(crate) const DEFAULT_DOCS_JSON_MAX_AGE: u64 = 7;
+
+// Settings from `config.toml`. Every setting is optional, and missing ones fall back to their
+// defaults.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) width: Option<usize>,
+    pub(crate) max_width: Option<usize>,
+    pub(crate) color: Option<String>,
+    pub(crate) theme: Option<String>,
+    pub(crate) docsets: Option<Vec<String>>,
+    // Base URL of a DevDocs mirror, or a local directory with the same layout.
+    pub(crate) mirror: Option<String>,
+    // Overrides for the URL of `docs.json` and the URL docsets are downloaded from.
+    pub(crate) docs_json_url: Option<String>,
+    pub(crate) documents_url: Option<String>,
+    // In days.
+    pub(crate) docs_json_max_age: Option<u64>,
+    pub(crate) data_dir: Option<String>,
+    pub(crate) system_dirs: Option<Vec<String>>,
+    pub(crate) zeal_dir: Option<String>,
+    pub(crate) storage: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum ValueKind {
+    Number,
+    String,
+    List,
+    Choice(&'static [&'static str]),
+    // Built-in themes, or themes from files, which are read to check them.
+    Theme,
+}
+
+struct ConfigKey {
+    name: &'static str,
+    kind: ValueKind,
+    description: &'static str,
+}
+
+const CONFIG_KEYS: &[ConfigKey] = &[
+    ConfigKey {
+        name: "width",
+        kind: ValueKind::Number,
+        description: "Page width, instead of the width of the terminal.",
+    },
+    ConfigKey {
+        name: "max_width",
+        kind: ValueKind::Number,
+        description: "Largest page width when using the width of the terminal. Defaults to 120.",
+    },
+    ConfigKey {
+        name: "color",
+        kind: ValueKind::Choice(&["on", "off", "auto"]),
+        description: "Whether to use colors, like `--color`.",
+    },
+    ConfigKey {
+        name: "theme",
+        kind: ValueKind::Theme,
+        description: "Color theme: `dark`, `light`, `none`, or the name of a file in `themes`.",
+    },
+    ConfigKey {
+        name: "docsets",
+        kind: ValueKind::List,
+        description: "Docsets to search when `search` is only given a query, separated by commas.",
+    },
+    ConfigKey {
+        name: "mirror",
+        kind: ValueKind::String,
+        description: "DevDocs mirror to download from, a URL or a local directory.",
+    },
+    ConfigKey {
+        name: "docs_json_url",
+        kind: ValueKind::String,
+        description: "URL of `docs.json`, if it's not on the mirror.",
+    },
+    ConfigKey {
+        name: "documents_url",
+        kind: ValueKind::String,
+        description: "URL to download docsets from, if they're not on the mirror.",
+    },
+    ConfigKey {
+        name: "docs_json_max_age",
+        kind: ValueKind::Number,
+        description: "Days after which `docs.json` is considered old. Defaults to 7.",
+    },
+    ConfigKey {
+        name: "data_dir",
+        kind: ValueKind::String,
+        description: "Directory for `docs.json` and docsets. The config stays in the default one.",
+    },
+    ConfigKey {
+        name: "system_dirs",
+        kind: ValueKind::List,
+        description: "Directories with docsets shared by every user, separated by commas.",
+    },
+    ConfigKey {
+        name: "zeal_dir",
+        kind: ValueKind::String,
+        description: "Directory with Zeal or Dash docsets.",
+    },
+    ConfigKey {
+        name: "storage",
+        kind: ValueKind::Choice(&["files", "pack"]),
+        description: "How `download` stores pages. `pack` keeps them in one compressed file.",
+    },
+];
+
+// The config is read once, and kept in sync when changed with `config set`.
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+
+// Doesn't move with `data_dir`, since it's where `data_dir` is read from.
+pub(crate) fn get_config_path() -> Result<PathBuf, String> {
+    Ok(get_config_directory()?.join(CONFIG_FILE_NAME))
+}
+
+// Configs are read as `Config`, or as a table that keeps keys which `Config` doesn't have, so that
+// they can be removed with `config unset`.
+fn read_config_as<T: DeserializeOwned>() -> Result<T, String> {
+    let config_path = get_config_path()?;
+
+    let contents = if config_path.exists() {
+        read_to_string(&config_path)
+            .map_err(|err| format!("Could not read `{}`: {err}", config_path.display()))?
+    } else {
+        String::new()
+    };
+
+    toml::from_str(&contents)
+        .map_err(|err| format!("Could not parse `{}`: {err}", config_path.display()))
+}
+
+pub(crate) fn get_config() -> Result<Config, String> {
+    let mut cached_config = CONFIG.lock().map_err(|err| err.to_string())?;
+
+    if let Some(config) = cached_config.as_ref() {
+        return Ok(config.clone());
+    }
+
+    // A broken config shouldn't stop every command, including `config` that can repair it.
+    let config = read_config_as::<Config>().unwrap_or_else(|err| {
+        print_warning!("{}\nDefault settings are used instead.", err.trim_end());
+        Config::default()
+    });
+    *cached_config = Some(config.clone());
+
+    Ok(config)
+}
+
+fn write_config(config: &Config) -> ResultS {
+    let config_path = get_config_path()?;
+
+    let contents =
+        toml::to_string(config).map_err(|err| format!("Could not serialize the config: {err}"))?;
+
+    create_dir_all_or_error(&get_config_directory()?)?;
+
+    write(&config_path, contents)
+        .map_err(|err| format!("Could not write `{}`: {err}", config_path.display()))?;
+
+    *CONFIG.lock().map_err(|err| err.to_string())? = Some(config.clone());
+
+    Ok(())
+}
+
+fn get_config_key(key: &str) -> Result<&'static ConfigKey, String> {
+    CONFIG_KEYS
+        .iter()
+        .find(|config_key| config_key.name == key)
+        .ok_or_else(|| format!("Unknown key `{key}`. Run `config list` to see available keys"))
+}
+
+fn get_config_table(config: &Config) -> Result<toml::Table, String> {
+    toml::Table::try_from(config).map_err(|err| format!("Could not serialize the config: {err}"))
+}
+
+fn format_config_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(string) => string.to_owned(),
+        toml::Value::Array(array) => array
+            .iter()
+            .map(format_config_value)
+            .collect::<Vec<String>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+fn parse_config_value(config_key: &ConfigKey, value: &str) -> Result<toml::Value, String> {
+    let key = config_key.name;
+
+    let value = match config_key.kind {
+        ValueKind::Number => {
+            let number = value
+                .parse::<u32>()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| format!("`{key}` requires a positive number, got `{value}`."))?;
+            toml::Value::Integer(number.into())
+        }
+        ValueKind::String => toml::Value::String(value.to_owned()),
+        ValueKind::List => toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_owned()))
+                .collect(),
+        ),
+        ValueKind::Choice(choices) => {
+            if !choices.contains(&value) {
+                return Err(format!(
+                    "`{key}` can be `{}`, got `{value}`.",
+                    choices.join("`/`")
+                ));
+            }
+            toml::Value::String(value.to_owned())
+        }
+        ValueKind::Theme => {
+            read_theme(value)?;
+            toml::Value::String(value.to_owned())
+        }
+    };
+
+    Ok(value)
+}
+
+fn set_config_value(
+    mut table: toml::Table,
+    key: &str,
+    value: Option<&str>,
+) -> Result<Config, String> {
+    match value {
+        Some(value) => {
+            let config_key = get_config_key(key)?;
+            table.insert(key.to_owned(), parse_config_value(config_key, value)?);
+        }
+        // Unknown keys can be unset as well, since they make the config unreadable.
+        None => {
+            if table.remove(key).is_none() {
+                get_config_key(key)?;
+            }
+        }
+    }
+
+    table
+        .try_into()
+        .map_err(|err| format!("Could not change `{key}`: {}.", err.to_string().trim_end()))
+}
+
+fn show_config_help() -> ResultS {
+    println!(
+        "\
+{GREEN}USAGE{RESET}
+    {BOLD}{PROGRAM_NAME} config{RESET} <list | get <key> | set <key> <value> | unset <key>>
+    Show or change settings, which are stored in `{CONFIG_FILE_NAME}` in the program directory.
+
+{GREEN}SUBCOMMANDS{RESET}
+    list                            Show all settings and their values.
+    get <key>                       Print the value of a setting.
+    set <key> <value>               Change a setting.
+    unset <key>                     Reset a setting to its default.
+
+{GREEN}OPTIONS{RESET}
+        --help                      Display help message."
+    );
+    Ok(())
+}
+
+pub(crate) fn config<Args>(mut args: Args) -> ResultS
+where
+    Args: Iterator<Item = String>,
+{
+    let mut flag_help;
+
+    let mut flags = flags![
+        flag_help: BoolFlag, ["--help"]
+    ];
+
+    let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
+    if flag_help || args.is_empty() {
+        return show_config_help();
+    }
+
+    let config = get_config()?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["list"] => {
+            let table = get_config_table(&config)?;
+
+            for config_key in CONFIG_KEYS {
+                match table.get(config_key.name) {
+                    Some(value) => println!("{} = {}", config_key.name, format_config_value(value)),
+                    None => println!("{GRAY}{} is not set{RESET}", config_key.name),
+                }
+                println!("    {GRAY}{}{RESET}", config_key.description);
+            }
+
+            println!("{GRAY}Stored in `{}`.{RESET}", get_config_path()?.display());
+        }
+        ["get", key] => {
+            get_config_key(key)?;
+
+            match get_config_table(&config)?.get(*key) {
+                Some(value) => println!("{}", format_config_value(value)),
+                None => print_warning!("`{key}` is not set."),
+            }
+        }
+        // The file is read again, so that a broken config isn't overwritten with defaults.
+        ["set", key, value @ ..] if !value.is_empty() => {
+            let config = set_config_value(read_config_as()?, key, Some(&value.join(" ")))?;
+            write_config(&config)?;
+        }
+        ["unset", key] => {
+            let config = set_config_value(read_config_as()?, key, None)?;
+            write_config(&config)?;
+        }
+        _ => {
+            return Err(format!(
+                "Invalid arguments `{}`. Try `config --help` for more information.",
+                args.join(" ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_config_value() {
+        let set = |config: &Config, key, value| {
+            set_config_value(get_config_table(config).unwrap(), key, value)
+        };
+
+        let config = Config::default();
+
+        let config = set(&config, "width", Some("100")).unwrap();
+        let config = set(&config, "docsets", Some("rust, tokio")).unwrap();
+        let config = set(&config, "mirror", Some("file:///srv/devdocs")).unwrap();
+        assert_eq!(config.width, Some(100));
+        assert_eq!(
+            config.docsets,
+            Some(vec!["rust".to_string(), "tokio".to_string()])
+        );
+
+        let contents = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&contents).unwrap(), config);
+
+        let config = set(&config, "width", None).unwrap();
+        assert_eq!(config.width, None);
+        assert_eq!(config.mirror.as_deref(), Some("file:///srv/devdocs"));
+
+        assert!(set(&config, "width", Some("wide")).is_err());
+        assert!(set(&config, "color", Some("sometimes")).is_err());
+        assert!(set(&config, "colour", Some("on")).is_err());
+        assert!(set(&config, "colour", None).is_err());
+
+        assert!(toml::from_str::<Config>("colour = \"on\"").is_err());
+
+        let table: toml::Table = toml::from_str("colour = \"on\"\nwidth = 100").unwrap();
+        assert!(set_config_value(table.clone(), "width", None).is_err());
+        let config = set_config_value(table, "colour", None).unwrap();
+        assert_eq!(config.width, Some(100));
+    }
+}
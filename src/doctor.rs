@@ -0,0 +1,447 @@
+use std::fs::{read_dir, remove_dir, remove_dir_all, remove_file, rename, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use toiletcli::flags;
+use toiletcli::flags::*;
+
+use crate::common::ResultS;
+use crate::common::{
+    deserialize_docs_json, get_docset_roots, get_flag_error, is_docs_json_exists,
+    read_docset_manifest_at,
+};
+use crate::common::{BOLD, GRAY, GREEN, PROGRAM_NAME, RESET, YELLOW};
+use crate::download::{install_docsets, DocsetInstall};
+use crate::index::{build_fulltext_index, FULLTEXT_INDEX_FILE_NAME};
+use crate::mirror::{Mirror, PART_EXTENSION};
+use crate::pack::{Pack, PACK_FILE_NAME};
+use crate::print_warning;
+use crate::search::IndexJson;
+
+fn show_doctor_help() -> ResultS {
+    println!(
+        "\
+{GREEN}USAGE{RESET}
+    {BOLD}{PROGRAM_NAME} doctor{RESET} [-fm] [docset1, docset2, ..]
+    Check downloaded docsets for broken installs, like ones that were interrupted. Checks every
+    local docset when none are specified.
+
+{GREEN}OPTIONS{RESET}
+    -f, --fix                       Repair found problems. Broken docsets are downloaded again.
+    -m, --mirror <url>              Download from a DevDocs mirror or a local directory.
+        --help                      Display help message."
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Problem {
+    MissingIndexJson,
+    InvalidIndexJson(String),
+    InvalidPack(String),
+    // Extraction of the docset didn't finish.
+    LeftoverDbJson,
+    MissingFulltextIndex,
+    // Parts of downloads and temporary files.
+    LeftoverFiles(Vec<PathBuf>),
+}
+
+impl Problem {
+    fn describe(&self) -> String {
+        match self {
+            Problem::MissingIndexJson => "`index.json` is missing.".to_string(),
+            Problem::InvalidIndexJson(err) => format!("`index.json` is broken: {err}"),
+            Problem::InvalidPack(err) => format!("Pack of pages is broken: {err}"),
+            Problem::LeftoverDbJson => "`db.json` was left by an interrupted install.".to_string(),
+            Problem::MissingFulltextIndex => "Full-text index is missing.".to_string(),
+            Problem::LeftoverFiles(paths) => format!("{} temporary files were left.", paths.len()),
+        }
+    }
+
+    #[inline]
+    fn needs_reinstall(&self) -> bool {
+        matches!(
+            self,
+            Problem::MissingIndexJson
+                | Problem::InvalidIndexJson(_)
+                | Problem::InvalidPack(_)
+                | Problem::LeftoverDbJson
+        )
+    }
+}
+
+// Leftovers in the staging directory, which `download` and `update` leave when they are interrupted.
+#[derive(Debug, PartialEq)]
+enum StagedLeftover {
+    // Downloads are kept to be resumed by `download`, but are also left when extraction crashed.
+    UnfinishedInstall,
+    // Previous version of a docset that was already replaced.
+    ReplacedVersion,
+    // Previous version of a docset that was moved aside, but not replaced, so it's the only copy.
+    MovedAsideVersion,
+}
+
+impl StagedLeftover {
+    fn describe(&self, staged_path: &Path) -> String {
+        match self {
+            StagedLeftover::UnfinishedInstall => {
+                format!("has an unfinished install in `{}`.", staged_path.display())
+            }
+            StagedLeftover::ReplacedVersion => {
+                format!(
+                    "has a replaced version left in `{}`.",
+                    staged_path.display()
+                )
+            }
+            StagedLeftover::MovedAsideVersion => {
+                format!(
+                    "was moved aside to `{}` and not put back.",
+                    staged_path.display()
+                )
+            }
+        }
+    }
+}
+
+fn check_staged(docsets_path: &Path, name: &str) -> StagedLeftover {
+    match name.strip_suffix(".previous") {
+        Some(docset_name) if docsets_path.join(docset_name).exists() => {
+            StagedLeftover::ReplacedVersion
+        }
+        Some(_) => StagedLeftover::MovedAsideVersion,
+        None => StagedLeftover::UnfinishedInstall,
+    }
+}
+
+// Docsets that were moved aside are put back in place, everything else is removed.
+fn repair_staged(docsets_path: &Path, staged_path: &Path, leftover: &StagedLeftover) -> ResultS {
+    if *leftover == StagedLeftover::MovedAsideVersion {
+        let file_name = staged_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let docset_name = file_name.strip_suffix(".previous").unwrap_or(&file_name);
+        let docset_path = docsets_path.join(docset_name);
+
+        return rename(staged_path, &docset_path).map_err(|err| {
+            format!(
+                "Could not move `{}` to `{}`: {err}",
+                staged_path.display(),
+                docset_path.display()
+            )
+        });
+    }
+
+    remove_dir_all(staged_path)
+        .map_err(|err| format!("Could not remove `{}`: {err}", staged_path.display()))
+}
+
+fn check_docset(docset_path: &Path) -> Result<Vec<Problem>, String> {
+    let mut problems = vec![];
+
+    let index_json_path = docset_path.join("index.json");
+
+    if !index_json_path.is_file() {
+        problems.push(Problem::MissingIndexJson);
+    } else {
+        let file = File::open(&index_json_path)
+            .map_err(|err| format!("Could not open `{}`: {err}", index_json_path.display()))?;
+
+        if let Err(err) = serde_json::from_reader::<_, IndexJson>(BufReader::new(file)) {
+            problems.push(Problem::InvalidIndexJson(err.to_string()));
+        }
+    }
+
+    let pack_path = docset_path.join(PACK_FILE_NAME);
+
+    if pack_path.exists() {
+        if let Err(err) = Pack::open(&pack_path) {
+            problems.push(Problem::InvalidPack(err));
+        }
+    }
+
+    if docset_path.join("db.json").exists() {
+        problems.push(Problem::LeftoverDbJson);
+    }
+
+    if !docset_path.join(FULLTEXT_INDEX_FILE_NAME).is_file() {
+        problems.push(Problem::MissingFulltextIndex);
+    }
+
+    let entries = read_dir(docset_path)
+        .map_err(|err| format!("Could not read `{}`: {err}", docset_path.display()))?;
+
+    let mut leftover_files = vec![];
+
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+
+        let is_leftover = matches!(
+            path.extension(),
+            Some(extension) if extension == PART_EXTENSION || extension == "tmp"
+        );
+
+        if is_leftover && path.is_file() {
+            leftover_files.push(path);
+        }
+    }
+
+    if !leftover_files.is_empty() {
+        leftover_files.sort();
+        problems.push(Problem::LeftoverFiles(leftover_files));
+    }
+
+    Ok(problems)
+}
+
+// Names of directories in `directory`, optionally only the ones in `names`. Previous versions that
+// are kept during installs count as the docset itself.
+fn get_directory_names(directory: &Path, names: &[String]) -> Result<Vec<String>, String> {
+    let mut directory_names = vec![];
+
+    if !directory.is_dir() {
+        return Ok(directory_names);
+    }
+
+    let entries = read_dir(directory)
+        .map_err(|err| format!("Could not read `{}`: {err}", directory.display()))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if name.starts_with('.') || !entry.path().is_dir() {
+            continue;
+        }
+
+        let docset_name = name.strip_suffix(".previous").unwrap_or(&name);
+
+        if names.is_empty() || names.iter().any(|name| name == docset_name) {
+            directory_names.push(name);
+        }
+    }
+
+    directory_names.sort();
+
+    Ok(directory_names)
+}
+
+fn remove_leftover_files(paths: &[PathBuf]) -> ResultS {
+    for path in paths {
+        remove_file(path).map_err(|err| format!("Could not remove `{}`: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn doctor<Args>(mut args: Args) -> ResultS
+where
+    Args: Iterator<Item = String>,
+{
+    let mut flag_fix;
+    let mut flag_mirror;
+    let mut flag_help;
+
+    let mut flags = flags![
+        flag_fix: BoolFlag,      ["-f", "--fix"],
+        flag_mirror: StringFlag, ["-m", "--mirror"],
+        flag_help: BoolFlag,     ["--help"]
+    ];
+
+    let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
+    if flag_help {
+        return show_doctor_help();
+    }
+
+    let mut problem_count = 0;
+    let mut installs = vec![];
+
+    for root in get_docset_roots()? {
+        let mut docsets = get_directory_names(&root.path, &args)?;
+        let staged = get_directory_names(&root.get_staging_directory(), &args)?;
+
+        if docsets.is_empty() && staged.is_empty() {
+            continue;
+        }
+
+        println!("Checking `{}`...", root.path.display());
+
+        let can_fix = flag_fix && root.is_writable();
+
+        if flag_fix && !can_fix {
+            print_warning!(
+                "`{}` is read-only, so problems in it can't be repaired.",
+                root.path.display()
+            );
+        }
+
+        for name in staged {
+            let staged_path = root.get_staging_directory().join(&name);
+            let leftover = check_staged(&root.path, &name);
+            let docset_name = name.strip_suffix(".previous").unwrap_or(&name);
+
+            println!(
+                "{YELLOW}{docset_name}{RESET} {}",
+                leftover.describe(&staged_path)
+            );
+            problem_count += 1;
+
+            if can_fix {
+                repair_staged(&root.path, &staged_path, &leftover)?;
+            }
+        }
+
+        // Docsets that were put back are checked like the others.
+        if can_fix {
+            let _ = remove_dir(root.get_staging_directory());
+            docsets = get_directory_names(&root.path, &args)?;
+        }
+
+        for docset in docsets {
+            let docset_path = root.path.join(&docset);
+            let problems = check_docset(&docset_path)?;
+
+            if problems.is_empty() {
+                println!("{GREEN}{docset}{RESET} is fine.");
+                continue;
+            }
+
+            println!("{YELLOW}{docset}{RESET} has problems:");
+            for problem in problems.iter() {
+                println!("    {GRAY}{}{RESET}", problem.describe());
+            }
+            problem_count += problems.len();
+
+            if !can_fix {
+                continue;
+            }
+
+            if problems.iter().any(Problem::needs_reinstall) {
+                let manifest = read_docset_manifest_at(&docset_path).ok().flatten();
+                installs.push(DocsetInstall {
+                    docset_name: docset,
+                    root: root.clone(),
+                    prerender: matches!(&manifest, Some(manifest) if manifest.prerendered),
+                    pack: matches!(&manifest, Some(manifest) if manifest.packed),
+                });
+                continue;
+            }
+
+            for problem in problems.iter() {
+                match problem {
+                    Problem::LeftoverFiles(paths) => remove_leftover_files(paths)?,
+                    Problem::MissingFulltextIndex => {
+                        println!("Indexing `{docset}`...");
+                        build_fulltext_index(&docset_path)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if problem_count == 0 {
+        println!("{BOLD}No problems were found{RESET}.");
+        return Ok(());
+    }
+
+    if !flag_fix {
+        match problem_count {
+            1 => println!("{BOLD}Found 1 problem{RESET}. Run `doctor --fix` to repair it."),
+            n => println!("{BOLD}Found {n} problems{RESET}. Run `doctor --fix` to repair them."),
+        }
+        return Ok(());
+    }
+
+    if !installs.is_empty() {
+        if !is_docs_json_exists()? {
+            return Err("The list of available documents has not yet been downloaded. Please run `fetch` first.".to_string());
+        }
+
+        let docs = deserialize_docs_json()?;
+        let mirror = Mirror::resolve(&flag_mirror)?;
+
+        // Docsets that can't be downloaded again are left as they are.
+        installs.retain(|install| {
+            let is_available = docs.iter().any(|entry| entry.slug == install.docset_name);
+            if !is_available {
+                print_warning!(
+                    "`{0}` is no longer available, so it can't be repaired. Run `remove {0}` to remove it.",
+                    install.docset_name
+                );
+            }
+            is_available
+        });
+
+        if !installs.is_empty() {
+            install_docsets(&installs, &docs, &mirror)?;
+        }
+    }
+
+    println!("{BOLD}Repair has finished{RESET}.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TestDirectory;
+
+    #[test]
+    fn test_check_docset() {
+        let test_directory = TestDirectory::new("doctor");
+        let docset_path = &test_directory.path;
+
+        std::fs::write(docset_path.join("db.json"), "{}").unwrap();
+        std::fs::write(docset_path.join("index.json.1700000000.part"), "{").unwrap();
+        let broken = check_docset(docset_path);
+
+        std::fs::remove_file(docset_path.join("db.json")).unwrap();
+        std::fs::remove_file(docset_path.join("index.json.1700000000.part")).unwrap();
+        std::fs::write(docset_path.join("index.json"), r#"{"entries": []}"#).unwrap();
+        std::fs::write(docset_path.join(FULLTEXT_INDEX_FILE_NAME), "").unwrap();
+        let fine = check_docset(docset_path);
+
+        std::fs::write(docset_path.join("index.json"), r#"{"entries": ["#).unwrap();
+        let invalid = check_docset(docset_path);
+
+        let broken = broken.unwrap();
+        assert_eq!(broken.len(), 4);
+        assert_eq!(broken[0], Problem::MissingIndexJson);
+        assert_eq!(broken[1], Problem::LeftoverDbJson);
+        assert_eq!(broken[2], Problem::MissingFulltextIndex);
+        assert!(matches!(&broken[3], Problem::LeftoverFiles(paths) if paths.len() == 1));
+
+        assert_eq!(fine.unwrap(), vec![]);
+
+        let invalid = invalid.unwrap();
+        assert!(matches!(invalid.as_slice(), [Problem::InvalidIndexJson(_)]));
+    }
+
+    #[test]
+    fn test_repair_staged() {
+        let test_directory = TestDirectory::new("staged");
+        let docsets_path = &test_directory.path;
+        let staged_path = docsets_path.join(".staging").join("rust.previous");
+        std::fs::create_dir_all(&staged_path).unwrap();
+        std::fs::write(staged_path.join("index.json"), "{}").unwrap();
+
+        let moved_aside = check_staged(docsets_path, "rust.previous");
+        let put_back = repair_staged(docsets_path, &staged_path, &moved_aside)
+            .map(|_| docsets_path.join("rust").join("index.json").is_file());
+
+        std::fs::create_dir_all(&staged_path).unwrap();
+        let replaced = check_staged(docsets_path, "rust.previous");
+        let removed = repair_staged(docsets_path, &staged_path, &replaced)
+            .map(|_| !staged_path.exists() && docsets_path.join("rust").exists());
+
+        assert_eq!(moved_aside, StagedLeftover::MovedAsideVersion);
+        assert_eq!(put_back, Ok(true));
+        assert_eq!(replaced, StagedLeftover::ReplacedVersion);
+        assert_eq!(removed, Ok(true));
+        assert_eq!(
+            check_staged(docsets_path, "rust"),
+            StagedLeftover::UnfinishedInstall
+        );
+    }
+}
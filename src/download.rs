@@ -1,8 +1,9 @@
-use std::fs::{create_dir_all, remove_file, File};
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
-
-use attohttpc::get;
+use std::fs::{create_dir_all, read_dir, remove_dir, remove_dir_all, remove_file, rename, File};
+use std::io::{stdout, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
 use serde::de::{Error, MapAccess, Visitor};
 use serde::Deserializer;
@@ -11,149 +12,335 @@ use toiletcli::flags;
 use toiletcli::flags::*;
 
 use crate::common::{
-    deserialize_docs_json, get_docset_path, get_flag_error, is_docs_json_exists,
-    is_docset_downloaded, is_docset_in_docs_or_print_warning,
+    deserialize_docs_json, find_docset_root, get_flag_error, get_system_docsets_roots,
+    get_user_docsets_root, is_docs_json_exists, is_docset_in_docs_or_print_warning,
+    write_docset_manifest,
 };
-use crate::common::{Docs, ResultS};
+use crate::common::{Docs, DocsetManifest, DocsetRoot, DocsetRootKind, ResultS};
 use crate::common::{
-    BOLD, DEFAULT_DB_JSON_LINK, DEFAULT_USER_AGENT, GREEN, PROGRAM_NAME, RESET, VERSION,
+    BOLD, GREEN, PRERENDERED_PAGE_EXTENSION, PROGRAM_NAME, RESET, UNWRAPPED_WIDTH,
 };
+use crate::config::get_config;
+use crate::index::build_fulltext_index;
+use crate::mirror::{download_file, DownloadProgress, Mirror, PART_EXTENSION};
+use crate::pack::{PackWriter, PACK_FILE_NAME};
+use crate::prerender::serialize_prerendered_page;
 use crate::print_warning;
+use crate::sanitize::{sanitize_html, DEFAULT_SANITIZE_RULES};
 
 fn show_download_help() -> ResultS {
     println!(
         "\
 {GREEN}USAGE{RESET}
-    {BOLD}{PROGRAM_NAME} download{RESET} [-f] <docset1> [docset2, ..]
+    {BOLD}{PROGRAM_NAME} download{RESET} [-fpzms] <docset1> [docset2, ..]
     Download a docset. Available docsets can be displayed using `list`.
 
 {GREEN}OPTIONS{RESET}
     -f, --force                     Force the download and overwrite files.
+    -p, --prerender                 Also store pages pre-rendered, which makes opening them faster.
+    -z, --pack                      Store pages in one compressed file, which takes less space.
+    -m, --mirror <url>              Download from a DevDocs mirror or a local directory.
+    -s, --system                    Install for every user, into the first system directory.
         --help                      Display help message."
     );
     Ok(())
 }
 
-fn download_db_and_index_json_with_progress(docset_name: &String, docs: &[Docs]) -> ResultS {
-    let user_agent = format!("{DEFAULT_USER_AGENT}/{VERSION}");
+// Docsets are downloaded this many files at a time.
+const CONCURRENT_DOWNLOADS: usize = 4;
 
-    for entry in docs.iter() {
-        if docset_name == &entry.slug {
-            let docset_path = get_docset_path(docset_name)?;
+const DOCSET_FILES: [&str; 2] = ["db.json", "index.json"];
 
-            if !docset_path.exists() {
-                create_dir_all(&docset_path).map_err(|err| {
-                    format!("Cannot create `{}` directory: {err}", docset_path.display())
-                })?;
-            }
+// What `install_docsets` installs, and where.
+pub(crate) struct DocsetInstall {
+    pub(crate) docset_name: String,
+    pub(crate) root: DocsetRoot,
+    pub(crate) prerender: bool,
+    pub(crate) pack: bool,
+}
 
-            for (file_name, i) in [("db.json", 1), ("index.json", 2)] {
-                let file_path = docset_path.join(file_name);
+// Files of other versions can't be resumed or reused, so the version is a part of the name.
+#[inline]
+fn get_downloaded_file_name(file_name: &str, mtime: u64) -> String {
+    format!("{file_name}.{mtime}")
+}
 
-                let file = File::create(&file_path)
-                    .map_err(|err| format!("Could not create `{}`: {err}", file_path.display()))?;
+#[inline]
+fn get_part_file_name(file_name: &str, mtime: u64) -> String {
+    format!(
+        "{}.{PART_EXTENSION}",
+        get_downloaded_file_name(file_name, mtime)
+    )
+}
 
-                let download_link = format!(
-                    "{DEFAULT_DB_JSON_LINK}/{docset_name}/{}?{}",
-                    file_name, entry.mtime
-                );
+// Keeps downloaded files and parts of the same version of the docset, which will be reused and
+// resumed, and removes everything else left from an earlier install.
+fn prepare_staging_directory(staging_path: &Path, mtime: u64) -> ResultS {
+    create_dir_all(staging_path).map_err(|err| {
+        format!(
+            "Cannot create `{}` directory: {err}",
+            staging_path.display()
+        )
+    })?;
 
-                let response = get(&download_link)
-                    .header_append("user-agent", &user_agent)
-                    .send()
-                    .map_err(|err| format!("Could not GET {download_link}: {err}"))?;
+    let kept_file_names: Vec<String> = DOCSET_FILES
+        .iter()
+        .flat_map(|file_name| {
+            [
+                get_downloaded_file_name(file_name, mtime),
+                get_part_file_name(file_name, mtime),
+            ]
+        })
+        .collect();
+
+    let entries = read_dir(staging_path)
+        .map_err(|err| format!("Could not read `{}`: {err}", staging_path.display()))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let path = entry.path();
+
+        if path.is_file()
+            && kept_file_names.contains(&entry.file_name().to_string_lossy().to_string())
+        {
+            continue;
+        }
 
-                let mut file_writer = BufWriter::new(file);
-                let mut response_reader = BufReader::new(response);
+        let result = if path.is_dir() {
+            remove_dir_all(&path)
+        } else {
+            remove_file(&path)
+        };
+        result.map_err(|err| format!("Could not remove `{}`: {err}", path.display()))?;
+    }
 
-                let mut buffer = [0; 1024 * 4];
-                let mut file_size = 0;
+    Ok(())
+}
 
-                while let Ok(size) = response_reader.read(&mut buffer) {
-                    if size == 0 {
-                        break;
-                    }
+// Gives downloaded files the names that extraction and the docset use.
+fn rename_downloaded_files(staging_path: &Path, mtime: u64) -> ResultS {
+    for file_name in DOCSET_FILES {
+        let downloaded_path = staging_path.join(get_downloaded_file_name(file_name, mtime));
+        let path = staging_path.join(file_name);
+
+        rename(&downloaded_path, &path).map_err(|err| {
+            format!(
+                "Could not move `{}` to `{}`: {err}",
+                downloaded_path.display(),
+                path.display()
+            )
+        })?;
+    }
 
-                    file_writer
-                        .write(&buffer[..size])
-                        .map_err(|err| format!("Could not download file: {err}"))?;
+    Ok(())
+}
 
-                    file_size += size;
+struct FileDownload {
+    install_index: usize,
+    url: String,
+    path: PathBuf,
+    part_path: PathBuf,
+    progress: DownloadProgress,
+}
 
-                    print!("\rReceived {file_size} bytes, file {i} of 2...");
-                }
-                println!();
-            }
-        }
+fn prepare_file_downloads(
+    install_index: usize,
+    install: &DocsetInstall,
+    docs: &[Docs],
+    mirror: &Mirror,
+) -> Result<Vec<FileDownload>, String> {
+    let docset_name = &install.docset_name;
+
+    install.root.check_writable()?;
+
+    let entry = docs
+        .iter()
+        .find(|entry| entry.slug == *docset_name)
+        .ok_or_else(|| format!("`{docset_name}` is not available."))?;
+
+    let staging_path = install.root.get_staging_directory().join(docset_name);
+    prepare_staging_directory(&staging_path, entry.mtime)?;
+
+    // Files that were downloaded completely before aren't downloaded again.
+    let file_downloads = DOCSET_FILES
+        .iter()
+        .map(|file_name| FileDownload {
+            install_index,
+            url: mirror.get_docset_file_url(docset_name, file_name, entry.mtime),
+            path: staging_path.join(get_downloaded_file_name(file_name, entry.mtime)),
+            part_path: staging_path.join(get_part_file_name(file_name, entry.mtime)),
+            progress: DownloadProgress::default(),
+        })
+        .filter(|file_download| !file_download.path.is_file())
+        .collect();
+
+    Ok(file_downloads)
+}
+
+fn print_download_progress(file_downloads: &[FileDownload], finished_downloads: usize) {
+    let mut received = 0;
+    let mut total = Some(0);
+
+    for file_download in file_downloads {
+        received += file_download.progress.received.load(Ordering::Relaxed);
+
+        total = match file_download.progress.total.load(Ordering::Relaxed) {
+            0 => None,
+            file_total => total.map(|total| total + file_total),
+        };
     }
 
-    Ok(())
-}
+    let file_count = file_downloads.len();
 
-// Remove class="...", title="...", data-language="..." attributes from HTML tags to reduce size.
-fn sanitize_html_line(html_line: String) -> String {
-    enum State {
-        Default,
-        InTag,
-        InKey,
-        InValue,
+    match total {
+        Some(total) => print!(
+            "\rReceived {received} of {total} bytes, {finished_downloads} of {file_count} files..."
+        ),
+        None => {
+            print!("\rReceived {received} bytes, {finished_downloads} of {file_count} files...")
+        }
     }
+    let _ = stdout().flush();
+}
 
-    let length = html_line.len();
-    let bytes = html_line.as_bytes();
+// Downloads `db.json` and `index.json` of every docset at once. Returns the result for each
+// docset.
+fn download_docsets_with_progress(
+    installs: &[DocsetInstall],
+    docs: &[Docs],
+    mirror: &Mirror,
+) -> Vec<ResultS> {
+    let mut results = vec![];
+    let mut file_downloads = vec![];
+
+    for (i, install) in installs.iter().enumerate() {
+        match prepare_file_downloads(i, install, docs, mirror) {
+            Ok(docset_file_downloads) => {
+                file_downloads.extend(docset_file_downloads);
+                results.push(Ok(()));
+            }
+            Err(err) => results.push(Err(err)),
+        }
+    }
 
-    let mut sanitized_line = String::new();
+    if file_downloads.is_empty() {
+        return results;
+    }
 
-    let mut state = State::Default;
-    let mut position = 0;
+    let next_download = AtomicUsize::new(0);
+    let finished_downloads = AtomicUsize::new(0);
+
+    let file_results = thread::scope(|scope| {
+        let workers: Vec<_> = (0..CONCURRENT_DOWNLOADS.min(file_downloads.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut file_results = vec![];
+
+                    while let Some(file_download) =
+                        file_downloads.get(next_download.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let result = download_file(
+                            &file_download.url,
+                            &file_download.path,
+                            &file_download.part_path,
+                            &file_download.progress,
+                        );
+                        finished_downloads.fetch_add(1, Ordering::Relaxed);
+                        file_results.push((file_download.install_index, result));
+                    }
 
-    let html_line_chars = html_line.chars();
+                    file_results
+                })
+            })
+            .collect();
 
-    for ch in html_line_chars {
-        match state {
-            State::Default => {
-                if ch == '<' {
-                    state = State::InTag;
-                }
-                sanitized_line.push(ch);
-            }
-            State::InTag => match ch {
-                'd' if position + 15 < length
-                    && bytes[position..position + 15] == *b"data-language=\"" =>
-                {
-                    state = State::InKey;
-                }
-                't' if position + 7 < length && bytes[position..position + 7] == *b"title=\"" => {
-                    state = State::InKey;
-                }
-                'c' if position + 7 < length && bytes[position..position + 7] == *b"class=\"" => {
-                    state = State::InKey;
-                }
-                '>' => {
-                    state = State::Default;
-                    sanitized_line.push(ch);
-                }
-                _ => sanitized_line.push(ch),
-            },
-            State::InKey => {
-                if ch == '\"' {
-                    state = State::InValue;
+        while !workers.iter().all(|worker| worker.is_finished()) {
+            print_download_progress(&file_downloads, finished_downloads.load(Ordering::Relaxed));
+            thread::sleep(Duration::from_millis(100));
+        }
+        print_download_progress(&file_downloads, finished_downloads.load(Ordering::Relaxed));
+        println!();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|_| "Download thread has panicked.".to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()
+    });
+
+    match file_results {
+        Ok(file_results) => {
+            for (install_index, result) in file_results.into_iter().flatten() {
+                if let (Ok(()), Err(err)) = (&results[install_index], result) {
+                    results[install_index] = Err(err);
                 }
             }
-            State::InValue => {
-                if ch == '\"' {
-                    state = State::InTag;
+        }
+        Err(err) => results
+            .iter_mut()
+            .for_each(|result| *result = Err(err.clone())),
+    }
+
+    results
+}
+
+// Where extracted pages go: a file for each of them, or one pack.
+enum PageWriter {
+    Files(PathBuf),
+    Pack(PackWriter),
+}
+
+impl PageWriter {
+    fn create(docset_path: &Path, pack: bool) -> Result<Self, String> {
+        if pack {
+            let pack_writer = PackWriter::create(&docset_path.join(PACK_FILE_NAME))?;
+            Ok(PageWriter::Pack(pack_writer))
+        } else {
+            Ok(PageWriter::Files(docset_path.to_path_buf()))
+        }
+    }
+
+    // `relative_path` is relative to the docset directory.
+    fn write_page(&mut self, relative_path: &Path, contents: &[u8]) -> ResultS {
+        match self {
+            PageWriter::Files(docset_path) => {
+                let file_path = docset_path.join(relative_path);
+
+                if let Some(parent) = file_path.parent() {
+                    create_dir_all(parent)
+                        .map_err(|err| format!("Could not create `{}`: {err}", parent.display()))?;
                 }
+
+                let file = File::create(&file_path)
+                    .map_err(|err| format!("Could not create `{}`: {err}", file_path.display()))?;
+                let mut writer = BufWriter::new(file);
+
+                writer
+                    .write_all(contents)
+                    .and_then(|_| writer.flush())
+                    .map_err(|err| format!("Could not write to `{}`: {err}", file_path.display()))
             }
+            PageWriter::Pack(pack_writer) => pack_writer.add_file(relative_path, contents),
         }
-
-        position += ch.len_utf8();
     }
 
-    sanitized_line
+    fn finish(self) -> ResultS {
+        match self {
+            PageWriter::Files(_) => Ok(()),
+            PageWriter::Pack(pack_writer) => pack_writer.finish(),
+        }
+    }
 }
 
-fn build_docset_from_map_with_progress<'de, M>(docset_name: &str, mut map: M) -> ResultS
+fn build_docset_from_map_with_progress<'de, M>(
+    docset_path: &Path,
+    prerender: bool,
+    pack: bool,
+    mut map: M,
+) -> ResultS
 where
     M: MapAccess<'de>,
 {
@@ -167,7 +354,8 @@ where
             .collect::<String>()
     }
 
-    let docset_path = get_docset_path(docset_name)?;
+    let mut page_writer = PageWriter::create(docset_path, pack)?;
+
     let mut unpacked_amount = 1;
 
     while let Some((file_path, contents)) = map
@@ -176,27 +364,23 @@ where
     {
         #[cfg(target_family = "windows")]
         let file_path = sanitize_filename_for_windows(file_path);
-        let file_path = PathBuf::from(file_path);
-
-        if let Some(parent) = file_path.parent() {
-            create_dir_all(docset_path.join(parent))
-                .map_err(|err| format!("Could not create `{}`: {err}", parent.display()))?;
-        }
 
-        let mut file_name_html = file_path.as_os_str().to_owned();
+        let mut file_name_html = PathBuf::from(file_path).into_os_string();
         file_name_html.push(".html");
 
-        let file_path = docset_path.join(&file_name_html);
+        let file_path = PathBuf::from(file_name_html);
 
-        let file = File::create(&file_path)
-            .map_err(|err| format!("Could not create `{}`: {err}", file_path.display()))?;
-        let mut writer = BufWriter::new(file);
+        let sanitized_contents = sanitize_html(&contents, &DEFAULT_SANITIZE_RULES);
 
-        let sanitized_contents = sanitize_html_line(contents);
+        page_writer.write_page(&file_path, sanitized_contents.trim().as_bytes())?;
 
-        writer
-            .write_all(sanitized_contents.trim().as_bytes())
-            .map_err(|err| format!("Could not write to `{}`: {err}", file_path.display()))?;
+        if prerender {
+            let rich_page =
+                html2text::from_read_rich(sanitized_contents.trim().as_bytes(), UNWRAPPED_WIDTH);
+            let prerendered_path = file_path.with_extension(PRERENDERED_PAGE_EXTENSION);
+
+            page_writer.write_page(&prerendered_path, &serialize_prerendered_page(&rich_page)?)?;
+        }
 
         print!("Unpacked {unpacked_amount} files...\r");
 
@@ -204,11 +388,14 @@ where
     }
     println!();
 
-    Ok(())
+    page_writer.finish()
 }
 
 struct FileVisitor {
     docset_name: String,
+    docset_path: PathBuf,
+    prerender: bool,
+    pack: bool,
 }
 
 impl<'de> Visitor<'de> for FileVisitor {
@@ -222,18 +409,23 @@ impl<'de> Visitor<'de> for FileVisitor {
     where
         M: MapAccess<'de>,
     {
-        build_docset_from_map_with_progress(&self.docset_name, map).map_err(|err| {
-            Error::custom(format!(
-                "Error while building `{}`: {err}",
-                self.docset_name
-            ))
-        })?;
+        build_docset_from_map_with_progress(&self.docset_path, self.prerender, self.pack, map)
+            .map_err(|err| {
+                Error::custom(format!(
+                    "Error while building `{}`: {err}",
+                    self.docset_name
+                ))
+            })?;
         Ok(())
     }
 }
 
-fn build_docset_from_db_json(docset_name: &String) -> ResultS {
-    let docset_path = get_docset_path(docset_name)?;
+fn build_docset_from_db_json(
+    docset_name: &String,
+    docset_path: &Path,
+    prerender: bool,
+    pack: bool,
+) -> ResultS {
     let db_json_path = docset_path.join("db").with_extension("json");
 
     let file = File::open(&db_json_path)
@@ -245,6 +437,9 @@ fn build_docset_from_db_json(docset_name: &String) -> ResultS {
 
     let file_visitor = FileVisitor {
         docset_name: docset_name.to_owned(),
+        docset_path: docset_path.to_owned(),
+        prerender,
+        pack,
     };
     db_json_deserializer
         .deserialize_map(file_visitor)
@@ -260,16 +455,153 @@ fn build_docset_from_db_json(docset_name: &String) -> ResultS {
     Ok(())
 }
 
+// Replaces the docset with the staged one. The previous version is moved aside first, and is put
+// back if the staged one can't be moved in.
+fn swap_in_docset(staging_path: &Path, docset_path: &Path) -> ResultS {
+    let mut previous_path = staging_path.as_os_str().to_owned();
+    previous_path.push(".previous");
+    let previous_path = PathBuf::from(previous_path);
+
+    let has_previous = docset_path.exists();
+
+    if has_previous {
+        if previous_path.exists() {
+            remove_dir_all(&previous_path)
+                .map_err(|err| format!("Could not remove `{}`: {err}", previous_path.display()))?;
+        }
+
+        rename(docset_path, &previous_path)
+            .map_err(|err| format!("Could not move `{}`: {err}", docset_path.display()))?;
+    }
+
+    if let Err(err) = rename(staging_path, docset_path) {
+        if has_previous {
+            let _ = rename(&previous_path, docset_path);
+        }
+        return Err(format!(
+            "Could not move `{}` to `{}`: {err}",
+            staging_path.display(),
+            docset_path.display()
+        ));
+    }
+
+    if has_previous {
+        if let Err(err) = remove_dir_all(&previous_path) {
+            print_warning!(
+                "Could not remove previous version in `{}`: {err}",
+                previous_path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn build_staged_docset(install: &DocsetInstall, staging_path: &Path, docs: &[Docs]) -> ResultS {
+    let docset_name = &install.docset_name;
+
+    let entry = docs
+        .iter()
+        .find(|entry| entry.slug == *docset_name)
+        .ok_or_else(|| format!("`{docset_name}` is not available."))?;
+
+    rename_downloaded_files(staging_path, entry.mtime)?;
+
+    build_docset_from_db_json(docset_name, staging_path, install.prerender, install.pack)?;
+
+    println!("Indexing `{docset_name}`...");
+    build_fulltext_index(staging_path)?;
+
+    write_docset_manifest(
+        staging_path,
+        &DocsetManifest::from_docs(entry, install.prerender, install.pack),
+    )
+}
+
+// Extracts and indexes the downloaded docset in the staging directory, and moves it into place
+// only when that's done. Until then, the previous version stays usable.
+fn extract_docset(install: &DocsetInstall, docs: &[Docs]) -> ResultS {
+    let docset_name = &install.docset_name;
+    let docset_path = install.root.path.join(docset_name);
+    let staging_path = install.root.get_staging_directory().join(docset_name);
+
+    println!(
+        "Extracting `{docset_name}` to `{}`...",
+        docset_path.display()
+    );
+
+    let result = build_staged_docset(install, &staging_path, docs)
+        .and_then(|_| swap_in_docset(&staging_path, &docset_path));
+
+    // Files can't be resumed after extraction has started.
+    if result.is_err() && staging_path.exists() {
+        let _ = remove_dir_all(&staging_path);
+    }
+
+    // Only removed when nothing else is staged.
+    let _ = remove_dir(install.root.get_staging_directory());
+
+    result
+}
+
+// Downloads docsets concurrently, then extracts and indexes them, replacing existing ones, and
+// records their versions. Docsets that fail don't stop the others from being installed.
+pub(crate) fn install_docsets(
+    installs: &[DocsetInstall],
+    docs: &[Docs],
+    mirror: &Mirror,
+) -> ResultS {
+    let docset_names = installs
+        .iter()
+        .map(|install| format!("`{}`", install.docset_name))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    println!("Downloading {docset_names}...");
+    let download_results = download_docsets_with_progress(installs, docs, mirror);
+
+    let mut failures = vec![];
+
+    for (install, download_result) in installs.iter().zip(download_results) {
+        if let Err(err) = download_result.and_then(|_| extract_docset(install, docs)) {
+            failures.push((&install.docset_name, err));
+        }
+    }
+
+    match failures.as_slice() {
+        [] => Ok(()),
+        [(_, err)] if installs.len() == 1 => Err(err.to_owned()),
+        _ => {
+            for (docset_name, err) in failures.iter() {
+                print_warning!("Could not install `{docset_name}`: {err}");
+            }
+            Err(format!(
+                "{} of {} docsets could not be installed.",
+                failures.len(),
+                installs.len()
+            ))
+        }
+    }
+}
+
 pub(crate) fn download<Args>(mut args: Args) -> ResultS
 where
     Args: Iterator<Item = String>,
 {
     let mut flag_force;
+    let mut flag_prerender;
+    let mut flag_pack;
+    let mut flag_mirror;
+    let mut flag_system;
     let mut flag_help;
 
     let mut flags = flags![
-        flag_force: BoolFlag, ["-f", "--force"],
-        flag_help: BoolFlag,  ["--help"]
+        flag_force: BoolFlag,     ["-f", "--force"],
+        flag_prerender: BoolFlag, ["-p", "--prerender"],
+        flag_pack: BoolFlag,      ["-z", "--pack"],
+        flag_mirror: StringFlag,  ["-m", "--mirror"],
+        flag_system: BoolFlag,    ["-s", "--system"],
+        flag_help: BoolFlag,      ["--help"]
     ];
 
     let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
@@ -281,37 +613,62 @@ where
         return Err("The list of available documents has not yet been downloaded. Please run `fetch` first.".to_string());
     }
 
+    let root = if flag_system {
+        get_system_docsets_roots()?.into_iter().next().ok_or(
+            "There are no system directories for docsets. Set `system_dirs` with `config`.",
+        )?
+    } else {
+        get_user_docsets_root()?
+    };
+
+    root.check_writable()?;
+
     let docs = deserialize_docs_json()?;
+    let mirror = Mirror::resolve(&flag_mirror)?;
+
+    let pack = flag_pack || get_config()?.storage.as_deref() == Some("pack");
 
-    let mut successful_downloads = 0;
+    let mut installs: Vec<DocsetInstall> = vec![];
 
-    for docset in args.iter() {
+    for docset in args.into_iter() {
         // Don't print warnings when using with ls -n
-        if docset == "[downloaded]" {
+        if docset == "[downloaded]"
+            || docset == "[system]"
+            || installs.iter().any(|install| install.docset_name == docset)
+        {
             continue;
         }
 
-        if !flag_force && is_docset_downloaded(docset)? {
+        // Docsets from system directories count as downloaded for users, but not the other way.
+        let existing_root = find_docset_root(&docset)?
+            .filter(|existing_root| *existing_root == root || root.kind == DocsetRootKind::User);
+
+        if let (false, Some(existing_root)) = (flag_force, existing_root) {
             print_warning!(
-                "Docset `{docset}` is already downloaded. \
-                If you still want to update it, re-run this command with `--force`"
+                "Docset `{docset}` is already downloaded to `{}`. \
+                Run `update {docset}` to update it, or re-run this command with `--force` to download it again",
+                existing_root.path.display()
             );
             continue;
-        } else if is_docset_in_docs_or_print_warning(docset, &docs) {
-            println!("Downloading `{docset}`...");
-            download_db_and_index_json_with_progress(docset, &docs)?;
-
-            println!("Extracting to `{}`...", get_docset_path(docset)?.display());
-            build_docset_from_db_json(docset)?;
-
-            successful_downloads += 1;
+        } else if is_docset_in_docs_or_print_warning(&docset, &docs) {
+            installs.push(DocsetInstall {
+                docset_name: docset,
+                root: root.clone(),
+                prerender: flag_prerender,
+                pack,
+            });
         }
     }
 
-    match successful_downloads {
-        0 => {}
+    if installs.is_empty() {
+        return Ok(());
+    }
+
+    install_docsets(&installs, &docs, &mirror)?;
+
+    match installs.len() {
         1 => println!("{BOLD}Install has successfully finished{RESET}."),
-        _ => println!("{BOLD}{successful_downloads} items were successfully installed{RESET}."),
+        n => println!("{BOLD}{n} items were successfully installed{RESET}."),
     }
 
     Ok(())
@@ -320,43 +677,43 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::TestDirectory;
 
     #[test]
-    fn test_sanitize_html() {
-        let html_text = r#"
-<summary>
-    <section id="method.new" class="method">
-        <span class="rightside">
-            <a class="srclink" href="https://doc.rust-lang.org/src/alloc/vec/mod.rs.html#420">source</a>
-            <span class="since" title="const since 1.39.0">
-                const: 1.39.0
-            </span>
-        </span>
-        <pre class="code-header" data-language="rust">
-            pub const fn new() -> Vec<T, Global>;
-        </pre>
-    </section>
-</summary>
-        "#;
-
-        let should_be = r#"
-<summary>
-    <section id="method.new" >
-        <span >
-            <a  href="https://doc.rust-lang.org/src/alloc/vec/mod.rs.html#420">source</a>
-            <span  >
-                const: 1.39.0
-            </span>
-        </span>
-        <pre  >
-            pub const fn new() -> Vec<T, Global>;
-        </pre>
-    </section>
-</summary>
-        "#;
-
-        let result = sanitize_html_line(html_text.to_owned());
-
-        assert_eq!(result, should_be);
+    fn test_prepare_staging_directory() {
+        let test_directory = TestDirectory::new("staging");
+        let staging_path = test_directory.path.join("rust");
+        create_dir_all(staging_path.join("std")).unwrap();
+
+        let kept = ["db.json.1700000000", "index.json.1700000000.part"];
+        let removed = [
+            "db.json.1600000000",
+            "index.json.1600000000.part",
+            "db.json",
+            "std",
+        ];
+
+        for file_name in kept.iter().chain(removed.iter().take(3)) {
+            std::fs::write(staging_path.join(file_name), "{}").unwrap();
+        }
+
+        prepare_staging_directory(&staging_path, 1700000000).unwrap();
+
+        for file_name in kept {
+            assert!(staging_path.join(file_name).exists(), "{file_name}");
+        }
+        for file_name in removed {
+            assert!(!staging_path.join(file_name).exists(), "{file_name}");
+        }
+
+        rename(
+            staging_path.join("index.json.1700000000.part"),
+            staging_path.join("index.json.1700000000"),
+        )
+        .unwrap();
+        rename_downloaded_files(&staging_path, 1700000000).unwrap();
+
+        assert!(staging_path.join("db.json").exists());
+        assert!(staging_path.join("index.json").exists());
     }
 }
@@ -1,45 +1,39 @@
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Read};
 use std::path::PathBuf;
 
-use attohttpc::get;
-
 use toiletcli::flags;
 use toiletcli::flags::*;
 
 use crate::common::{
-    create_program_directory, get_flag_error, get_program_directory, is_docs_json_exists,
-    is_docs_json_old, write_to_logfile,
+    create_program_directory, get_docs_json_max_age, get_flag_error, get_program_directory,
+    is_docs_json_exists, is_docs_json_old, write_to_logfile,
 };
 use crate::common::{Docs, ResultS};
-use crate::common::{
-    BOLD, DEFAULT_DOCS_JSON_LINK, DEFAULT_USER_AGENT, GREEN, PROGRAM_NAME, RESET, VERSION, YELLOW,
-};
+use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET};
+use crate::mirror::{open_url, Mirror};
+use crate::print_warning;
 
 fn show_fetch_help() -> ResultS {
     println!(
         "\
 {GREEN}USAGE{RESET}
-    {BOLD}{PROGRAM_NAME} fetch{RESET} [-f]
+    {BOLD}{PROGRAM_NAME} fetch{RESET} [-fm]
     Fetch latest `docs.json` which lists available languages and frameworks.
 
 {GREEN}OPTIONS{RESET}
     -f, --force                     Force the download and overwrite `docs.json`.
+    -m, --mirror <url>              Fetch from a DevDocs mirror or a local directory.
         --help                      Display help message."
     );
     Ok(())
 }
 
-fn fetch_docs() -> Result<Vec<Docs>, String> {
-    let user_agent = format!("{DEFAULT_USER_AGENT}/{VERSION}");
+fn fetch_docs(docs_json_url: &str) -> Result<Vec<Docs>, String> {
+    let mut body = String::new();
 
-    let response = get(DEFAULT_DOCS_JSON_LINK)
-        .header_append("user-agent", user_agent)
-        .send()
-        .map_err(|err| format!("Could not GET `{DEFAULT_DOCS_JSON_LINK}`: {err:?}"))?;
-
-    let body = response
-        .text()
+    open_url(docs_json_url)?
+        .read_to_string(&mut body)
         .map_err(|err| format!("Unable to read response body: {err}"))?;
 
     let docs: Vec<Docs> = serde_json::from_str(body.as_str()).map_err(|err| {
@@ -70,11 +64,13 @@ where
     Args: Iterator<Item = String>,
 {
     let mut flag_force;
+    let mut flag_mirror;
     let mut flag_help;
 
     let mut flags = flags![
-        flag_force: BoolFlag, ["-f", "--force"],
-        flag_help: BoolFlag,  ["--help"]
+        flag_force: BoolFlag,    ["-f", "--force"],
+        flag_mirror: StringFlag, ["-m", "--mirror"],
+        flag_help: BoolFlag,     ["--help"]
     ];
 
     parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
@@ -83,17 +79,18 @@ where
         return show_fetch_help();
     }
     if !flag_force && is_docs_json_exists()? && !is_docs_json_old()? {
-        let message = format!(
-            "\
-{YELLOW}WARNING{RESET}: It seems that your `docs.json` was updated less than a week ago. \
-Run `fetch --force` to ignore this warning."
+        print_warning!(
+            "It seems that your `docs.json` was updated less than {} days ago. \
+Run `fetch --force` to ignore this warning.",
+            get_docs_json_max_age()?
         );
-        println!("{}", message);
         return Ok(());
     }
 
-    println!("Fetching `{DEFAULT_DOCS_JSON_LINK}`...");
-    let docs = fetch_docs()?;
+    let mirror = Mirror::resolve(&flag_mirror)?;
+
+    println!("Fetching `{}`...", mirror.docs_json_url);
+    let docs = fetch_docs(&mirror.docs_json_url)?;
 
     let program_path = get_program_directory()?;
     let docs_json_path = program_path.join("docs.json");
@@ -0,0 +1,207 @@
+// Fuzzy matcher used to rank `index.json` entries.
+//
+// Query characters have to appear in the candidate in the same order, but not necessarily next to
+// each other. The best alignment is found with dynamic programming, rewarding matches on word
+// boundaries (start of the string, after `::`, `.`, `/`, `#`, `_`, `-` or a space, camelCase humps)
+// and runs of consecutive characters, and penalizing gaps. Whitespace in the query is skipped, but
+// the character following it is expected to start a new word, so `vec push` finds `Vec::push`.
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_START: i64 = 32;
+const BONUS_BOUNDARY: i64 = 24;
+const BONUS_CAMEL: i64 = 20;
+const BONUS_CONSECUTIVE: i64 = 16;
+const BONUS_CASE: i64 = 2;
+const PENALTY_GAP_START: i64 = 6;
+const PENALTY_GAP_EXTEND: i64 = 1;
+const PENALTY_LEADING_MAX: i64 = 12;
+const PENALTY_NOT_BOUNDARY: i64 = 24;
+const BONUS_EXACT: i64 = 1000;
+const BONUS_EXACT_SEGMENT: i64 = 500;
+
+#[inline]
+pub(crate) fn is_separator(ch: char) -> bool {
+    matches!(
+        ch,
+        ':' | '.' | '/' | '#' | '_' | '-' | ' ' | '(' | ')' | '<' | '>' | ','
+    )
+}
+
+#[derive(Clone, Copy)]
+struct QueryChar {
+    lowercase: char,
+    original: char,
+    after_space: bool,
+}
+
+// Bonus for matching a character at `position`, based on the character before it.
+fn position_bonus(candidate: &[char], position: usize) -> i64 {
+    if position == 0 {
+        return BONUS_START;
+    }
+
+    let previous = candidate[position - 1];
+    let current = candidate[position];
+
+    if is_separator(previous) && !is_separator(current) {
+        BONUS_BOUNDARY
+    } else if previous.is_lowercase() && current.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+// Name of the last segment, e.g. `push` in `std::vec::Vec::push` or `into_iter` in
+// `trait.intoiterator#method.into_iter`.
+fn last_segment(candidate: &str) -> &str {
+    candidate
+        .rsplit([':', '.', '/', '#', ' '])
+        .find(|segment| !segment.is_empty())
+        .unwrap_or(candidate)
+}
+
+/// Returns `None` when `query` is not a subsequence of `candidate`, and a score otherwise. Higher
+/// scores are better matches.
+pub(crate) fn fuzzy_score(query: &str, candidate: &str, case_insensitive: bool) -> Option<i64> {
+    let mut query_chars = vec![];
+    let mut after_space = false;
+
+    for ch in query.chars() {
+        if ch.is_whitespace() {
+            after_space = !query_chars.is_empty();
+            continue;
+        }
+        query_chars.push(QueryChar {
+            lowercase: ch.to_lowercase().next().unwrap_or(ch),
+            original: ch,
+            after_space,
+        });
+        after_space = false;
+    }
+
+    if query_chars.is_empty() {
+        return None;
+    }
+
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let n = query_chars.len();
+    let m = candidate_chars.len();
+
+    if n > m {
+        return None;
+    }
+
+    let chars_equal = |q: &QueryChar, c: char| -> bool {
+        if case_insensitive {
+            c.to_lowercase().next().unwrap_or(c) == q.lowercase
+        } else {
+            c == q.original
+        }
+    };
+
+    let match_score = |q: &QueryChar, position: usize| -> i64 {
+        let bonus = position_bonus(&candidate_chars, position);
+        let mut score = SCORE_MATCH + bonus;
+
+        if q.after_space && bonus == 0 {
+            score -= PENALTY_NOT_BOUNDARY;
+        }
+        if candidate_chars[position] == q.original {
+            score += BONUS_CASE;
+        }
+
+        score
+    };
+
+    // `previous[j]` is the best score of matching the query up to the previous character, with
+    // that character matched at position `j` of the candidate.
+    let mut previous: Vec<Option<i64>> = vec![None; m];
+    let mut current: Vec<Option<i64>> = vec![None; m];
+
+    for (j, &ch) in candidate_chars.iter().enumerate() {
+        if chars_equal(&query_chars[0], ch) {
+            let leading_penalty = std::cmp::min(j as i64, PENALTY_LEADING_MAX);
+            previous[j] = Some(match_score(&query_chars[0], j) - leading_penalty);
+        }
+    }
+
+    for query_char in query_chars.iter().skip(1) {
+        // Best score of a previous match that is at least one character behind, with gap penalty.
+        let mut gapped: Option<i64> = None;
+
+        for j in 0..m {
+            current[j] = None;
+
+            if j >= 2 {
+                let extended = gapped.map(|score| score - PENALTY_GAP_EXTEND);
+                let started = previous[j - 2].map(|score| score - PENALTY_GAP_START);
+                gapped = std::cmp::max(extended, started);
+            }
+
+            if j == 0 || !chars_equal(query_char, candidate_chars[j]) {
+                continue;
+            }
+
+            let consecutive = previous[j - 1].map(|score| score + BONUS_CONSECUTIVE);
+            let best_previous = std::cmp::max(consecutive, gapped);
+
+            current[j] = best_previous.map(|score| score + match_score(query_char, j));
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let mut score = previous.iter().flatten().copied().max()?;
+
+    let query_words: String = query.split_whitespace().collect();
+    let equals = |a: &str, b: &str| {
+        if case_insensitive {
+            a.to_lowercase() == b.to_lowercase()
+        } else {
+            a == b
+        }
+    };
+
+    if equals(candidate, query.trim()) {
+        score += BONUS_EXACT;
+    } else if equals(last_segment(candidate), &query_words) {
+        score += BONUS_EXACT_SEGMENT;
+    }
+
+    // Prefer shorter candidates when everything else is equal.
+    score -= m as i64 / 8;
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_subsequence() {
+        assert!(fuzzy_score("bufrd", "std::io::BufReader", true).is_some());
+        assert!(fuzzy_score("vec push", "std::vec::Vec::push", true).is_some());
+        assert!(fuzzy_score("vecpush", "std::vec::Vec::pop", true).is_none());
+        assert!(fuzzy_score("BufRd", "std::io::bufreader", false).is_none());
+        assert!(fuzzy_score("", "anything", true).is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_ranking() {
+        let score = |candidate| fuzzy_score("read", candidate, true).unwrap();
+
+        assert!(score("Read") > score("std::io::Read"));
+        assert!(score("std::io::Read") > score("std::io::BufReader"));
+        assert!(score("std::io::Read::read") > score("std::fs::read_to_string"));
+
+        let score = |candidate| fuzzy_score("bufrd", candidate, true).unwrap();
+
+        assert!(score("std::io::BufReader") > score("std::io::BufWriter::get_ref_mut_ready"));
+
+        let score = |candidate| fuzzy_score("vec push", candidate, true).unwrap();
+
+        assert!(score("std::vec::Vec::push") > score("std::vec::Vec::drain_filter_push"));
+    }
+}
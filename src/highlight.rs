@@ -0,0 +1,416 @@
+// Highlights code blocks of opened pages with syntaxes and themes that are built into dedoc, so
+// nothing is downloaded. The language of a block is taken from `data-language` of its `<pre>`,
+// which `download` keeps, or from the docset, e.g. Python for `python~3.12`. Colors are added to
+// pages as `RichAnnotation::Colour`, and are printed with as many colors as the terminal has.
+
+use std::io::Read;
+use std::path::Path;
+
+use html2text::render::text_renderer::{
+    RichAnnotation, TaggedLine, TaggedLineElement, TaggedString,
+};
+
+use toiletcli::common::should_use_colors;
+
+use crate::common::RichPage;
+use crate::debug_println;
+use crate::html::{Token, Tokenizer};
+use crate::pack::open_docset_file;
+use crate::theme::get_theme;
+
+#[cfg(debug_assertions)]
+use crate::common::{RED, RESET};
+
+// Docsets and languages without a syntax of their own, and the syntaxes that suit them.
+const LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("angular", "js"),
+    ("bun", "js"),
+    ("console", "bash"),
+    ("deno", "js"),
+    ("django", "python"),
+    ("dom", "js"),
+    ("express", "js"),
+    ("flask", "python"),
+    ("jquery", "js"),
+    ("jsx", "js"),
+    ("mariadb", "sql"),
+    ("node", "js"),
+    ("numpy", "python"),
+    ("pandas", "python"),
+    ("postgresql", "sql"),
+    ("rails", "ruby"),
+    ("react", "js"),
+    ("shell", "bash"),
+    ("sqlite", "sql"),
+    ("ts", "js"),
+    ("tsx", "js"),
+    ("typescript", "js"),
+    ("vue", "js"),
+    ("zsh", "bash"),
+];
+
+// Returns the language of a docset or a `data-language`, e.g. `js` for `typescript~5.1`.
+fn get_language(name: &str) -> String {
+    let name = name.split('~').next().unwrap_or(name).to_lowercase();
+
+    match LANGUAGE_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        Some((_, language)) => language.to_string(),
+        None => name,
+    }
+}
+
+// Languages of `<pre>` elements, in order, from their `data-language` or the one of `<code>`
+// inside of them.
+fn get_code_block_languages(html: &str) -> Vec<Option<String>> {
+    let mut languages = vec![];
+    let mut is_in_pre = false;
+
+    for token in Tokenizer::new(html) {
+        let Token::Tag(tag) = token else {
+            continue;
+        };
+
+        let is_pre = tag.name.eq_ignore_ascii_case("pre");
+        let is_code = tag.name.eq_ignore_ascii_case("code");
+
+        if is_pre && tag.is_end {
+            is_in_pre = false;
+        } else if is_pre && !is_in_pre {
+            is_in_pre = true;
+            languages.push(tag.get_attribute("data-language").map(|l| l.into_owned()));
+        } else if is_code && is_in_pre && !tag.is_end {
+            if let Some(language @ None) = languages.last_mut() {
+                *language = tag.get_attribute("data-language").map(|l| l.into_owned());
+            }
+        }
+    }
+
+    languages
+}
+
+#[inline]
+fn is_code(annotations: &[RichAnnotation]) -> bool {
+    annotations
+        .iter()
+        .any(|annotation| matches!(annotation, RichAnnotation::Preformat(_)))
+}
+
+#[inline]
+fn is_code_line(rich_line: &TaggedLine<Vec<RichAnnotation>>) -> bool {
+    rich_line
+        .tagged_strings()
+        .any(|tagged_string| is_code(&tagged_string.tag))
+}
+
+// Lines that are too long for the page are wrapped onto continuation lines.
+fn is_continuation_line(rich_line: &TaggedLine<Vec<RichAnnotation>>) -> bool {
+    let first_code_string = rich_line
+        .tagged_strings()
+        .find(|tagged_string| is_code(&tagged_string.tag));
+
+    matches!(
+        first_code_string,
+        Some(tagged_string) if tagged_string.tag.contains(&RichAnnotation::Preformat(true))
+    )
+}
+
+// Ranges of lines that belong to code blocks.
+fn get_code_blocks(rich_page: &RichPage) -> Vec<(usize, usize)> {
+    let mut blocks = vec![];
+    let mut block_start = None;
+
+    for (i, rich_line) in rich_page.iter().enumerate() {
+        match (is_code_line(rich_line), block_start) {
+            (true, None) => block_start = Some(i),
+            (false, Some(start)) => {
+                blocks.push((start, i));
+                block_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = block_start {
+        blocks.push((start, rich_page.len()));
+    }
+
+    blocks
+}
+
+// Splits code of `rich_line` into strings of each color. `colors` are byte offsets where colors
+// end in `code`, the text of code strings of the line, which starts at `offset`.
+fn apply_colors(
+    rich_line: &TaggedLine<Vec<RichAnnotation>>,
+    colors: &[(usize, html2text::Colour)],
+    mut offset: usize,
+) -> TaggedLine<Vec<RichAnnotation>> {
+    let mut colored_line = TaggedLine::new();
+
+    for element in rich_line.iter() {
+        let tagged_string = match element {
+            TaggedLineElement::Str(tagged_string) if is_code(&tagged_string.tag) => tagged_string,
+            other => {
+                colored_line.push(other.clone());
+                continue;
+            }
+        };
+
+        // Links in code keep their own color.
+        let is_link = tagged_string
+            .tag
+            .iter()
+            .any(|annotation| matches!(annotation, RichAnnotation::Link(_)));
+
+        let mut rest = tagged_string.s.as_str();
+
+        while !rest.is_empty() {
+            let color = colors.iter().find(|(end, _)| *end > offset);
+
+            let length = match color {
+                Some((end, _)) => std::cmp::min(end - offset, rest.len()),
+                None => rest.len(),
+            };
+            // Colors end on character boundaries of the whole text, but not always of a string.
+            let length = (length..=rest.len())
+                .find(|length| rest.is_char_boundary(*length))
+                .unwrap_or(rest.len());
+
+            let mut tag = tagged_string.tag.clone();
+            if let (Some((_, colour)), false) = (color, is_link) {
+                tag.push(RichAnnotation::Colour(*colour));
+            }
+
+            colored_line.push(TaggedLineElement::Str(TaggedString {
+                s: rest[..length].to_owned(),
+                tag,
+            }));
+
+            rest = &rest[length..];
+            offset += length;
+        }
+    }
+
+    colored_line
+}
+
+fn get_code_text(rich_line: &TaggedLine<Vec<RichAnnotation>>) -> String {
+    rich_line
+        .tagged_strings()
+        .filter(|tagged_string| is_code(&tagged_string.tag))
+        .map(|tagged_string| tagged_string.s.as_str())
+        .collect()
+}
+
+#[cfg(feature = "highlight")]
+mod syntaxes {
+    use std::sync::Mutex;
+
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::ThemeSet;
+    use syntect::parsing::SyntaxSet;
+
+    // Loading syntaxes takes a while, so they are kept for the next pages of `tui`.
+    static SYNTAXES: Mutex<Option<(SyntaxSet, ThemeSet)>> = Mutex::new(None);
+
+    // Colors of each line, as byte offsets where they end, or `None` if there's no syntax for
+    // `language`. `theme_name` is one of `SYNTAX_THEMES`.
+    pub(super) fn highlight_lines(
+        lines: &[String],
+        language: &str,
+        theme_name: &str,
+    ) -> Option<Vec<Vec<(usize, html2text::Colour)>>> {
+        let mut syntaxes = SYNTAXES.lock().ok()?;
+
+        if syntaxes.is_none() {
+            *syntaxes = Some((
+                SyntaxSet::load_defaults_newlines(),
+                ThemeSet::load_defaults(),
+            ));
+        }
+
+        let (syntax_set, theme_set) = syntaxes.as_ref()?;
+        let syntax = syntax_set.find_syntax_by_token(language)?;
+        let theme = theme_set.themes.get(theme_name)?;
+
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let mut colored_lines = vec![];
+
+        for line in lines {
+            let line_with_newline = format!("{line}\n");
+            let ranges = highlighter
+                .highlight_line(&line_with_newline, syntax_set)
+                .ok()?;
+
+            let mut colors = vec![];
+            let mut end = 0;
+
+            for (style, text) in ranges {
+                end += text.len();
+                let color = style.foreground;
+                colors.push((
+                    std::cmp::min(end, line.len()),
+                    html2text::Colour {
+                        r: color.r,
+                        g: color.g,
+                        b: color.b,
+                    },
+                ));
+            }
+
+            colored_lines.push(colors);
+        }
+
+        Some(colored_lines)
+    }
+}
+
+#[cfg(not(feature = "highlight"))]
+mod syntaxes {
+    pub(super) fn highlight_lines(
+        _lines: &[String],
+        _language: &str,
+        _theme_name: &str,
+    ) -> Option<Vec<Vec<(usize, html2text::Colour)>>> {
+        None
+    }
+}
+
+fn highlight_code_block(
+    rich_lines: &mut [TaggedLine<Vec<RichAnnotation>>],
+    language: &str,
+    theme_name: &str,
+) {
+    // Lines of code as they are in the page, with wrapped lines joined, and which lines of the
+    // page each of them takes.
+    let mut code_lines: Vec<String> = vec![];
+    let mut code_line_indices: Vec<Vec<usize>> = vec![];
+
+    for (i, rich_line) in rich_lines.iter().enumerate() {
+        let text = get_code_text(rich_line);
+
+        match (code_lines.last_mut(), code_line_indices.last_mut()) {
+            (Some(code_line), Some(indices)) if is_continuation_line(rich_line) => {
+                code_line.push_str(&text);
+                indices.push(i);
+            }
+            _ => {
+                code_lines.push(text);
+                code_line_indices.push(vec![i]);
+            }
+        }
+    }
+
+    let Some(colored_lines) = syntaxes::highlight_lines(&code_lines, language, theme_name) else {
+        return;
+    };
+
+    for (colors, indices) in colored_lines.iter().zip(code_line_indices) {
+        let mut offset = 0;
+
+        for i in indices {
+            let length = get_code_text(&rich_lines[i]).len();
+            rich_lines[i] = apply_colors(&rich_lines[i], colors, offset);
+            offset += length;
+        }
+    }
+}
+
+// Highlights code blocks of `rich_page`, which was rendered from `path`. Does nothing if colors
+// are disabled, or the theme doesn't highlight code.
+pub(crate) fn highlight_page(
+    rich_page: &mut RichPage,
+    docset_path: &Path,
+    path: &Path,
+    docset_name: Option<&str>,
+) {
+    let Some(theme_name) = get_theme().syntax_theme else {
+        return;
+    };
+
+    if cfg!(not(feature = "highlight")) || !should_use_colors() {
+        return;
+    }
+
+    let code_blocks = get_code_blocks(rich_page);
+
+    if code_blocks.is_empty() {
+        return;
+    }
+
+    let mut html = String::new();
+    let result = open_docset_file(docset_path, path).and_then(|mut file| {
+        file.read_to_string(&mut html)
+            .map_err(|err| format!("Could not read `{}`: {err}", path.display()))
+    });
+
+    if let Err(err) = result {
+        debug_println!("{err}");
+        return;
+    }
+
+    let languages = get_code_block_languages(&html);
+
+    for (i, (start, end)) in code_blocks.into_iter().enumerate() {
+        let language = languages
+            .get(i)
+            .cloned()
+            .flatten()
+            .or_else(|| docset_name.map(str::to_owned));
+
+        if let Some(language) = language {
+            let language = get_language(&language);
+            highlight_code_block(&mut rich_page[start..end], &language, theme_name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_block_languages() {
+        let html = r#"<pre data-language="rust">a</pre><p>b</p><pre><code data-language="js">c</code></pre><pre>d</pre>"#;
+
+        assert_eq!(
+            get_code_block_languages(html),
+            [Some("rust".to_string()), Some("js".to_string()), None]
+        );
+
+        assert_eq!(get_language("python~3.12"), "python");
+        assert_eq!(get_language("TypeScript"), "js");
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn test_highlight_code_block() {
+        let html = "<p>Text</p><pre>let numbers: Vec&lt;u8&gt; = vec![1, 2];\nfn main() {}</pre>";
+        let mut rich_page = html2text::from_read_rich(html.as_bytes(), 20);
+
+        let text_before: Vec<String> = rich_page.iter().map(get_code_text).collect();
+
+        let (start, end) = get_code_blocks(&rich_page)[0];
+        highlight_code_block(&mut rich_page[start..end], "rust", "base16-ocean.dark");
+
+        let text_after: Vec<String> = rich_page.iter().map(get_code_text).collect();
+        assert_eq!(text_before, text_after);
+
+        let colors: Vec<html2text::Colour> = rich_page[start..end]
+            .iter()
+            .flat_map(|rich_line| rich_line.tagged_strings())
+            .filter_map(|tagged_string| {
+                tagged_string
+                    .tag
+                    .iter()
+                    .find_map(|annotation| match annotation {
+                        RichAnnotation::Colour(colour) => Some(*colour),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        // `let`, `fn` and numbers are colored differently from names.
+        assert!(colors.len() > 4);
+        assert!(colors.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+}
@@ -0,0 +1,349 @@
+// A small HTML tokenizer, which is enough for pages of docsets. Tags are split into their names and
+// attributes, and everything else is returned as it's written. Quoted values with `>` or single
+// quotes are handled, and a `<` that doesn't start a tag, like in `a < b`, is kept as text.
+
+use std::borrow::Cow;
+
+pub(crate) struct Tag<'a> {
+    pub name: &'a str,
+    pub is_end: bool,
+    pub is_self_closing: bool,
+    // Each attribute as it's written, e.g. `href='#new'`.
+    pub attributes: Vec<(&'a str, &'a str)>,
+    // The whole tag as it's written.
+    pub source: &'a str,
+}
+
+impl Tag<'_> {
+    // Value of the attribute with entities decoded, or an empty string for attributes without a
+    // value, like `disabled`.
+    pub(crate) fn get_attribute(&self, name: &str) -> Option<Cow<'_, str>> {
+        let (_, source) = self
+            .attributes
+            .iter()
+            .find(|(attribute_name, _)| attribute_name.eq_ignore_ascii_case(name))?;
+
+        let Some((_, value)) = source.split_once('=') else {
+            return Some(Cow::Borrowed(""));
+        };
+
+        let value = value.trim_start();
+        let value = match value.as_bytes().first() {
+            Some(quote @ (b'"' | b'\'')) => value
+                .strip_prefix(*quote as char)
+                .and_then(|value| value.strip_suffix(*quote as char))
+                .unwrap_or(value),
+            _ => value,
+        };
+
+        Some(decode_entities(value))
+    }
+}
+
+pub(crate) enum Token<'a> {
+    Text(&'a str),
+    Tag(Tag<'a>),
+    // Comments, doctypes and processing instructions.
+    Markup(&'a str),
+}
+
+#[inline]
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b':' | b'.')
+}
+
+fn skip_whitespace(bytes: &[u8], mut position: usize) -> usize {
+    while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+        position += 1;
+    }
+    position
+}
+
+// Parses a start or an end tag at the beginning of `html`. Returns `None` if it's not a tag, like
+// in `a < b`, or if the tag is not closed.
+fn parse_tag(html: &str) -> Option<Tag<'_>> {
+    let bytes = html.as_bytes();
+
+    let is_end = bytes.get(1) == Some(&b'/');
+    let name_start = if is_end { 2 } else { 1 };
+
+    if !bytes.get(name_start)?.is_ascii_alphabetic() {
+        return None;
+    }
+
+    let mut position = name_start;
+    while position < bytes.len() && is_name_byte(bytes[position]) {
+        position += 1;
+    }
+
+    let name = &html[name_start..position];
+
+    let mut attributes = vec![];
+    let mut is_self_closing = false;
+
+    loop {
+        position = skip_whitespace(bytes, position);
+
+        match bytes.get(position)? {
+            b'>' => {
+                position += 1;
+                break;
+            }
+            b'/' => {
+                position += 1;
+                is_self_closing = bytes.get(position) == Some(&b'>');
+                continue;
+            }
+            _ => {}
+        }
+
+        let attribute_start = position;
+
+        while position < bytes.len()
+            && !bytes[position].is_ascii_whitespace()
+            && !matches!(bytes[position], b'=' | b'>' | b'/')
+        {
+            position += 1;
+        }
+
+        // A stray `=`.
+        if position == attribute_start {
+            position += 1;
+            continue;
+        }
+
+        let attribute_name = &html[attribute_start..position];
+
+        let value_start = skip_whitespace(bytes, position);
+
+        if bytes.get(value_start) == Some(&b'=') {
+            position = skip_whitespace(bytes, value_start + 1);
+
+            match *bytes.get(position)? {
+                quote @ (b'"' | b'\'') => {
+                    let value_end = html[position + 1..].find(quote as char)?;
+                    position += value_end + 2;
+                }
+                _ => {
+                    while position < bytes.len()
+                        && !bytes[position].is_ascii_whitespace()
+                        && bytes[position] != b'>'
+                    {
+                        position += 1;
+                    }
+                }
+            }
+        }
+
+        attributes.push((attribute_name, &html[attribute_start..position]));
+    }
+
+    Some(Tag {
+        name,
+        is_end,
+        is_self_closing,
+        attributes,
+        source: &html[..position],
+    })
+}
+
+// Length of a comment, a doctype or a processing instruction at the beginning of `html`.
+fn get_markup_declaration_length(html: &str) -> Option<usize> {
+    if let Some(comment) = html.strip_prefix("<!--") {
+        let end = comment.find("-->").map_or(html.len(), |end| end + 7);
+        return Some(end);
+    }
+
+    if html.starts_with("<!") || html.starts_with("<?") {
+        let end = html.find('>').map_or(html.len(), |end| end + 1);
+        return Some(end);
+    }
+
+    None
+}
+
+pub(crate) struct Tokenizer<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn new(html: &'a str) -> Self {
+        Tokenizer { rest: html }
+    }
+
+    // Skips contents of the element whose start tag was just returned, up to the end of its
+    // closing tag, or to the end of the HTML if there's none. Used for `<script>` and such.
+    pub(crate) fn skip_element(&mut self, name: &str) {
+        let lowercase_rest = self.rest.to_ascii_lowercase();
+        let closing_tag = format!("</{}", name.to_ascii_lowercase());
+
+        let length = match lowercase_rest.find(&closing_tag) {
+            Some(closing_start) => self.rest[closing_start..]
+                .find('>')
+                .map_or(self.rest.len(), |end| closing_start + end + 1),
+            None => self.rest.len(),
+        };
+
+        self.rest = &self.rest[length..];
+    }
+
+    fn take(&mut self, length: usize) -> &'a str {
+        let (taken, rest) = self.rest.split_at(length);
+        self.rest = rest;
+        taken
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match self.rest.find('<') {
+            Some(0) => {}
+            Some(tag_start) => return Some(Token::Text(self.take(tag_start))),
+            None => return Some(Token::Text(self.take(self.rest.len()))),
+        }
+
+        if let Some(length) = get_markup_declaration_length(self.rest) {
+            return Some(Token::Markup(self.take(length)));
+        }
+
+        if let Some(tag) = parse_tag(self.rest) {
+            self.rest = &self.rest[tag.source.len()..];
+            return Some(Token::Tag(tag));
+        }
+
+        // Not a tag, so it's text up to the next `<`.
+        let text_length = self.rest[1..]
+            .find('<')
+            .map_or(self.rest.len(), |end| end + 1);
+
+        Some(Token::Text(self.take(text_length)))
+    }
+}
+
+// Entities that are common in docsets. Others are kept as they are.
+const NAMED_ENTITIES: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", " "),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("hellip", "…"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("larr", "←"),
+    ("rarr", "→"),
+    ("times", "×"),
+    ("minus", "−"),
+    ("le", "≤"),
+    ("ge", "≥"),
+    ("ne", "≠"),
+    ("copy", "©"),
+    ("reg", "®"),
+    ("trade", "™"),
+    ("sect", "§"),
+    ("para", "¶"),
+    ("bull", "•"),
+    ("middot", "·"),
+    ("deg", "°"),
+    ("zwj", "\u{200d}"),
+    ("zwnj", "\u{200c}"),
+];
+
+fn decode_entity(entity: &str) -> Option<Cow<'static, str>> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        return Some(Cow::Owned(char::from_u32(code)?.to_string()));
+    }
+
+    NAMED_ENTITIES
+        .iter()
+        .find(|(name, _)| *name == entity)
+        .map(|(_, decoded)| Cow::Borrowed(*decoded))
+}
+
+pub(crate) fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 32)
+            .and_then(|end| Some((end, decode_entity(&rest[1..end + 1])?)));
+
+        match entity {
+            Some((end, character)) => {
+                decoded.push_str(&character);
+                rest = &rest[end + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+
+    Cow::Owned(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenizer() {
+        let html = r#"a < b<a HREF = 'x.html#y&amp;z' hidden><!-- c --></a>&lt;T&gt; &unknown; &#x41;&#66;"#;
+        let mut tokenizer = Tokenizer::new(html);
+
+        assert!(matches!(tokenizer.next(), Some(Token::Text("a "))));
+        assert!(matches!(tokenizer.next(), Some(Token::Text("< b"))));
+
+        let Some(Token::Tag(tag)) = tokenizer.next() else {
+            panic!("`<a>` is not a tag");
+        };
+        assert_eq!(tag.name, "a");
+        assert_eq!(tag.get_attribute("href").unwrap(), "x.html#y&z");
+        assert_eq!(tag.get_attribute("hidden").unwrap(), "");
+        assert!(tag.get_attribute("class").is_none());
+
+        assert!(matches!(
+            tokenizer.next(),
+            Some(Token::Markup("<!-- c -->"))
+        ));
+        assert!(matches!(
+            tokenizer.next(),
+            Some(Token::Tag(Tag { is_end: true, .. }))
+        ));
+
+        let Some(Token::Text(text)) = tokenizer.next() else {
+            panic!("entities are not text");
+        };
+        assert_eq!(decode_entities(text), "<T> &unknown; AB");
+        assert!(tokenizer.next().is_none());
+    }
+}
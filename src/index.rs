@@ -0,0 +1,582 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read_dir, rename, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use toiletcli::flags;
+use toiletcli::flags::*;
+
+use crate::common::ResultS;
+use crate::common::{
+    convert_path_to_item, find_docset_root, get_docset_path, get_flag_error, get_local_docsets,
+    render_page_text,
+};
+use crate::common::{BOLD, DOC_PAGE_EXTENSION, GREEN, PROGRAM_NAME, RESET};
+use crate::pack::get_packed_file_paths;
+use crate::print_warning;
+
+fn show_index_help() -> ResultS {
+    println!(
+        "\
+{GREEN}USAGE{RESET}
+    {BOLD}{PROGRAM_NAME} index{RESET} [-a] <docset1> [docset2, ..]
+    Build full-text search index for downloaded docsets, which is used by `search --precise`.
+    Docsets are indexed automatically when downloaded.
+
+{GREEN}OPTIONS{RESET}
+    -a, --all                       Index all downloaded docsets.
+        --help                      Display help message."
+    );
+    Ok(())
+}
+
+pub(crate) const FULLTEXT_INDEX_FILE_NAME: &str = "fulltext_index.bin";
+const FULLTEXT_INDEX_MAGIC: &[u8; 4] = b"DDFT";
+const FULLTEXT_INDEX_VERSION: u32 = 3;
+
+// Terms longer than this are most likely base64 blobs or minified code. Only their beginning is
+// indexed, and the rest may contain anything.
+const MAX_TERM_LENGTH: usize = 64;
+
+// Each posting is a page index and a line number inside of rendered text of that page.
+const POSTING_SIZE: u64 = 8;
+
+// Layout of the index file, all numbers are little-endian:
+//
+//     magic "DDFT", u32 version
+//     u32 page count, then for each page: u32 length, UTF-8 path of the page
+//     u32 term count, then for each term: u8 length, UTF-8 term, u64 first posting, u32 postings
+//     postings: u32 page index, u32 line number, grouped by term and sorted
+//
+// Pages and terms are small and read into memory, postings are read only for terms that match the
+// query.
+pub(crate) struct FullTextIndex {
+    file: File,
+    postings_start: u64,
+    pub pages: Vec<String>,
+    terms: Vec<(String, u64, u32)>,
+}
+
+#[inline]
+fn is_term_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+// Splits text into lowercase terms, and cuts long ones to `MAX_TERM_LENGTH` bytes.
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|ch: char| !is_term_char(ch))
+        .filter(|term| !term.is_empty())
+        .map(|term| {
+            let mut term = term.to_lowercase();
+            if term.len() > MAX_TERM_LENGTH {
+                let mut end = MAX_TERM_LENGTH;
+                while !term.is_char_boundary(end) {
+                    end -= 1;
+                }
+                term.truncate(end);
+            }
+            term
+        })
+}
+
+// Terms are cut at character boundaries, so a cut term can be up to 3 bytes shorter.
+#[inline]
+fn is_cut_term(term: &str) -> bool {
+    term.len() + 3 >= MAX_TERM_LENGTH
+}
+
+// How a query term can match an index term. A term in the middle of the query has to be equal to
+// an index term, while the first and the last one may be cut off by the query.
+#[derive(Debug, PartialEq)]
+enum TermMatch {
+    Equal,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+fn get_query_terms(query: &str) -> Vec<(String, TermMatch)> {
+    let mut terms = vec![];
+
+    let chars: Vec<(usize, char)> = query.char_indices().collect();
+    let mut position = 0;
+
+    while position < chars.len() {
+        if !is_term_char(chars[position].1) {
+            position += 1;
+            continue;
+        }
+
+        let start = position;
+        while position < chars.len() && is_term_char(chars[position].1) {
+            position += 1;
+        }
+
+        let closed_left = start > 0;
+        let closed_right = position < chars.len();
+
+        let start_byte = chars[start].0;
+        let end_byte = chars.get(position).map_or(query.len(), |(index, _)| *index);
+
+        let term = query[start_byte..end_byte].to_lowercase();
+
+        let term_match = match (closed_left, closed_right) {
+            (true, true) => TermMatch::Equal,
+            (true, false) => TermMatch::Prefix,
+            (false, true) => TermMatch::Suffix,
+            (false, false) => TermMatch::Contains,
+        };
+
+        terms.push((term, term_match));
+    }
+
+    terms
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn read_string(reader: &mut impl Read, length: usize) -> std::io::Result<String> {
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+fn collect_pages(path: &PathBuf, pages: &mut Vec<PathBuf>) -> ResultS {
+    let dir = read_dir(path)
+        .map_err(|err| format!("Could not read `{}` directory: {err}", path.display()))?;
+
+    for entry in dir {
+        let entry = entry.map_err(|err| format!("Could not read file: {err}"))?;
+
+        let file_type = entry
+            .file_type()
+            .map_err(|err| format!("Could not read file type of {:?}: {err}", entry.file_name()))?;
+
+        if file_type.is_dir() {
+            collect_pages(&entry.path(), pages)?;
+        } else if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(DOC_PAGE_EXTENSION)
+        {
+            pages.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+// Pages of the docset, from its pack if it's packed.
+pub(crate) fn get_page_paths(docset_path: &Path) -> Result<Vec<PathBuf>, String> {
+    if let Some(file_paths) = get_packed_file_paths(docset_path)? {
+        let page_paths = file_paths
+            .into_iter()
+            .filter(|path| path.to_string_lossy().ends_with(DOC_PAGE_EXTENSION))
+            .collect();
+        return Ok(page_paths);
+    }
+
+    let mut page_paths = vec![];
+    collect_pages(&docset_path.to_path_buf(), &mut page_paths)?;
+
+    Ok(page_paths)
+}
+
+fn write_fulltext_index(
+    path: &Path,
+    pages: &[String],
+    terms: &BTreeMap<String, Vec<(u32, u32)>>,
+) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+
+    writer.write_all(FULLTEXT_INDEX_MAGIC)?;
+    write_u32(&mut writer, FULLTEXT_INDEX_VERSION)?;
+
+    write_u32(&mut writer, pages.len() as u32)?;
+    for page in pages {
+        write_u32(&mut writer, page.len() as u32)?;
+        writer.write_all(page.as_bytes())?;
+    }
+
+    write_u32(&mut writer, terms.len() as u32)?;
+    let mut first_posting = 0;
+    for (term, postings) in terms {
+        writer.write_all(&[term.len() as u8])?;
+        writer.write_all(term.as_bytes())?;
+        write_u64(&mut writer, first_posting)?;
+        write_u32(&mut writer, postings.len() as u32)?;
+        first_posting += postings.len() as u64;
+    }
+
+    for postings in terms.values() {
+        for (page, line) in postings {
+            write_u32(&mut writer, *page)?;
+            write_u32(&mut writer, *line)?;
+        }
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn build_fulltext_index(docset_path: &Path) -> ResultS {
+    let docset_path = docset_path.to_path_buf();
+
+    let mut page_paths = get_page_paths(&docset_path)?;
+    page_paths.sort_unstable();
+
+    let mut pages = vec![];
+    let mut terms: BTreeMap<String, Vec<(u32, u32)>> = BTreeMap::new();
+
+    for (page_index, page_path) in page_paths.into_iter().enumerate() {
+        let lines = render_page_text(&docset_path, &page_path)?;
+
+        for (line_number, line) in lines.iter().enumerate() {
+            for term in tokenize(line) {
+                let posting = (page_index as u32, line_number as u32);
+                let postings = terms.entry(term).or_default();
+
+                if postings.last() != Some(&posting) {
+                    postings.push(posting);
+                }
+            }
+        }
+
+        pages.push(convert_path_to_item(page_path, &docset_path)?);
+
+        print!("Indexed {} files...\r", page_index + 1);
+    }
+    println!();
+
+    // Write to a temporary file first, to never leave a half-written index behind.
+    let index_path = docset_path.join(FULLTEXT_INDEX_FILE_NAME);
+    let temporary_path = index_path.with_extension("tmp");
+
+    write_fulltext_index(&temporary_path, &pages, &terms)
+        .map_err(|err| format!("Could not write `{}`: {err}", temporary_path.display()))?;
+
+    rename(&temporary_path, &index_path)
+        .map_err(|err| format!("Could not write `{}`: {err}", index_path.display()))?;
+
+    Ok(())
+}
+
+#[inline]
+pub(crate) fn has_fulltext_index(docset_name: &str) -> Result<bool, String> {
+    Ok(get_docset_path(docset_name)?
+        .join(FULLTEXT_INDEX_FILE_NAME)
+        .is_file())
+}
+
+impl FullTextIndex {
+    // Returns `None` when the docset has no index.
+    pub(crate) fn open(docset_name: &str) -> Result<Option<Self>, String> {
+        let index_path = get_docset_path(docset_name)?.join(FULLTEXT_INDEX_FILE_NAME);
+
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+
+        let file = File::open(&index_path)
+            .map_err(|err| format!("Could not open `{}`: {err}", index_path.display()))?;
+
+        Self::read(file).map(Some).map_err(|err| {
+            format!(
+                "Could not read `{}`: {err}. Try running `index {docset_name}`.",
+                index_path.display()
+            )
+        })
+    }
+
+    fn read(file: File) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if magic != *FULLTEXT_INDEX_MAGIC || read_u32(&mut reader)? != FULLTEXT_INDEX_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unknown index format",
+            ));
+        }
+
+        let page_count = read_u32(&mut reader)?;
+        let mut pages = Vec::with_capacity(page_count as usize);
+        for _ in 0..page_count {
+            let length = read_u32(&mut reader)?;
+            pages.push(read_string(&mut reader, length as usize)?);
+        }
+
+        let term_count = read_u32(&mut reader)?;
+        let mut terms = Vec::with_capacity(term_count as usize);
+        for _ in 0..term_count {
+            let mut length = [0; 1];
+            reader.read_exact(&mut length)?;
+            let term = read_string(&mut reader, length[0] as usize)?;
+            let first_posting = read_u64(&mut reader)?;
+            let posting_count = read_u32(&mut reader)?;
+            terms.push((term, first_posting, posting_count));
+        }
+
+        let postings_start = reader.stream_position()?;
+
+        Ok(Self {
+            file: reader.into_inner(),
+            postings_start,
+            pages,
+            terms,
+        })
+    }
+
+    fn read_postings(
+        &mut self,
+        first_posting: u64,
+        count: u32,
+    ) -> std::io::Result<Vec<(u32, u32)>> {
+        self.file.seek(SeekFrom::Start(
+            self.postings_start + first_posting * POSTING_SIZE,
+        ))?;
+
+        let mut buffer = vec![0; count as usize * POSTING_SIZE as usize];
+        self.file.read_exact(&mut buffer)?;
+
+        let postings = buffer
+            .chunks_exact(POSTING_SIZE as usize)
+            .map(|chunk| {
+                let page = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let line = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                (page, line)
+            })
+            .collect();
+
+        Ok(postings)
+    }
+
+    // Returns lines which may contain the query, grouped by page index, or `None` when the query
+    // has no terms and the index can't be used. Terms are compared without case, so results have to
+    // be checked again with the query itself.
+    pub(crate) fn find_candidates(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<BTreeMap<u32, BTreeSet<u32>>>, String> {
+        let query_terms = get_query_terms(query);
+
+        if query_terms.is_empty() {
+            return Ok(None);
+        }
+
+        let mut candidates: Option<BTreeSet<(u32, u32)>> = None;
+
+        for (query_term, term_match) in query_terms {
+            let matching_terms: Vec<(u64, u32)> = self
+                .terms
+                .iter()
+                .filter(|(term, _, _)| {
+                    let is_match = match term_match {
+                        TermMatch::Equal => *term == query_term,
+                        TermMatch::Prefix => term.starts_with(&query_term),
+                        TermMatch::Suffix => term.ends_with(&query_term),
+                        TermMatch::Contains => term.contains(&query_term),
+                    };
+
+                    // The query term may be in the part of a cut term that wasn't indexed.
+                    let is_cut_match = is_cut_term(term)
+                        && match term_match {
+                            TermMatch::Equal | TermMatch::Prefix => {
+                                query_term.starts_with(term.as_str())
+                            }
+                            TermMatch::Suffix | TermMatch::Contains => true,
+                        };
+
+                    is_match || is_cut_match
+                })
+                .map(|(_, first_posting, count)| (*first_posting, *count))
+                .collect();
+
+            let mut lines = BTreeSet::new();
+
+            for (first_posting, count) in matching_terms {
+                let postings = self
+                    .read_postings(first_posting, count)
+                    .map_err(|err| format!("Could not read full-text index: {err}"))?;
+                lines.extend(postings);
+            }
+
+            candidates = Some(match candidates {
+                Some(previous) => previous.intersection(&lines).copied().collect(),
+                None => lines,
+            });
+        }
+
+        let mut pages: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+
+        for (page, line) in candidates.unwrap_or_default() {
+            pages.entry(page).or_default().insert(line);
+        }
+
+        Ok(Some(pages))
+    }
+}
+
+pub(crate) fn index<Args>(mut args: Args) -> ResultS
+where
+    Args: Iterator<Item = String>,
+{
+    let mut flag_all;
+    let mut flag_help;
+
+    let mut flags = flags![
+        flag_all: BoolFlag,  ["-a", "--all"],
+        flag_help: BoolFlag, ["--help"]
+    ];
+
+    let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
+    if flag_help || (args.is_empty() && !flag_all) {
+        return show_index_help();
+    }
+
+    let docsets = if flag_all { get_local_docsets()? } else { args };
+
+    let mut successful_indexes = 0;
+
+    for docset in docsets.iter() {
+        let Some(root) = find_docset_root(docset)? else {
+            print_warning!("Docset `{docset}` is not downloaded. Try running `download {docset}`.");
+            continue;
+        };
+
+        if !root.is_writable() {
+            print_warning!(
+                "`{docset}` is in read-only `{}`, so it can't be indexed.",
+                root.path.display()
+            );
+            continue;
+        }
+
+        println!("Indexing `{docset}`...");
+        build_fulltext_index(&root.path.join(docset))?;
+
+        successful_indexes += 1;
+    }
+
+    match successful_indexes {
+        0 => {}
+        1 => println!("{BOLD}Indexing has successfully finished{RESET}."),
+        _ => println!("{BOLD}{successful_indexes} docsets were successfully indexed{RESET}."),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TestDirectory;
+
+    #[test]
+    fn test_tokenize() {
+        let terms: Vec<String> = tokenize("pub fn Vec::push(&mut self, value: T)").collect();
+
+        assert_eq!(
+            terms,
+            ["pub", "fn", "vec", "push", "mut", "self", "value", "t"]
+        );
+    }
+
+    #[test]
+    fn test_query_terms() {
+        assert_eq!(
+            get_query_terms("bufrea"),
+            [("bufrea".to_string(), TermMatch::Contains)]
+        );
+        assert_eq!(
+            get_query_terms("fn new_in(cap"),
+            [
+                ("fn".to_string(), TermMatch::Suffix),
+                ("new_in".to_string(), TermMatch::Equal),
+                ("cap".to_string(), TermMatch::Prefix),
+            ]
+        );
+        assert!(get_query_terms(":: ->").is_empty());
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let test_directory = TestDirectory::new("index");
+        let path = test_directory.path.join(FULLTEXT_INDEX_FILE_NAME);
+
+        let pages = vec!["std/vec/struct.vec".to_string(), "std/index".to_string()];
+        let mut terms = BTreeMap::new();
+        terms.insert("push".to_string(), vec![(0, 3), (0, 7)]);
+        terms.insert("vec".to_string(), vec![(0, 3), (1, 1)]);
+
+        write_fulltext_index(&path, &pages, &terms).unwrap();
+
+        let mut index = FullTextIndex::read(File::open(&path).unwrap()).unwrap();
+
+        assert_eq!(index.pages, pages);
+
+        let candidates = index.find_candidates("vec push").unwrap().unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[&0], BTreeSet::from([3]));
+
+        let candidates = index.find_candidates("ve").unwrap().unwrap();
+        assert_eq!(candidates.keys().copied().collect::<Vec<_>>(), [0, 1]);
+
+        assert!(index.find_candidates("::").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_long_terms() {
+        let test_directory = TestDirectory::new("long_terms");
+        let path = test_directory.path.join(FULLTEXT_INDEX_FILE_NAME);
+
+        let long_term = format!("{}needle{}", "a".repeat(60), "b".repeat(40));
+        let lines = [format!("data:{long_term}"), "no match".to_string()];
+
+        let mut terms: BTreeMap<String, Vec<(u32, u32)>> = BTreeMap::new();
+        for (line_number, line) in lines.iter().enumerate() {
+            for term in tokenize(line) {
+                terms.entry(term).or_default().push((0, line_number as u32));
+            }
+        }
+        assert!(terms.keys().all(|term| term.len() <= MAX_TERM_LENGTH));
+
+        write_fulltext_index(&path, &["page".to_string()], &terms).unwrap();
+        let mut index = FullTextIndex::read(File::open(&path).unwrap()).unwrap();
+
+        // Every query that the scan finds in the long term has to be a candidate.
+        for query in [
+            "needle".to_string(),
+            "needlebbb".to_string(),
+            format!("data:{long_term}"),
+            format!("data:{long_term} "),
+        ] {
+            let candidates = index.find_candidates(&query).unwrap().unwrap();
+            assert_eq!(candidates.get(&0), Some(&BTreeSet::from([0])), "{query}");
+        }
+
+        let candidates = index.find_candidates("data:aaab").unwrap().unwrap();
+        assert!(candidates.is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+// With the global `--json` flag, or `--format json`, `list`, `search` and `open` print one JSON
+// document to stdout instead of text, for editor plugins and scripts. Warnings are still printed
+// to stderr, and errors are printed as `{"error": "<message>"}` with a failing exit code. Fields
+// may be added in later versions, but the ones below keep their names and meaning.
+//
+// `list` prints an array of docsets:
+//
+//     {
+//       "slug": "rust",
+//       "version": "",                  version in `docs.json`, null if it's not there
+//       "release": "1.75.0",            same
+//       "downloaded": true,             downloaded with `download`
+//       "installed_version": "",        from the downloaded docset, null if unknown
+//       "installed_release": "1.74.0",  same
+//       "location": "user"              "user", "system", "zeal", or null if not downloaded
+//     }
+//
+// `search` prints the results, numbered the same way as for `--open`:
+//
+//     {
+//       "query": "bufreader",
+//       "docsets": ["rust"],
+//       "exact": [
+//         {"index": 1, "item": "std/io/struct.bufreader", "fragment": null, "type": "Structs",
+//          "docset": "rust"}
+//       ],
+//       "vague": [                      empty without `--precise`
+//         {"index": 2, "item": "std/io/index",
+//          "contexts": [{"text": "...", "start": 10, "end": 19}], "docset": "rust"}
+//       ]
+//     }
+//
+// `type` is empty for results of `--precise`. `start` and `end` are byte offsets of the match in
+// the UTF-8 `text`. With `--list-types`, `search` prints an array of
+// `{"docset": "rust", "type": "Structs", "count": 120}` instead.
+//
+// `open`, and `search --open`, print the page:
+//
+//     {
+//       "docset": "rust",               null with `open --html`
+//       "page": "std/io/struct.bufreader",  same
+//       "fragment": "method.borrow",    null when no fragment was given
+//       "fragment_found": true,         whether only the fragment is in `text`
+//       "path": "/home/user/.local/share/dedoc/docsets/rust/std/io/struct.bufreader.html",
+//       "text": "...",                  rendered text without colors, with link numbers
+//       "line_numbers": [12, 13, 20],   numbers of lines of `text` in the whole page
+//       "links": ["std/io/trait.read"]  targets of links, `links[0]` is `[1]` in `text`
+//     }
+//
+// With `--lines` or `--grep`, `text` only has the selected lines, and `links` still has every link
+// of the page or the fragment. With `--markdown`, `text` is markdown with links in place, and
+// `line_numbers` and `links` are empty.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::common::ResultS;
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+#[inline]
+pub(crate) fn set_json_output(value: bool) {
+    JSON_OUTPUT.store(value, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+pub(crate) fn print_json<T: Serialize + ?Sized>(value: &T) -> ResultS {
+    let mut stdout = std::io::stdout().lock();
+
+    serde_json::to_writer(&mut stdout, value)
+        .map_err(|err| format!("Could not write JSON: {err}"))?;
+    writeln!(stdout).map_err(|err| format!("Could not write JSON: {err}"))
+}
+
+#[derive(Serialize)]
+pub(crate) struct PageJson<'a> {
+    pub docset: Option<&'a str>,
+    pub page: Option<&'a str>,
+    pub fragment: Option<&'a str>,
+    pub fragment_found: bool,
+    pub path: String,
+    pub text: String,
+    pub line_numbers: Vec<usize>,
+    pub links: Vec<String>,
+}
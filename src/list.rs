@@ -1,21 +1,27 @@
+use serde::Serialize;
+
 use toiletcli::flags;
 use toiletcli::flags::*;
 
 use crate::common::ResultS;
 use crate::common::{
-    deserialize_docs_json, get_flag_error, get_local_docsets, is_docs_json_exists,
+    deserialize_docs_json, find_docset_root, format_docset_version, get_flag_error,
+    get_local_docsets, is_docs_json_exists, read_docset_manifest, Docs, DocsetRootKind,
 };
-use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET};
+use crate::common::{BOLD, GRAY, GREEN, PROGRAM_NAME, RESET, YELLOW};
+use crate::json::{is_json_output, print_json};
+use crate::zeal::get_zeal_docsets;
 
 fn show_list_help() -> ResultS {
     println!(
         "\
 {GREEN}USAGE{RESET}
     {BOLD}{PROGRAM_NAME} list{RESET} [-lan]
-    Show available docsets.
+    Show available docsets. Docsets of Zeal and Dash, found in `$DEDOC_ZEAL_DIR` or in the
+    docsets directory of Zeal, are tagged with `[zeal]`.
 
 {GREEN}OPTIONS{RESET}
-    -l, --local                     Show only local docsets.
+    -l, --local                     Show only local docsets, with installed and available versions.
     -a, --all                       Show all version-specific docsets.
     -n, --newlines                  Print each docset on a separate line.
         --help                      Display help message."
@@ -23,6 +29,109 @@ fn show_list_help() -> ResultS {
     Ok(())
 }
 
+// Returns e.g. ` 1.71.0 -> 1.72.0` for outdated docsets and ` 1.72.0` for up-to-date ones.
+fn get_version_info(docset_name: &str, docs: &[Docs]) -> Result<String, String> {
+    let manifest = read_docset_manifest(docset_name)?;
+    let entry = docs.iter().find(|entry| entry.slug == docset_name);
+
+    let installed_version = manifest
+        .as_ref()
+        .map(|manifest| format_docset_version(&manifest.version, &manifest.release))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let info = match (manifest, entry) {
+        (Some(manifest), Some(entry)) if !manifest.is_outdated(entry) => {
+            format!(" {GRAY}{installed_version}{RESET}")
+        }
+        (_, Some(entry)) => {
+            let available_version = format_docset_version(&entry.version, &entry.release);
+            format!(" {GRAY}{installed_version}{RESET} {YELLOW}-> {available_version}{RESET}")
+        }
+        (_, None) => format!(" {GRAY}{installed_version}, no longer available{RESET}"),
+    };
+
+    Ok(info)
+}
+
+// Docsets from system directories are shared by every user.
+fn get_docset_tag(docset_name: &str) -> Result<&'static str, String> {
+    match find_docset_root(docset_name)? {
+        Some(root) if root.kind == DocsetRootKind::System => Ok("[system]"),
+        _ => Ok("[downloaded]"),
+    }
+}
+
+// A docset in the JSON output of `list`. The schema is in `src/json.rs`.
+#[derive(Serialize)]
+struct DocsetJson {
+    slug: String,
+    version: Option<String>,
+    release: Option<String>,
+    downloaded: bool,
+    installed_version: Option<String>,
+    installed_release: Option<String>,
+    location: Option<&'static str>,
+}
+
+fn get_docset_json(docset_name: &str, docs: &[Docs]) -> Result<DocsetJson, String> {
+    let entry = docs.iter().find(|entry| entry.slug == docset_name);
+
+    let mut docset_json = DocsetJson {
+        slug: docset_name.to_owned(),
+        version: entry.map(|entry| entry.version.clone()),
+        release: entry.map(|entry| entry.release.clone()),
+        downloaded: false,
+        installed_version: None,
+        installed_release: None,
+        location: None,
+    };
+
+    if let Some(root) = find_docset_root(docset_name)? {
+        let manifest = read_docset_manifest(docset_name)?;
+
+        docset_json.downloaded = true;
+        docset_json.installed_version = manifest.as_ref().map(|manifest| manifest.version.clone());
+        docset_json.installed_release = manifest.map(|manifest| manifest.release);
+        docset_json.location = match root.kind {
+            DocsetRootKind::User => Some("user"),
+            DocsetRootKind::System => Some("system"),
+        };
+    }
+
+    Ok(docset_json)
+}
+
+fn print_docsets_json(
+    local_docsets: &[String],
+    zeal_docsets: &[String],
+    flag_local: bool,
+    flag_all: bool,
+) -> ResultS {
+    let docs = deserialize_docs_json()?;
+    let mut docsets = vec![];
+
+    if flag_local {
+        for docset_name in local_docsets {
+            docsets.push(get_docset_json(docset_name, &docs)?);
+        }
+    } else {
+        for entry in docs.iter() {
+            if !flag_all && entry.slug.contains('~') {
+                continue;
+            }
+            docsets.push(get_docset_json(&entry.slug, &docs)?);
+        }
+    }
+
+    for docset_name in zeal_docsets {
+        let mut docset_json = get_docset_json(docset_name, &docs)?;
+        docset_json.location = Some("zeal");
+        docsets.push(docset_json);
+    }
+
+    print_json(&docsets)
+}
+
 pub(crate) fn list<Args>(mut args: Args) -> ResultS
 where
     Args: Iterator<Item = String>,
@@ -49,22 +158,40 @@ where
     }
 
     let local_docsets = get_local_docsets()?;
+    let zeal_docsets = get_zeal_docsets()?;
+
+    if is_json_output() {
+        return print_docsets_json(&local_docsets, &zeal_docsets, flag_local, flag_all);
+    }
 
     let separator = if flag_newlines { "\n" } else { ", " };
 
     if flag_local {
-        let mut local_docsets_iter_peekable = local_docsets.iter().peekable();
+        let docs = deserialize_docs_json()?;
+        let mut items = vec![];
+
+        for entry in local_docsets.iter() {
+            let mut item = format!("{GREEN}{} {}{RESET}", entry, get_docset_tag(entry)?);
 
-        while let Some(entry) = local_docsets_iter_peekable.next() {
-            print!("{GREEN}{} [downloaded]{RESET}", entry);
+            // Keep the output of `-n` suitable for `download`.
+            if !flag_newlines {
+                item += &get_version_info(entry, &docs)?;
+            }
+
+            items.push(item);
+        }
 
-            if local_docsets_iter_peekable.peek().is_some() {
-                print!("{}", separator);
-            } else {
-                println!();
+        // Zeal docsets can't be downloaded, so they are left out of `-n` too.
+        if !flag_newlines {
+            for entry in zeal_docsets.iter() {
+                items.push(format!("{GREEN}{} [zeal]{RESET}", entry));
             }
         }
 
+        if !items.is_empty() {
+            println!("{}", items.join(separator));
+        }
+
         return Ok(());
     }
 
@@ -83,17 +210,26 @@ where
         }
 
         if local_docsets.contains(entry) {
-            print!("{GREEN}{} [downloaded]{RESET}", entry);
+            print!("{GREEN}{} {}{RESET}", entry, get_docset_tag(entry)?);
         } else {
             print!("{}", entry);
         }
 
-        if docs_names_peekable.peek().is_some() {
+        if docs_names_peekable.peek().is_some() || !zeal_docsets.is_empty() {
             print!("{}", separator);
         } else {
             println!();
         }
     }
 
+    if !zeal_docsets.is_empty() {
+        let zeal_docsets_list = zeal_docsets
+            .iter()
+            .map(|entry| format!("{GREEN}{} [zeal]{RESET}", entry))
+            .collect::<Vec<String>>();
+
+        println!("{}", zeal_docsets_list.join(separator));
+    }
+
     Ok(())
 }
@@ -10,22 +10,45 @@ use common::get_flag_error;
 use common::ResultS;
 use common::{BOLD, GRAY, GREEN, PROGRAM_NAME, RED, RESET, UNDERLINE, VERSION};
 
+mod config;
+mod doctor;
 mod download;
 mod fetch;
+mod fuzzy;
+mod highlight;
+mod html;
+mod index;
+mod json;
 mod list;
+mod markdown;
+mod mirror;
 mod open;
+mod pack;
+mod prerender;
 mod remove;
+mod sanitize;
 mod search;
+mod theme;
+mod tui;
+mod update;
+mod zeal;
 
 #[cfg(debug_assertions)]
 mod test;
 
+use config::{config, get_config};
+use doctor::doctor;
 use download::download;
 use fetch::fetch;
+use index::index;
+use json::{is_json_output, set_json_output};
 use list::list;
 use open::open;
 use remove::remove;
 use search::search;
+use theme::{get_theme, read_theme, set_theme};
+use tui::tui;
+use update::update;
 
 #[cfg(debug_assertions)]
 use test::debug_test;
@@ -58,13 +81,21 @@ fn show_help() -> ResultS {
     fetch{GRAY}, ft{RESET}                       Fetch available docsets.
     list{GRAY}, ls{RESET}                        Show available docsets.
     download{GRAY}, dl{RESET}                    Download docsets.
+    update{GRAY}, up{RESET}                      Update outdated docsets.
     remove{GRAY}, rm{RESET}                      Delete docsets.
+    index{GRAY}, ix{RESET}                       Build full-text search index.
+    doctor{GRAY}, dr{RESET}                      Find and repair broken docsets.
     search{GRAY}, ss{RESET}                      List pages that match your query.
     open{GRAY}, op{RESET}                        Display specified pages.
+    tui{GRAY}, ui{RESET}                         Interactively search and read pages.
+    config{GRAY}, cf{RESET}                      Show or change settings.
 
 {GREEN}OPTIONS{RESET}
     -c, --force-colors              Forcefully enable colors.
         --color <on/off/auto>       Control output colors.
+        --theme <name>              Color theme: `dark`, `light`, `none` or a theme file.
+        --json                      Print results of `list`, `search` and `open` as JSON.
+        --format <text/json>        Same as above, with `json`.
     -v, --version                   Display version.
         --help                      Display help message."
     );
@@ -81,12 +112,18 @@ where
     let mut flag_version;
     let mut flag_color;
     let mut flag_color_force;
+    let mut flag_theme;
+    let mut flag_json;
+    let mut flag_format;
     let mut flag_help;
 
     let mut flags = flags![
         flag_version: BoolFlag,     ["-v", "--version"],
         flag_color_force: BoolFlag, ["-c", "--force-colors"],
         flag_color: StringFlag,     ["--color"],
+        flag_theme: StringFlag,     ["--theme"],
+        flag_json: BoolFlag,        ["--json"],
+        flag_format: StringFlag,    ["--format"],
         flag_help: BoolFlag,        ["--help"]
     ];
 
@@ -97,10 +134,15 @@ where
     if flag_color_force {
         unsafe { overwrite_should_use_colors(true) }
     }
+    // Flags take precedence over the config.
+    if !flag_color_force && flag_color.is_empty() {
+        flag_color = get_config()?.color.unwrap_or_default();
+    }
     if !flag_color.is_empty() {
         match flag_color.as_str() {
             "y" | "yes" | "on" => unsafe { overwrite_should_use_colors(true) },
             "n" | "no" | "off" => unsafe { overwrite_should_use_colors(false) },
+            "auto" => {}
             other => {
                 return Err(format!(
                     "Argument `{other}` for `--color <on/off/auto>` is invalid."
@@ -108,6 +150,29 @@ where
             }
         }
     }
+    if !flag_theme.is_empty() {
+        set_theme(read_theme(&flag_theme)?);
+    } else if let Some(config_theme) = get_config()?.theme {
+        // A theme that's gone from the config shouldn't stop `config unset theme`.
+        match read_theme(&config_theme) {
+            Ok(theme) => set_theme(theme),
+            Err(err) => print_warning!("{err} The default theme is used instead."),
+        }
+    }
+    match flag_format.as_str() {
+        "" | "text" => {}
+        "json" => flag_json = true,
+        other => {
+            return Err(format!(
+                "Argument `{other}` for `--format <text/json>` is invalid."
+            ));
+        }
+    }
+    if flag_json {
+        set_json_output(true);
+        // Text in JSON should be plain.
+        unsafe { overwrite_should_use_colors(false) }
+    }
     if flag_version {
         return show_version();
     }
@@ -119,9 +184,14 @@ where
         "ft" | "fetch" => fetch(args),
         "ls" | "list" => list(args),
         "dl" | "download" => download(args),
+        "up" | "update" => update(args),
         "rm" | "remove" => remove(args),
+        "ix" | "index" => index(args),
+        "dr" | "doctor" => doctor(args),
         "ss" | "search" => search(args),
         "op" | "open" => open(args),
+        "ui" | "tui" => tui(args),
+        "cf" | "config" => config(args),
         #[cfg(debug_assertions)]
         "test" => debug_test(args),
         other => Err(format!("Unknown subcommand `{other}`")),
@@ -133,11 +203,15 @@ fn main() -> ExitCode {
     let _ = &args.next().expect("Program path is provided");
 
     match entry(&mut args) {
+        Err(err) if is_json_output() => {
+            println!("{}", serde_json::json!({ "error": err }));
+            ExitCode::FAILURE
+        }
         Err(mut err) => {
             if !err.ends_with(['.', '?', ')']) {
                 err += ". Try `--help` for more information.";
             }
-            println!("{RED}ERROR{RESET}: {err}");
+            println!("{}ERROR{RESET}: {err}", get_theme().error);
             ExitCode::FAILURE
         }
         _ => ExitCode::SUCCESS,
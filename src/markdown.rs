@@ -0,0 +1,886 @@
+// Renders HTML of pages as CommonMark for `open --markdown`, so they can be piped to markdown
+// readers or kept in notes. Headings, emphasis, inline code, links, images, lists, quotes and code
+// blocks are converted, with the language of code blocks taken from `data-language`. Tables are
+// written like in GitHub Flavored Markdown, which most readers support. Other elements are reduced
+// to their text.
+
+use crate::html::{decode_entities, Tag, Token, Tokenizer};
+
+// Elements that are skipped with everything inside of them.
+const SKIPPED_TAGS: &[&str] = &["head", "script", "style", "noscript", "template"];
+
+// Elements that separate paragraphs.
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "body",
+    "caption",
+    "center",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "html",
+    "legend",
+    "main",
+    "nav",
+    "p",
+    "section",
+    "summary",
+];
+
+// Stands for `<br>` in inline text, until whitespace is collapsed.
+const LINE_BREAK: char = '\0';
+
+#[derive(PartialEq)]
+enum InlineKind {
+    Root,
+    // Elements like `<a>` without `href`, which only hold text.
+    Plain,
+    Strong,
+    Emphasis,
+    Code,
+    Link(String),
+}
+
+struct Inline {
+    tag_name: String,
+    kind: InlineKind,
+    text: String,
+}
+
+struct List {
+    is_ordered: bool,
+    next_number: usize,
+    has_items: bool,
+    has_open_item: bool,
+}
+
+struct CodeBlock {
+    language: String,
+    text: String,
+}
+
+struct Table {
+    rows: Vec<Vec<String>>,
+    is_in_cell: bool,
+    // Tables inside of cells are flattened into text.
+    nested_tables: usize,
+}
+
+struct MarkdownWriter {
+    output: String,
+    // Inline elements that are not closed yet. The first one is the text of the current block.
+    inlines: Vec<Inline>,
+    // Prefixes of lines in nested blocks, like `> ` for quotes and indentation of list items.
+    prefixes: Vec<String>,
+    // Prefix of the last written block.
+    last_prefix: String,
+    // Marker of the list item that has no lines yet, e.g. `- ` or `1. `.
+    list_marker: Option<String>,
+    lists: Vec<List>,
+    heading_level: Option<usize>,
+    code_block: Option<CodeBlock>,
+    table: Option<Table>,
+}
+
+fn get_heading_level(tag_name: &str) -> Option<usize> {
+    match tag_name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+// Block elements that can't be in cells of tables, which are reduced to their text there.
+fn is_structural_tag(tag_name: &str) -> bool {
+    BLOCK_TAGS.contains(&tag_name)
+        || ["ul", "ol", "li", "blockquote", "hr"].contains(&tag_name)
+        || get_heading_level(tag_name).is_some()
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let characters: Vec<char> = text.chars().collect();
+
+    for (i, ch) in characters.iter().enumerate() {
+        let previous = i.checked_sub(1).and_then(|i| characters.get(i));
+        let next = characters.get(i + 1);
+
+        let is_escaped = match ch {
+            '\\' | '`' | '*' | '<' => true,
+            // Brackets only make links when they are followed by these.
+            ']' => matches!(next, Some('(' | '[' | ':')),
+            // `_` only starts emphasis at the edges of words.
+            '_' => {
+                !matches!(previous, Some(ch) if ch.is_alphanumeric())
+                    || !matches!(next, Some(ch) if ch.is_alphanumeric())
+            }
+            // Only what looks like an entity, e.g. `&lt;`.
+            '&' => {
+                let name_length = characters[i + 1..]
+                    .iter()
+                    .take_while(|ch| ch.is_ascii_alphanumeric() || **ch == '#')
+                    .count();
+                name_length > 0 && characters.get(i + 1 + name_length) == Some(&';')
+            }
+            _ => false,
+        };
+
+        if is_escaped {
+            escaped.push('\\');
+        }
+        escaped.push(*ch);
+    }
+
+    escaped
+}
+
+// Escapes the start of a paragraph that would be read as a heading, a quote or a list.
+fn escape_block_start(text: &str) -> String {
+    if text.starts_with(['#', '>', '-', '+', '=']) {
+        return format!("\\{text}");
+    }
+
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+
+    if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        return format!("{}\\{}", &text[..digits], &text[digits..]);
+    }
+
+    text.to_owned()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut is_after_whitespace = false;
+
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            is_after_whitespace = true;
+            continue;
+        }
+
+        if is_after_whitespace && !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+
+        is_after_whitespace = false;
+        collapsed.push(ch);
+    }
+
+    collapsed
+}
+
+// Backticks that are longer than any run of backticks in `text`.
+fn get_fence(text: &str, min_length: usize) -> String {
+    let mut longest_run = 0;
+    let mut run = 0;
+
+    for ch in text.chars() {
+        if ch == '`' {
+            run += 1;
+            longest_run = longest_run.max(run);
+        } else {
+            run = 0;
+        }
+    }
+
+    "`".repeat(min_length.max(longest_run + 1))
+}
+
+fn format_code_span(text: &str) -> String {
+    let fence = get_fence(text, 1);
+
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{fence} {text} {fence}")
+    } else {
+        format!("{fence}{text}{fence}")
+    }
+}
+
+fn format_link_destination(href: &str) -> String {
+    href.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+}
+
+impl MarkdownWriter {
+    fn new() -> Self {
+        MarkdownWriter {
+            output: String::new(),
+            inlines: vec![Inline {
+                tag_name: String::new(),
+                kind: InlineKind::Root,
+                text: String::new(),
+            }],
+            prefixes: vec![],
+            last_prefix: String::new(),
+            list_marker: None,
+            lists: vec![],
+            heading_level: None,
+            code_block: None,
+            table: None,
+        }
+    }
+
+    #[inline]
+    fn is_in_cell(&self) -> bool {
+        matches!(&self.table, Some(table) if table.is_in_cell)
+    }
+
+    #[inline]
+    fn is_in_code(&self) -> bool {
+        self.inlines
+            .iter()
+            .any(|inline| inline.kind == InlineKind::Code)
+    }
+
+    fn push_inline_text(&mut self, text: &str) {
+        if let Some(inline) = self.inlines.last_mut() {
+            inline.text.push_str(text);
+        }
+    }
+
+    // Writes lines of a block, separated from the previous block by an empty line, or by a line
+    // break for items of the same list.
+    fn write_block(&mut self, block: &str) {
+        let prefix = self.prefixes.concat();
+
+        let mut first_prefix = prefix.clone();
+
+        if let Some(marker) = self.list_marker.take() {
+            let parent_prefixes = &self.prefixes[..self.prefixes.len().saturating_sub(1)];
+            first_prefix = parent_prefixes.concat() + &marker;
+        }
+
+        let is_list_item = first_prefix != prefix;
+        let is_in_tight_list = is_list_item && self.lists.iter().any(|list| list.has_items);
+
+        if !self.output.is_empty() {
+            self.output.push('\n');
+
+            if !is_in_tight_list {
+                // The empty line only continues quotes that both blocks are in.
+                let common_length = prefix
+                    .chars()
+                    .zip(self.last_prefix.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(ch, _)| ch.len_utf8())
+                    .sum();
+
+                self.output.push_str(prefix[..common_length].trim_end());
+                self.output.push('\n');
+            }
+        }
+
+        self.last_prefix = prefix.clone();
+
+        if is_list_item {
+            if let Some(list) = self.lists.last_mut() {
+                list.has_items = true;
+            }
+        }
+
+        for (i, line) in block.split('\n').enumerate() {
+            if i > 0 {
+                self.output.push('\n');
+            }
+
+            if line.is_empty() {
+                self.output.push_str(prefix.trim_end());
+            } else if i == 0 {
+                self.output.push_str(&first_prefix);
+            } else {
+                self.output.push_str(&prefix);
+            }
+
+            self.output.push_str(line);
+        }
+    }
+
+    // Closes inline elements that were left open, and returns the text of the current block with
+    // collapsed whitespace.
+    fn take_inline_text(&mut self) -> String {
+        while self.inlines.len() > 1 {
+            if let Some(inline) = self.inlines.pop() {
+                self.push_inline_text(&inline.text);
+            }
+        }
+
+        let text = std::mem::take(&mut self.inlines[0].text);
+
+        collapse_whitespace(&text)
+    }
+
+    fn end_paragraph(&mut self) {
+        if self.heading_level.is_some() {
+            return;
+        }
+
+        if self.is_in_cell() {
+            self.push_inline_text(" ");
+            return;
+        }
+
+        let text = self.take_inline_text();
+
+        let mut lines: Vec<&str> = text
+            .split(LINE_BREAK)
+            .map(str::trim)
+            .skip_while(|line| line.is_empty())
+            .collect();
+
+        while matches!(lines.last(), Some(line) if line.is_empty()) {
+            lines.pop();
+        }
+
+        if !lines.is_empty() {
+            self.write_block(&escape_block_start(&lines.join("\\\n")));
+        }
+    }
+
+    fn close_inline(&mut self) {
+        let Some(inline) = self.inlines.pop() else {
+            return;
+        };
+
+        let is_in_code = self.is_in_code();
+
+        let text = match inline.kind {
+            InlineKind::Code => collapse_whitespace(&inline.text.replace(LINE_BREAK, " ")),
+            _ => inline.text,
+        };
+
+        let trimmed = text.trim_matches(|ch: char| ch.is_whitespace() || ch == LINE_BREAK);
+
+        if trimmed.is_empty() || is_in_code {
+            self.push_inline_text(&text);
+            return;
+        }
+
+        let formatted = match inline.kind {
+            InlineKind::Root | InlineKind::Plain => trimmed.to_owned(),
+            InlineKind::Strong => format!("**{trimmed}**"),
+            InlineKind::Emphasis => format!("*{trimmed}*"),
+            InlineKind::Code => format_code_span(trimmed),
+            InlineKind::Link(href) => format!("[{trimmed}]({})", format_link_destination(&href)),
+        };
+
+        // Whitespace is moved outside, since markers can't be next to it.
+        if text.starts_with(char::is_whitespace) {
+            self.push_inline_text(" ");
+        }
+        self.push_inline_text(&formatted);
+        if text.ends_with(char::is_whitespace) {
+            self.push_inline_text(" ");
+        }
+    }
+
+    fn open_inline(&mut self, tag_name: &str, kind: InlineKind) {
+        self.inlines.push(Inline {
+            tag_name: tag_name.to_owned(),
+            kind,
+            text: String::new(),
+        });
+    }
+
+    fn end_inline(&mut self, tag_name: &str) {
+        let Some(position) = self
+            .inlines
+            .iter()
+            .rposition(|inline| inline.kind != InlineKind::Root && inline.tag_name == tag_name)
+        else {
+            return;
+        };
+
+        while self.inlines.len() > position {
+            self.close_inline();
+        }
+    }
+
+    fn write_code_block(&mut self) {
+        let Some(code_block) = self.code_block.take() else {
+            return;
+        };
+
+        // A line break right after `<pre>` is not displayed.
+        let text = code_block
+            .text
+            .strip_prefix('\n')
+            .unwrap_or(&code_block.text);
+        let text = text.trim_end();
+
+        let fence = get_fence(text, 3);
+        let block = format!("{fence}{}\n{text}\n{fence}", code_block.language);
+
+        self.write_block(&block);
+    }
+
+    fn write_table(&mut self) {
+        let Some(table) = self.table.take() else {
+            return;
+        };
+
+        let rows: Vec<Vec<String>> = table
+            .rows
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        let column_count = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        if column_count == 0 {
+            return;
+        }
+
+        let mut widths = vec![3; column_count];
+        for row in rows.iter() {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+
+        let format_row = |row: &[String]| {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(i, width)| {
+                    let cell = row.get(i).map(String::as_str).unwrap_or_default();
+                    format!("{cell:width$}")
+                })
+                .collect();
+
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let mut lines = vec![format_row(&rows[0])];
+
+        let separators: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        lines.push(format!("| {} |", separators.join(" | ")));
+
+        for row in rows[1..].iter() {
+            lines.push(format_row(row));
+        }
+
+        self.write_block(&lines.join("\n"));
+    }
+
+    fn start_list_item(&mut self) {
+        self.end_paragraph();
+        self.end_list_item();
+
+        let Some(list) = self.lists.last_mut() else {
+            return;
+        };
+
+        let marker = if list.is_ordered {
+            list.next_number += 1;
+            format!("{}. ", list.next_number - 1)
+        } else {
+            "- ".to_string()
+        };
+
+        list.has_open_item = true;
+
+        self.prefixes.push(" ".repeat(marker.len()));
+        self.list_marker = Some(marker);
+    }
+
+    fn end_list_item(&mut self) {
+        if let Some(list) = self.lists.last_mut() {
+            if list.has_open_item {
+                list.has_open_item = false;
+                self.prefixes.pop();
+                self.list_marker = None;
+            }
+        }
+    }
+
+    // Returns `false` if the start tag is handled as an element of a code block or a table.
+    fn handle_special_start_tag(&mut self, tag: &Tag, tag_name: &str) -> bool {
+        if let Some(code_block) = self.code_block.as_mut() {
+            match tag_name {
+                "br" => code_block.text.push('\n'),
+                "code" if code_block.language.is_empty() => {
+                    if let Some(language) = tag.get_attribute("data-language") {
+                        code_block.language = language.split_whitespace().collect();
+                    }
+                }
+                _ => {}
+            }
+            return false;
+        }
+
+        let Some(table) = self.table.as_mut() else {
+            return true;
+        };
+
+        match tag_name {
+            "table" => {
+                table.nested_tables += 1;
+                false
+            }
+            "tr" if table.nested_tables == 0 => {
+                table.rows.push(vec![]);
+                false
+            }
+            "td" | "th" if table.nested_tables == 0 => {
+                if table.rows.is_empty() {
+                    table.rows.push(vec![]);
+                }
+                table.is_in_cell = true;
+                // Whitespace between cells.
+                self.take_inline_text();
+                false
+            }
+            "tr" | "td" | "th" => {
+                self.push_inline_text(" ");
+                false
+            }
+            _ => true,
+        }
+    }
+
+    // Returns `false` if the end tag is handled as an element of a code block or a table.
+    fn handle_special_end_tag(&mut self, tag_name: &str) -> bool {
+        if self.code_block.is_some() {
+            if tag_name == "pre" {
+                self.write_code_block();
+            }
+            return false;
+        }
+
+        let Some(table) = self.table.as_mut() else {
+            return true;
+        };
+
+        match tag_name {
+            "table" if table.nested_tables > 0 => {
+                table.nested_tables -= 1;
+                false
+            }
+            "table" => {
+                self.end_paragraph();
+                self.write_table();
+                false
+            }
+            "td" | "th" if table.nested_tables == 0 && table.is_in_cell => {
+                table.is_in_cell = false;
+
+                let cell = self
+                    .take_inline_text()
+                    .replace(LINE_BREAK, " ")
+                    .replace('|', "\\|");
+                let cell = collapse_whitespace(&cell);
+
+                if let Some(table) = self.table.as_mut() {
+                    if let Some(row) = table.rows.last_mut() {
+                        row.push(cell);
+                    }
+                }
+                false
+            }
+            "tr" | "td" | "th" => false,
+            _ => true,
+        }
+    }
+
+    fn start_tag(&mut self, tag: &Tag, tag_name: &str) {
+        if !self.handle_special_start_tag(tag, tag_name) {
+            return;
+        }
+
+        if self.is_in_cell() && is_structural_tag(tag_name) {
+            self.push_inline_text(" ");
+            return;
+        }
+
+        if let Some(level) = get_heading_level(tag_name) {
+            self.end_paragraph();
+            self.heading_level = Some(level);
+            return;
+        }
+
+        match tag_name {
+            _ if BLOCK_TAGS.contains(&tag_name) => self.end_paragraph(),
+            "br" if self.is_in_cell() => self.push_inline_text(" "),
+            "br" => self.push_inline_text(&LINE_BREAK.to_string()),
+            "hr" => {
+                self.end_paragraph();
+                self.write_block("---");
+            }
+            "pre" if self.is_in_cell() => self.open_inline(tag_name, InlineKind::Code),
+            "pre" => {
+                self.end_paragraph();
+                let language = tag
+                    .get_attribute("data-language")
+                    .map(|language| language.split_whitespace().collect())
+                    .unwrap_or_default();
+                self.code_block = Some(CodeBlock {
+                    language,
+                    text: String::new(),
+                });
+            }
+            "table" => {
+                self.end_paragraph();
+                self.table = Some(Table {
+                    rows: vec![],
+                    is_in_cell: false,
+                    nested_tables: 0,
+                });
+            }
+            "ul" | "ol" => {
+                self.end_paragraph();
+                let start = tag
+                    .get_attribute("start")
+                    .and_then(|start| start.trim().parse().ok())
+                    .unwrap_or(1);
+                self.lists.push(List {
+                    is_ordered: tag_name == "ol",
+                    next_number: start,
+                    has_items: false,
+                    has_open_item: false,
+                });
+            }
+            "li" => self.start_list_item(),
+            "blockquote" => {
+                self.end_paragraph();
+                self.prefixes.push("> ".to_string());
+            }
+            "img" => {
+                let source = tag.get_attribute("src").unwrap_or_default();
+                if !source.is_empty() {
+                    let alt = escape_text(&tag.get_attribute("alt").unwrap_or_default());
+                    let image = format!("![{alt}]({})", format_link_destination(&source));
+                    self.push_inline_text(&image);
+                }
+            }
+            _ if tag.is_self_closing => {}
+            "strong" | "b" => self.open_inline(tag_name, InlineKind::Strong),
+            "em" | "i" | "var" | "cite" => self.open_inline(tag_name, InlineKind::Emphasis),
+            "code" | "kbd" | "samp" | "tt" => self.open_inline(tag_name, InlineKind::Code),
+            "a" => match tag.get_attribute("href") {
+                Some(href) if !href.is_empty() => {
+                    self.open_inline(tag_name, InlineKind::Link(href.into_owned()))
+                }
+                _ => self.open_inline(tag_name, InlineKind::Plain),
+            },
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, tag_name: &str) {
+        if !self.handle_special_end_tag(tag_name) {
+            return;
+        }
+
+        if self.is_in_cell() && is_structural_tag(tag_name) {
+            self.push_inline_text(" ");
+            return;
+        }
+
+        if let Some(level) = get_heading_level(tag_name) {
+            if self.heading_level.take().is_none() {
+                return;
+            }
+
+            let text = self.take_inline_text().replace(LINE_BREAK, " ");
+            let text = collapse_whitespace(&text);
+
+            if !text.is_empty() {
+                self.write_block(&format!("{} {text}", "#".repeat(level)));
+            }
+            return;
+        }
+
+        match tag_name {
+            _ if BLOCK_TAGS.contains(&tag_name) => self.end_paragraph(),
+            "ul" | "ol" => {
+                self.end_paragraph();
+                self.end_list_item();
+                self.lists.pop();
+            }
+            "li" => {
+                self.end_paragraph();
+                self.end_list_item();
+            }
+            "blockquote" => {
+                self.end_paragraph();
+                if matches!(self.prefixes.last(), Some(prefix) if prefix == "> ") {
+                    self.prefixes.pop();
+                }
+            }
+            _ => self.end_inline(tag_name),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let decoded = decode_entities(text);
+
+        if let Some(code_block) = self.code_block.as_mut() {
+            code_block.text.push_str(&decoded);
+        } else if self.is_in_code() {
+            self.push_inline_text(&decoded);
+        } else {
+            self.push_inline_text(&escape_text(&decoded));
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.end_paragraph();
+        self.write_code_block();
+        self.write_table();
+
+        self.output
+    }
+}
+
+// Returns markdown of the page, or only of `fragment` if it's found, and whether it was found.
+// Like with text, a fragment lasts until the next element with an `id`.
+pub(crate) fn html_to_markdown(html: &str, fragment: Option<&str>) -> (String, bool) {
+    let mut writer = MarkdownWriter::new();
+    let mut tokenizer = Tokenizer::new(html);
+
+    let lowercase_fragment = fragment.map(str::to_lowercase);
+    let mut fragment_start = None;
+    let mut fragment_end = None;
+
+    while let Some(token) = tokenizer.next() {
+        let tag = match token {
+            Token::Text(text) => {
+                writer.text(text);
+                continue;
+            }
+            Token::Markup(_) => continue,
+            Token::Tag(tag) => tag,
+        };
+
+        let tag_name = tag.name.to_ascii_lowercase();
+
+        if tag.is_end {
+            writer.end_tag(&tag_name);
+            continue;
+        }
+
+        if SKIPPED_TAGS.contains(&tag_name.as_str()) {
+            if !tag.is_self_closing {
+                tokenizer.skip_element(&tag_name);
+            }
+            continue;
+        }
+
+        if let (Some(lowercase_fragment), Some(id)) = (&lowercase_fragment, tag.get_attribute("id"))
+        {
+            if fragment_start.is_some() {
+                writer.end_paragraph();
+                fragment_end = Some(writer.output.len());
+                break;
+            }
+
+            if id.to_lowercase() == *lowercase_fragment {
+                writer.end_paragraph();
+                fragment_start = Some(writer.output.len());
+            }
+        }
+
+        writer.start_tag(&tag, &tag_name);
+    }
+
+    let output = if fragment_end.is_some() {
+        writer.output
+    } else {
+        writer.finish()
+    };
+
+    let markdown = match fragment_start {
+        Some(start) => &output[start..fragment_end.unwrap_or(output.len())],
+        None => &output,
+    };
+
+    (markdown.trim().to_owned(), fragment_start.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"
+<h1>Struct std::vec::Vec</h1>
+<p>A <em>contiguous</em> growable array type, written as <code>Vec&lt;T&gt;</code>, see
+<a href="../boxed/struct.Box.html">Box</a> and <strong> more </strong>.<br>Next line_with_underscores, *stars* and [brackets](not a link).</p>
+<pre data-language="rust">
+let mut vec = Vec::new();
+vec.push(1);
+</pre>
+<section id="method.push">
+  <h3>pub fn push(&amp;mut self, value: T)</h3>
+  <ul>
+    <li>First</li>
+    <li>Second with <code>`tick`</code>
+      <ol start="3"><li>Nested</li></ol>
+    </li>
+  </ul>
+  <blockquote><p>Quoted</p><p>twice</p></blockquote>
+</section>
+<section id="method.pop">
+  <table>
+    <tr><th>Name</th><th>Description</th></tr>
+    <tr><td>pop</td><td>Removes the <code>last</code> | element</td></tr>
+  </table>
+  <p>1. Not a list</p>
+</section>
+"#;
+
+        let should_be = r#"# Struct std::vec::Vec
+
+A *contiguous* growable array type, written as `Vec<T>`, see [Box](../boxed/struct.Box.html) and **more** .\
+Next line_with_underscores, \*stars\* and [brackets\](not a link).
+
+```rust
+let mut vec = Vec::new();
+vec.push(1);
+```
+
+### pub fn push(&mut self, value: T)
+
+- First
+- Second with `` `tick` ``
+  3. Nested
+
+> Quoted
+>
+> twice
+
+| Name | Description                   |
+| ---- | ----------------------------- |
+| pop  | Removes the `last` \| element |
+
+1\. Not a list"#;
+
+        let (markdown, is_fragment_found) = html_to_markdown(html, None);
+        assert_eq!(markdown, should_be);
+        assert!(!is_fragment_found);
+
+        let (markdown, is_fragment_found) = html_to_markdown(html, Some("Method.Push"));
+        assert!(is_fragment_found);
+        assert!(markdown.starts_with("### pub fn push"));
+        assert!(markdown.ends_with("> twice"));
+    }
+}
@@ -0,0 +1,532 @@
+// Where `docs.json` and docsets are downloaded from. A mirror has the same layout as DevDocs:
+//
+//     <mirror>/docs.json
+//     <mirror>/<docset>/db.json
+//     <mirror>/<docset>/index.json
+//
+// and can be an `http(s)://` URL, a `file://` URL or a plain local directory.
+//
+// DevDocs doesn't publish checksums of its files, so there's nothing to verify downloads against.
+// They are checked with `Content-Length` and `Content-Range` instead, and are parsed when extracted.
+
+use std::fs::{rename, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use attohttpc::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use attohttpc::{get, StatusCode};
+
+use crate::common::ResultS;
+use crate::common::{DEFAULT_DB_JSON_LINK, DEFAULT_DOCS_JSON_LINK, DEFAULT_USER_AGENT, VERSION};
+use crate::config::get_config;
+
+pub(crate) const MIRROR_ENV: &str = "DEDOC_MIRROR";
+pub(crate) const DOCS_JSON_URL_ENV: &str = "DEDOC_DOCS_JSON_URL";
+pub(crate) const DOCUMENTS_URL_ENV: &str = "DEDOC_DOCUMENTS_URL";
+
+pub(crate) const PART_EXTENSION: &str = "part";
+
+// How many times an interrupted download is resumed before giving up.
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+#[derive(Default, Debug, Clone)]
+struct MirrorSettings {
+    mirror: Option<String>,
+    docs_json_url: Option<String>,
+    documents_url: Option<String>,
+}
+
+impl MirrorSettings {
+    fn from_env() -> Self {
+        let get_env = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Self {
+            mirror: get_env(MIRROR_ENV),
+            docs_json_url: get_env(DOCS_JSON_URL_ENV),
+            documents_url: get_env(DOCUMENTS_URL_ENV),
+        }
+    }
+
+    fn get_docs_json_url(&self) -> Option<String> {
+        self.docs_json_url.clone().or_else(|| {
+            self.mirror
+                .as_ref()
+                .map(|mirror| format!("{}/docs.json", mirror.trim_end_matches('/')))
+        })
+    }
+
+    fn get_documents_url(&self) -> Option<String> {
+        self.documents_url.clone().or_else(|| self.mirror.clone())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mirror {
+    pub(crate) docs_json_url: String,
+    pub(crate) documents_url: String,
+}
+
+impl Mirror {
+    // Each URL is taken from the first settings that specify it, and defaults to DevDocs.
+    fn from_settings(settings: &[MirrorSettings]) -> Self {
+        let docs_json_url = settings
+            .iter()
+            .find_map(|settings| settings.get_docs_json_url())
+            .unwrap_or_else(|| DEFAULT_DOCS_JSON_LINK.to_string());
+
+        let documents_url = settings
+            .iter()
+            .find_map(|settings| settings.get_documents_url())
+            .unwrap_or_else(|| DEFAULT_DB_JSON_LINK.to_string());
+
+        Self {
+            docs_json_url,
+            documents_url: documents_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // `--mirror` takes precedence over environment variables, which take precedence over the config.
+    pub(crate) fn resolve(flag_mirror: &str) -> Result<Self, String> {
+        let config = get_config()?;
+
+        let flag_settings = MirrorSettings {
+            mirror: Some(flag_mirror.to_string()).filter(|mirror| !mirror.is_empty()),
+            ..Default::default()
+        };
+
+        let config_settings = MirrorSettings {
+            mirror: config.mirror,
+            docs_json_url: config.docs_json_url,
+            documents_url: config.documents_url,
+        };
+
+        Ok(Self::from_settings(&[
+            flag_settings,
+            MirrorSettings::from_env(),
+            config_settings,
+        ]))
+    }
+
+    pub(crate) fn get_docset_file_url(
+        &self,
+        docset_name: &str,
+        file_name: &str,
+        mtime: u64,
+    ) -> String {
+        let url = format!("{}/{docset_name}/{file_name}", self.documents_url);
+
+        // DevDocs uses modification time to bust caches.
+        if is_http_url(&url) {
+            format!("{url}?{mtime}")
+        } else {
+            url
+        }
+    }
+}
+
+#[inline]
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+// A reader that starts at `offset`, and the size of the whole file, if it's known.
+struct Download {
+    reader: Box<dyn Read>,
+    offset: u64,
+    length: Option<u64>,
+}
+
+fn open_http_url_from(url: &str, offset: u64) -> Result<Download, String> {
+    let user_agent = format!("{DEFAULT_USER_AGENT}/{VERSION}");
+
+    let mut request = get(url).header_append("user-agent", user_agent);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={offset}-"));
+    }
+
+    let response = request
+        .send()
+        .map_err(|err| format!("Could not GET `{url}`: {err}"))?;
+
+    // What was received before is already the whole file, or the file has changed since.
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return open_http_url_from(url, 0);
+    }
+
+    if !response.is_success() {
+        return Err(format!("Could not GET `{url}`: {}", response.status()));
+    }
+
+    let get_header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    let content_length = get_header(CONTENT_LENGTH).and_then(|value| value.parse::<u64>().ok());
+
+    // Servers that don't support ranges send the whole file. A range that doesn't start where the
+    // part ends can't be appended to it, so the download starts over.
+    let (offset, length) = if response.status() == StatusCode::PARTIAL_CONTENT {
+        match get_header(CONTENT_RANGE).and_then(parse_content_range) {
+            Some((start, total)) if start == offset => (
+                offset,
+                total.or_else(|| content_length.map(|length| offset + length)),
+            ),
+            _ if offset > 0 => return open_http_url_from(url, 0),
+            _ => {
+                return Err(format!(
+                    "Could not GET `{url}`: received a range that wasn't requested."
+                ))
+            }
+        }
+    } else {
+        (0, content_length)
+    };
+
+    Ok(Download {
+        reader: Box::new(response),
+        offset,
+        length,
+    })
+}
+
+// Parses `bytes <start>-<end>/<total>` into the start and the total, which can be `*` when unknown.
+fn parse_content_range(content_range: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = content_range.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+
+    let start = start.trim().parse::<u64>().ok()?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse::<u64>().ok()?),
+    };
+
+    Some((start, total))
+}
+
+// Opens an `http(s)://` URL, a `file://` URL or a local path for reading, starting at `offset`
+// when possible.
+fn open_url_from(url: &str, offset: u64) -> Result<Download, String> {
+    if is_http_url(url) {
+        return open_http_url_from(url, offset);
+    }
+
+    let path = url.strip_prefix("file://").unwrap_or(url);
+
+    let mut file = File::open(path).map_err(|err| format!("Could not open `{path}`: {err}"))?;
+
+    let length = file
+        .metadata()
+        .map_err(|err| format!("Could not read metadata of `{path}`: {err}"))?
+        .len();
+
+    let offset = if offset <= length { offset } else { 0 };
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|err| format!("Could not read `{path}`: {err}"))?;
+
+    Ok(Download {
+        reader: Box::new(file),
+        offset,
+        length: Some(length),
+    })
+}
+
+// Opens an `http(s)://` URL, a `file://` URL or a local path for reading.
+pub(crate) fn open_url(url: &str) -> Result<Box<dyn Read>, String> {
+    Ok(open_url_from(url, 0)?.reader)
+}
+
+// Bytes received so far and the size of the file, or 0 while it's unknown. Shared with the thread
+// that displays progress.
+#[derive(Default, Debug)]
+pub(crate) struct DownloadProgress {
+    pub(crate) received: AtomicU64,
+    pub(crate) total: AtomicU64,
+}
+
+// Appends the rest of the file to `part_path`. Fails if the connection breaks, or if less than
+// `Content-Length` was received.
+fn write_part(
+    url: &str,
+    download: Download,
+    part_path: &Path,
+    progress: &DownloadProgress,
+) -> ResultS {
+    let part_error =
+        |err: std::io::Error| format!("Could not write `{}`: {err}", part_path.display());
+
+    let mut options = OpenOptions::new();
+    if download.offset > 0 {
+        options.append(true);
+    } else {
+        options.write(true).create(true).truncate(true);
+    }
+
+    let file = options.open(part_path).map_err(part_error)?;
+    let mut writer = BufWriter::new(file);
+    let mut reader = download.reader;
+
+    let mut received = download.offset;
+    progress.received.store(received, Ordering::Relaxed);
+    progress
+        .total
+        .store(download.length.unwrap_or(0), Ordering::Relaxed);
+
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(format!("Could not download `{url}`: {err}")),
+        };
+
+        writer.write_all(&buffer[..size]).map_err(part_error)?;
+
+        received += size as u64;
+        progress.received.store(received, Ordering::Relaxed);
+    }
+
+    writer.flush().map_err(part_error)?;
+
+    match download.length {
+        Some(length) if received != length => Err(format!(
+            "Could not download `{url}`: received {received} of {length} bytes."
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Downloads `url` into `part_path`, which is renamed to `path` only when it's complete. An
+// existing `part_path` is resumed from where it stopped, as is a download that gets interrupted.
+pub(crate) fn download_file(
+    url: &str,
+    path: &Path,
+    part_path: &Path,
+    progress: &DownloadProgress,
+) -> ResultS {
+    let mut attempt = 1;
+
+    loop {
+        let received = part_path
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        let download = open_url_from(url, received)?;
+
+        match write_part(url, download, part_path, progress) {
+            Ok(()) => break,
+            Err(_) if attempt < DOWNLOAD_ATTEMPTS => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+
+    rename(part_path, path).map_err(|err| {
+        format!(
+            "Could not move `{}` to `{}`: {err}",
+            part_path.display(),
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TestDirectory;
+
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_mirror_settings() {
+        let settings = |mirror: Option<&str>, docs_json_url: Option<&str>| MirrorSettings {
+            mirror: mirror.map(String::from),
+            docs_json_url: docs_json_url.map(String::from),
+            documents_url: None,
+        };
+
+        assert_eq!(
+            Mirror::from_settings(&[]),
+            Mirror {
+                docs_json_url: DEFAULT_DOCS_JSON_LINK.to_string(),
+                documents_url: DEFAULT_DB_JSON_LINK.to_string(),
+            }
+        );
+
+        let mirror = Mirror::from_settings(&[
+            settings(None, None),
+            settings(Some("/srv/devdocs/"), None),
+            settings(
+                Some("https://mirror.local"),
+                Some("https://mirror.local/list.json"),
+            ),
+        ]);
+        assert_eq!(mirror.docs_json_url, "/srv/devdocs/docs.json");
+        assert_eq!(mirror.documents_url, "/srv/devdocs");
+        assert_eq!(
+            mirror.get_docset_file_url("rust", "db.json", 1700000000),
+            "/srv/devdocs/rust/db.json"
+        );
+
+        let mirror = Mirror::from_settings(&[
+            settings(None, Some("file:///srv/docs.json")),
+            settings(Some("http://mirror.local"), None),
+        ]);
+        assert_eq!(mirror.docs_json_url, "file:///srv/docs.json");
+        assert_eq!(
+            mirror.get_docset_file_url("rust", "db.json", 1700000000),
+            "http://mirror.local/rust/db.json?1700000000"
+        );
+    }
+
+    #[test]
+    fn test_open_url() {
+        let test_directory = TestDirectory::new("mirror");
+        let directory = &test_directory.path;
+        std::fs::create_dir_all(directory.join("rust")).unwrap();
+        std::fs::write(directory.join("rust").join("index.json"), "{}").unwrap();
+
+        let mirror = Mirror::from_settings(&[MirrorSettings {
+            mirror: Some(format!("file://{}", directory.display())),
+            ..Default::default()
+        }]);
+
+        let mut contents = String::new();
+        let result = open_url(&mirror.get_docset_file_url("rust", "index.json", 0)).and_then(
+            |mut reader| {
+                reader
+                    .read_to_string(&mut contents)
+                    .map_err(|err| err.to_string())
+            },
+        );
+        let missing = open_url(&directory.join("docs.json").display().to_string()).is_err();
+
+        result.unwrap();
+        assert_eq!(contents, "{}");
+        assert!(missing);
+
+        // A stand-in for a mirror that serves one file and then fails.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+
+                let response = if i == 0 {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]"
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let mirror = Mirror::from_settings(&[MirrorSettings {
+            mirror: Some(format!("http://{address}")),
+            ..Default::default()
+        }]);
+
+        let mut contents = String::new();
+        open_url(&mirror.docs_json_url)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "[]");
+
+        assert!(open_url(&mirror.get_docset_file_url("rust", "db.json", 0)).is_err());
+
+        server.join().unwrap();
+    }
+
+    // A stand-in for a mirror that sends `responses` to requests one after another. Returns the
+    // requests it got.
+    fn serve(responses: &'static [&'static str]) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let mut requests = vec![];
+
+            for (response, stream) in responses.iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let size = stream.read(&mut request).unwrap();
+                requests.push(String::from_utf8_lossy(&request[..size]).to_lowercase());
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+
+            requests
+        });
+
+        (format!("http://{address}/rust/db.json"), server)
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 5-9/10"), Some((5, Some(10))));
+        assert_eq!(parse_content_range("bytes 5-9/*"), Some((5, None)));
+        assert_eq!(parse_content_range("bytes */10"), None);
+        assert_eq!(parse_content_range("items 5-9/10"), None);
+    }
+
+    #[test]
+    fn test_download_file() {
+        // Drops the connection halfway, and then serves the rest.
+        let (url, server) = serve(&[
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n01234",
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\nContent-Length: 5\r\nConnection: close\r\n\r\n56789",
+        ]);
+
+        let test_directory = TestDirectory::new("download");
+        let path = test_directory.path.join("db.json");
+        let part_path = test_directory.path.join("db.json.part");
+        let progress = DownloadProgress::default();
+
+        let result = download_file(&url, &path, &part_path, &progress);
+        let contents = std::fs::read_to_string(&path);
+        let part_exists = part_path.exists();
+
+        result.unwrap();
+        assert_eq!(contents.unwrap(), "0123456789");
+        assert!(!part_exists);
+        assert_eq!(progress.received.load(Ordering::Relaxed), 10);
+        assert_eq!(progress.total.load(Ordering::Relaxed), 10);
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("range:"));
+        assert!(requests[1].contains("range: bytes=5-"));
+    }
+
+    #[test]
+    fn test_download_file_from_other_offset() {
+        // Sends a range that starts before the part ends, and then the whole file.
+        let (url, server) = serve(&[
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-9/10\r\nContent-Length: 7\r\nConnection: close\r\n\r\n3456789",
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n0123456789",
+        ]);
+
+        let test_directory = TestDirectory::new("download_offset");
+        let path = test_directory.path.join("db.json");
+        let part_path = test_directory.path.join("db.json.part");
+        std::fs::write(&part_path, "01234").unwrap();
+
+        let result = download_file(&url, &path, &part_path, &DownloadProgress::default());
+        let contents = std::fs::read_to_string(&path);
+
+        result.unwrap();
+        assert_eq!(contents.unwrap(), "0123456789");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("range: bytes=5-"));
+        assert!(!requests[1].contains("range:"));
+    }
+}
@@ -5,22 +5,31 @@ use toiletcli::flags::*;
 
 use crate::common::ResultS;
 use crate::common::{
-    deserialize_docs_json, get_flag_error, get_terminal_width, is_docs_json_exists,
-    is_docset_in_docs_or_print_warning, print_docset_file, print_page_from_docset,
-    split_to_item_and_fragment,
+    deserialize_docs_json, get_flag_error, get_page_width, is_docs_json_exists,
+    is_docset_in_docs_or_print_warning, parse_follow_flag, parse_page_options, print_docset_file,
+    print_page_from_docset, split_to_item_and_fragment,
 };
 use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET};
+use crate::json::is_json_output;
 use crate::print_warning;
+use crate::zeal::is_zeal_docset;
 
 fn show_open_help() -> ResultS {
     println!("\
 {GREEN}USAGE{RESET}
-    {BOLD}{PROGRAM_NAME} open{RESET} [-hc] <docset> <page>
+    {BOLD}{PROGRAM_NAME} open{RESET} [-hmnc] <docset> <page>
     Print a page. Pages can be searched using `search`.
+    Links are numbered, and listed at the end of the page.
 
 {GREEN}OPTIONS{RESET}
     -h, --html                      Interpret arguments as a path to HTML file and translate it to markdown.
+    -m, --markdown                  Print the page as CommonMark, with links in place.
+    -n, --line-numbers              Print the number of each line.
+        --lines <a:b>               Print only lines from A to B.
+        --grep <pattern>            Print only lines that match a regular expression, with numbers.
+        --context <number>          For --grep: print N lines around each match. Defaults to 2.
     -c, --columns                   Make output N columns wide.
+        --follow <number>           Open the page behind n-th link instead.
         --help                      Display help message."
     );
     Ok(())
@@ -31,13 +40,25 @@ where
     Args: Iterator<Item = String>,
 {
     let mut flag_html;
+    let mut flag_markdown;
+    let mut flag_line_numbers;
+    let mut flag_lines;
+    let mut flag_grep;
+    let mut flag_context;
     let mut flag_columns;
+    let mut flag_follow;
     let mut flag_help;
 
     let mut flags = flags![
-        flag_html: BoolFlag,      ["-h", "--html"],
-        flag_columns: StringFlag, ["-c", "--columns"],
-        flag_help: BoolFlag,      ["--help"]
+        flag_html: BoolFlag,         ["-h", "--html"],
+        flag_markdown: BoolFlag,     ["-m", "--markdown"],
+        flag_line_numbers: BoolFlag, ["-n", "--line-numbers"],
+        flag_lines: StringFlag,      ["--lines"],
+        flag_grep: StringFlag,       ["--grep"],
+        flag_context: StringFlag,    ["--context"],
+        flag_columns: StringFlag,    ["-c", "--columns"],
+        flag_follow: StringFlag,     ["--follow"],
+        flag_help: BoolFlag,         ["--help"]
     ];
 
     let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
@@ -45,7 +66,16 @@ where
         return show_open_help();
     }
 
-    let mut width = get_terminal_width();
+    let follow_link = parse_follow_flag(&flag_follow)?;
+    let page_options = parse_page_options(
+        flag_markdown,
+        flag_line_numbers,
+        &flag_lines,
+        &flag_grep,
+        &flag_context,
+    )?;
+
+    let mut width = get_page_width(&page_options)?;
 
     let maybe_columns = flag_columns.parse::<usize>().ok();
     if let Some(col_number) = maybe_columns {
@@ -59,8 +89,13 @@ where
     }
 
     if flag_html {
+        if follow_link.is_some() {
+            return Err("`--follow` cannot be used with `--html`.".to_string());
+        }
+
         let path = PathBuf::from(args.join(" "));
-        print_docset_file(path, None, width)?;
+        let directory = path.parent().unwrap_or(&path).to_path_buf();
+        print_docset_file(&directory, path, None, width, None, None, &page_options)?;
         return Ok(());
     }
 
@@ -78,7 +113,8 @@ where
 
     let docs = deserialize_docs_json()?;
 
-    if is_docset_in_docs_or_print_warning(&docset, &docs) {
+    // Zeal docsets are not in the list of available docsets.
+    if is_zeal_docset(&docset)? || is_docset_in_docs_or_print_warning(&docset, &docs) {
         let query = args.collect::<Vec<String>>().join(" ");
 
         if query.is_empty() {
@@ -87,7 +123,17 @@ where
 
         let (item, fragment) = split_to_item_and_fragment(query)?;
 
-        print_page_from_docset(&docset, &item, fragment.as_ref(), width)?;
+        print_page_from_docset(
+            &docset,
+            &item,
+            fragment.as_ref(),
+            width,
+            follow_link,
+            &page_options,
+        )?;
+    } else if is_json_output() {
+        // JSON output is always a page or an error.
+        return Err(format!("Unknown docset `{docset}`."));
     }
 
     Ok(())
@@ -0,0 +1,428 @@
+// Pages of a docset can be stored in one compressed pack instead of a file for each of them, with
+// `download --pack`. The pack is `pages.pack` in the docset directory, and all numbers in it are
+// little-endian:
+//
+//     magic "DDPK", u32 version
+//     zstd frames, one for each file
+//     u32 file count, then for each file: u32 length, UTF-8 path, u64 offset, u32 size of the frame
+//     u64 offset of the file table
+//
+// Paths are relative to the docset directory and keep their extensions, e.g.
+// `std/vec/struct.Vec.html`, and pre-rendered pages are stored next to HTML ones. Files are read
+// with `open_docset_file`, which looks into the pack when a file is not on the disk, so the rest of
+// the program can use paths like the pack was extracted.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::common::ResultS;
+
+pub(crate) const PACK_FILE_NAME: &str = "pages.pack";
+
+const PACK_MAGIC: &[u8; 4] = b"DDPK";
+const PACK_VERSION: u32 = 1;
+
+#[cfg_attr(not(feature = "pack"), allow(dead_code))]
+const COMPRESSION_LEVEL: i32 = 9;
+
+#[cfg(feature = "pack")]
+fn compress(contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    zstd::bulk::compress(contents, COMPRESSION_LEVEL)
+}
+
+#[cfg(feature = "pack")]
+fn decompress(frame: &[u8]) -> std::io::Result<Vec<u8>> {
+    zstd::stream::decode_all(frame)
+}
+
+#[cfg(not(feature = "pack"))]
+fn compress(_contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "This build of dedoc does not support packed docsets.",
+    ))
+}
+
+#[cfg(not(feature = "pack"))]
+fn decompress(_frame: &[u8]) -> std::io::Result<Vec<u8>> {
+    compress(&[])
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+#[inline]
+fn to_pack_path(path: &Path) -> String {
+    // Packs made on Windows are readable everywhere else.
+    path.to_string_lossy().replace('\\', "/")
+}
+
+pub(crate) struct PackWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    offset: u64,
+    files: Vec<(String, u64, u32)>,
+}
+
+impl PackWriter {
+    pub(crate) fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|err| format!("Could not create `{}`: {err}", path.display()))?;
+
+        let mut pack_writer = PackWriter {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            offset: 0,
+            files: vec![],
+        };
+
+        pack_writer.write_all(PACK_MAGIC)?;
+        pack_writer.write_all(&PACK_VERSION.to_le_bytes())?;
+
+        Ok(pack_writer)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> ResultS {
+        self.writer
+            .write_all(bytes)
+            .map_err(|err| format!("Could not write `{}`: {err}", self.path.display()))?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    // `relative_path` is relative to the docset directory.
+    pub(crate) fn add_file(&mut self, relative_path: &Path, contents: &[u8]) -> ResultS {
+        let frame = compress(contents)
+            .map_err(|err| format!("Could not compress `{}`: {err}", relative_path.display()))?;
+
+        self.files
+            .push((to_pack_path(relative_path), self.offset, frame.len() as u32));
+
+        self.write_all(&frame)
+    }
+
+    pub(crate) fn finish(mut self) -> ResultS {
+        let table_offset = self.offset;
+        let files = std::mem::take(&mut self.files);
+
+        let mut table = vec![];
+        let write_table = |table: &mut Vec<u8>| -> std::io::Result<()> {
+            write_u32(table, files.len() as u32)?;
+            for (path, offset, size) in files.iter() {
+                write_u32(table, path.len() as u32)?;
+                table.write_all(path.as_bytes())?;
+                write_u64(table, *offset)?;
+                write_u32(table, *size)?;
+            }
+            write_u64(table, table_offset)
+        };
+        write_table(&mut table).map_err(|err| err.to_string())?;
+
+        self.write_all(&table)?;
+
+        self.writer
+            .flush()
+            .map_err(|err| format!("Could not write `{}`: {err}", self.path.display()))
+    }
+}
+
+pub(crate) struct Pack {
+    path: PathBuf,
+    file: File,
+    files: BTreeMap<String, (u64, u32)>,
+}
+
+impl Pack {
+    pub(crate) fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("Could not open `{}`: {err}", path.display()))?;
+
+        let read_error =
+            |err: std::io::Error| format!("Could not read `{}`: {err}", path.display());
+
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(read_error)?;
+        let version = read_u32(&mut reader).map_err(read_error)?;
+
+        if magic != *PACK_MAGIC || version != PACK_VERSION {
+            return Err(format!(
+                "`{}` is not a pack, or has an unsupported version. Redownload the docset to update it.",
+                path.display()
+            ));
+        }
+
+        let file_length = reader.get_ref().metadata().map_err(read_error)?.len();
+        let corrupt_error = |what: &str| format!("`{}` is corrupt: {what}.", path.display());
+
+        // Lengths and offsets are checked before they are used, to not allocate or read whatever a
+        // broken pack says.
+        let header_length = (PACK_MAGIC.len() + 4) as u64;
+        if file_length < header_length + 8 {
+            return Err(corrupt_error("the file is too short"));
+        }
+
+        let table_end = file_length - 8;
+        reader
+            .seek(SeekFrom::Start(table_end))
+            .map_err(read_error)?;
+        let table_offset = read_u64(&mut reader).map_err(read_error)?;
+
+        if table_offset < header_length || table_offset > table_end {
+            return Err(corrupt_error("the file table is out of bounds"));
+        }
+
+        let mut table = vec![0; (table_end - table_offset) as usize];
+        reader
+            .seek(SeekFrom::Start(table_offset))
+            .and_then(|_| reader.read_exact(&mut table))
+            .map_err(read_error)?;
+        let mut table = Cursor::new(table);
+
+        let mut files = BTreeMap::new();
+
+        for _ in 0..read_u32(&mut table).map_err(read_error)? {
+            let length = read_u32(&mut table).map_err(read_error)? as u64;
+
+            if length > table.get_ref().len() as u64 - table.position() {
+                return Err(corrupt_error("a path is longer than the file table"));
+            }
+
+            let mut file_path = vec![0; length as usize];
+            table.read_exact(&mut file_path).map_err(read_error)?;
+            let file_path = String::from_utf8_lossy(&file_path).to_string();
+
+            let offset = read_u64(&mut table).map_err(read_error)?;
+            let size = read_u32(&mut table).map_err(read_error)?;
+
+            let is_in_bounds = offset >= header_length
+                && matches!(offset.checked_add(size as u64), Some(end) if end <= table_offset);
+
+            if !is_in_bounds {
+                return Err(corrupt_error(&format!("`{file_path}` is out of bounds")));
+            }
+
+            files.insert(file_path, (offset, size));
+        }
+
+        Ok(Pack {
+            path: path.to_path_buf(),
+            file: reader.into_inner(),
+            files,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn contains(&self, relative_path: &Path) -> bool {
+        self.files.contains_key(&to_pack_path(relative_path))
+    }
+
+    pub(crate) fn read(&mut self, relative_path: &Path) -> Result<Vec<u8>, String> {
+        let pack_path = to_pack_path(relative_path);
+
+        let Some((offset, size)) = self.files.get(&pack_path).copied() else {
+            return Err(format!(
+                "`{pack_path}` is not in `{}`.",
+                self.path.display()
+            ));
+        };
+
+        let read_error = |err: std::io::Error| {
+            format!(
+                "Could not read `{pack_path}` from `{}`: {err}",
+                self.path.display()
+            )
+        };
+
+        let mut frame = vec![0; size as usize];
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.file.read_exact(&mut frame))
+            .map_err(read_error)?;
+
+        decompress(&frame).map_err(read_error)
+    }
+
+    pub(crate) fn get_file_paths(&self) -> impl Iterator<Item = &String> {
+        self.files.keys()
+    }
+}
+
+// Pages are read one after another from the same docset, so the last pack is kept open.
+static OPENED_PACK: Mutex<Option<Pack>> = Mutex::new(None);
+
+// Calls `f` with the pack of the docset, and the path relative to the docset directory. Returns
+// `None` if the docset is not packed, or `path` is not in the docset.
+fn with_pack<T>(
+    docset_path: &Path,
+    path: &Path,
+    f: impl FnOnce(&mut Pack, &Path) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    let pack_path = docset_path.join(PACK_FILE_NAME);
+
+    let Ok(relative_path) = path.strip_prefix(docset_path) else {
+        return Ok(None);
+    };
+
+    if !pack_path.is_file() {
+        return Ok(None);
+    }
+
+    let mut opened_pack = OPENED_PACK.lock().map_err(|err| err.to_string())?;
+
+    if !matches!(opened_pack.as_ref(), Some(pack) if pack.path == pack_path) {
+        *opened_pack = Some(Pack::open(&pack_path)?);
+    }
+
+    match opened_pack.as_mut() {
+        Some(pack) => f(pack, relative_path).map(Some),
+        None => Ok(None),
+    }
+}
+
+// Whether a file of the docset in `docset_path` exists, on the disk or in its pack.
+pub(crate) fn is_docset_file(docset_path: &Path, path: &Path) -> bool {
+    if path.is_file() {
+        return true;
+    }
+
+    matches!(
+        with_pack(docset_path, path, |pack, relative_path| Ok(
+            pack.contains(relative_path)
+        )),
+        Ok(Some(true))
+    )
+}
+
+// Opens a file of the docset in `docset_path` from the disk, or reads it from its pack.
+pub(crate) fn open_docset_file(docset_path: &Path, path: &Path) -> Result<Box<dyn Read>, String> {
+    if path.is_file() {
+        let file = File::open(path)
+            .map_err(|err| format!("Could not open `{}`: {err}", path.display()))?;
+        return Ok(Box::new(file));
+    }
+
+    match with_pack(docset_path, path, |pack, relative_path| {
+        pack.read(relative_path)
+    })? {
+        Some(contents) => Ok(Box::new(Cursor::new(contents))),
+        None => Err(format!(
+            "Could not open `{}`: no such file.",
+            path.display()
+        )),
+    }
+}
+
+// Paths of files in the pack of the docset, as if it was extracted, or `None` if the docset is not
+// packed.
+pub(crate) fn get_packed_file_paths(docset_path: &Path) -> Result<Option<Vec<PathBuf>>, String> {
+    let pack_path = docset_path.join(PACK_FILE_NAME);
+
+    if !pack_path.is_file() {
+        return Ok(None);
+    }
+
+    let pack = Pack::open(&pack_path)?;
+    let paths = pack
+        .get_file_paths()
+        .map(|file_path| docset_path.join(file_path))
+        .collect();
+
+    Ok(Some(paths))
+}
+
+#[cfg(feature = "pack")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TestDirectory;
+
+    #[test]
+    fn test_pack() {
+        let test_directory = TestDirectory::new("pack");
+        let docset_path = &test_directory.path;
+
+        let mut pack_writer = PackWriter::create(&docset_path.join(PACK_FILE_NAME)).unwrap();
+        pack_writer
+            .add_file(Path::new("index.html"), b"<h1>Index</h1>")
+            .unwrap();
+        pack_writer
+            .add_file(Path::new("std/vec/struct.Vec.html"), b"<h1>Vec</h1>")
+            .unwrap();
+        pack_writer.finish().unwrap();
+
+        let page_path = docset_path.join("std").join("vec").join("struct.Vec.html");
+        assert!(is_docset_file(docset_path, &page_path));
+        assert!(!is_docset_file(
+            docset_path,
+            &docset_path.join("std").join("missing.html")
+        ));
+        // Packs are only looked up in the docset directory.
+        assert!(!is_docset_file(&docset_path.join("std"), &page_path));
+
+        let mut contents = String::new();
+        open_docset_file(docset_path, &page_path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "<h1>Vec</h1>");
+
+        let paths = get_packed_file_paths(docset_path).unwrap().unwrap();
+        assert_eq!(
+            paths,
+            [
+                docset_path.join("index.html"),
+                docset_path.join("std/vec/struct.Vec.html")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_broken_pack() {
+        let test_directory = TestDirectory::new("broken_pack");
+        let pack_path = test_directory.path.join(PACK_FILE_NAME);
+
+        // A pack with one empty frame of `page.html`.
+        let open_pack = |path_length: u32, offset: u64, size: u32, table_offset: u64| {
+            let mut contents = PACK_MAGIC.to_vec();
+            contents.extend(PACK_VERSION.to_le_bytes());
+            contents.extend(1u32.to_le_bytes());
+            contents.extend(path_length.to_le_bytes());
+            contents.extend(b"page.html");
+            contents.extend(offset.to_le_bytes());
+            contents.extend(size.to_le_bytes());
+            contents.extend(table_offset.to_le_bytes());
+
+            std::fs::write(&pack_path, contents).unwrap();
+            Pack::open(&pack_path)
+        };
+
+        assert!(open_pack(9, 8, 0, 8).is_ok());
+        assert!(open_pack(u32::MAX, 8, 0, 8).is_err());
+        assert!(open_pack(9, 8, u32::MAX, 8).is_err());
+        assert!(open_pack(9, u64::MAX, 1, 8).is_err());
+        assert!(open_pack(9, 8, 0, u64::MAX).is_err());
+        assert!(open_pack(9, 8, 0, 0).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+// Pre-rendered pages, which `download --prerender` stores next to HTML pages.
+//
+// A page is rendered once without wrapping, and saved as JSON lines. The first line is a header,
+// every following line is an array of elements of one line of text. Elements are either spans of
+// text with annotations, or fragments which start at that position:
+//
+//     {"format":"dedoc-prerendered","version":1}
+//     [{"text":"fn "},{"text":"new","annotations":["code",{"link":"#method.new"}]}]
+//     [{"fragment":"method.new"},{"text":"Creates a new reader."}]
+//
+// Annotations are `link`, `image`, `emphasis`, `strong`, `strikeout`, `code`, `preformat`,
+// `colour` and `bg_colour`. When a page is opened, its lines are only wrapped to the requested
+// width, instead of rendering HTML again.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use html2text::render::text_renderer::{
+    RichAnnotation, TaggedLine, TaggedLineElement, TaggedString,
+};
+use html2text::Colour;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::RichPage;
+use crate::pack::open_docset_file;
+
+const FORMAT_NAME: &str = "dedoc-prerendered";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Annotation {
+    Link(String),
+    Image(String),
+    Emphasis,
+    Strong,
+    Strikeout,
+    Code,
+    Preformat,
+    Colour([u8; 3]),
+    BgColour([u8; 3]),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+enum Element {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        annotations: Vec<Annotation>,
+    },
+    Fragment {
+        fragment: String,
+    },
+}
+
+fn to_annotation(rich_annotation: &RichAnnotation) -> Option<Annotation> {
+    let annotation = match rich_annotation {
+        RichAnnotation::Default => return None,
+        RichAnnotation::Link(href) => Annotation::Link(href.clone()),
+        RichAnnotation::Image(src) => Annotation::Image(src.clone()),
+        RichAnnotation::Emphasis => Annotation::Emphasis,
+        RichAnnotation::Strong => Annotation::Strong,
+        RichAnnotation::Strikeout => Annotation::Strikeout,
+        RichAnnotation::Code => Annotation::Code,
+        RichAnnotation::Preformat(_) => Annotation::Preformat,
+        RichAnnotation::Colour(Colour { r, g, b }) => Annotation::Colour([*r, *g, *b]),
+        RichAnnotation::BgColour(Colour { r, g, b }) => Annotation::BgColour([*r, *g, *b]),
+        _ => return None,
+    };
+
+    Some(annotation)
+}
+
+// `is_continuation` marks preformatted lines that were wrapped.
+fn to_rich_annotation(annotation: &Annotation, is_continuation: bool) -> RichAnnotation {
+    match annotation {
+        Annotation::Link(href) => RichAnnotation::Link(href.clone()),
+        Annotation::Image(src) => RichAnnotation::Image(src.clone()),
+        Annotation::Emphasis => RichAnnotation::Emphasis,
+        Annotation::Strong => RichAnnotation::Strong,
+        Annotation::Strikeout => RichAnnotation::Strikeout,
+        Annotation::Code => RichAnnotation::Code,
+        Annotation::Preformat => RichAnnotation::Preformat(is_continuation),
+        Annotation::Colour([r, g, b]) => RichAnnotation::Colour(Colour {
+            r: *r,
+            g: *g,
+            b: *b,
+        }),
+        Annotation::BgColour([r, g, b]) => RichAnnotation::BgColour(Colour {
+            r: *r,
+            g: *g,
+            b: *b,
+        }),
+    }
+}
+
+fn to_elements(rich_line: &TaggedLine<Vec<RichAnnotation>>) -> Vec<Element> {
+    rich_line
+        .iter()
+        .map(|element| match element {
+            TaggedLineElement::Str(tagged_string) => Element::Text {
+                text: tagged_string.s.clone(),
+                annotations: tagged_string.tag.iter().filter_map(to_annotation).collect(),
+            },
+            TaggedLineElement::FragmentStart(fragment) => Element::Fragment {
+                fragment: fragment.clone(),
+            },
+        })
+        .collect()
+}
+
+// Pages are written into files, or into packs with `download --pack`.
+pub(crate) fn serialize_prerendered_page(rich_page: &RichPage) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![];
+
+    let header = Header {
+        format: FORMAT_NAME.to_string(),
+        version: FORMAT_VERSION,
+    };
+
+    let write_error =
+        |err: &dyn std::fmt::Display| format!("Could not serialize pre-rendered page: {err}");
+
+    serde_json::to_writer(&mut buffer, &header).map_err(|err| write_error(&err))?;
+    writeln!(buffer).map_err(|err| write_error(&err))?;
+
+    for rich_line in rich_page {
+        serde_json::to_writer(&mut buffer, &to_elements(rich_line))
+            .map_err(|err| write_error(&err))?;
+        writeln!(buffer).map_err(|err| write_error(&err))?;
+    }
+
+    Ok(buffer)
+}
+
+pub(crate) fn read_prerendered_page(
+    docset_path: &Path,
+    path: &Path,
+    width: usize,
+) -> Result<RichPage, String> {
+    let mut lines = BufReader::new(open_docset_file(docset_path, path)?).lines();
+
+    let read_error =
+        |err: &dyn std::fmt::Display| format!("Could not read `{}`: {err}", path.display());
+
+    let header_line = lines
+        .next()
+        .unwrap_or_else(|| Ok(String::new()))
+        .map_err(|err| read_error(&err))?;
+
+    let header: Header = serde_json::from_str(&header_line).map_err(|err| read_error(&err))?;
+
+    if header.format != FORMAT_NAME || header.version != FORMAT_VERSION {
+        return Err(format!(
+            "`{}` has unsupported format `{} {}`. Redownload the docset to update it.",
+            path.display(),
+            header.format,
+            header.version
+        ));
+    }
+
+    let mut rich_page = vec![];
+
+    for line in lines {
+        let line = line.map_err(|err| read_error(&err))?;
+        let elements: Vec<Element> = serde_json::from_str(&line).map_err(|err| read_error(&err))?;
+
+        rich_page.append(&mut reflow_line(&elements, width));
+    }
+
+    Ok(rich_page)
+}
+
+#[inline]
+fn is_table_line(text: &str) -> bool {
+    text.contains(['─', '│', '┼', '┬', '┴'])
+}
+
+// Returns what continuation lines start with: quote and heading markers are repeated, and list
+// items are indented to their text, e.g. after `* ` or `12. `.
+fn get_continuation_prefix(text: &str) -> String {
+    let mut prefix = String::new();
+    let mut rest = text;
+
+    loop {
+        let trimmed = rest.trim_start_matches(' ');
+        prefix += &" ".repeat(rest.len() - trimmed.len());
+        rest = trimmed;
+
+        if let Some(quoted) = rest.strip_prefix("> ") {
+            prefix += "> ";
+            rest = quoted;
+        } else {
+            break;
+        }
+    }
+
+    let hashes = rest.chars().take_while(|ch| *ch == '#').count();
+
+    if hashes > 0 && rest[hashes..].starts_with(' ') {
+        prefix += &rest[..hashes + 1];
+        return prefix;
+    }
+
+    let marker_length = if rest.starts_with("* ") || rest.starts_with("- ") {
+        2
+    } else {
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+
+        if digits > 0 && rest[digits..].starts_with(". ") {
+            digits + 2
+        } else {
+            0
+        }
+    };
+
+    prefix + &" ".repeat(marker_length)
+}
+
+// Splits `chars` into ranges of lines that fit into `width`, breaking on spaces when possible.
+// Spaces at line breaks are left out. Lines after the first one are `prefix_width` narrower.
+fn get_line_ranges(
+    chars: &[char],
+    width: usize,
+    prefix_width: usize,
+    wrap_words: bool,
+) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut start = 0;
+
+    while start < chars.len() {
+        let available = if ranges.is_empty() {
+            width
+        } else {
+            width.saturating_sub(prefix_width)
+        }
+        .max(1);
+
+        if chars.len() - start <= available {
+            ranges.push((start, chars.len()));
+            break;
+        }
+
+        let limit = start + available;
+
+        let space = if wrap_words {
+            chars[start + 1..=limit]
+                .iter()
+                .rposition(|ch| *ch == ' ')
+                .map(|position| start + 1 + position)
+        } else {
+            None
+        };
+
+        match space {
+            Some(space) => {
+                let mut end = space;
+                while end > start && chars[end - 1] == ' ' {
+                    end -= 1;
+                }
+                ranges.push((start, end));
+
+                start = space;
+                while start < chars.len() && chars[start] == ' ' {
+                    start += 1;
+                }
+            }
+            None => {
+                ranges.push((start, limit));
+                start = limit;
+            }
+        }
+    }
+
+    if ranges.is_empty() {
+        ranges.push((0, 0));
+    }
+
+    ranges
+}
+
+// Wraps one unwrapped line of a page to `width`.
+fn reflow_line(elements: &[Element], width: usize) -> Vec<TaggedLine<Vec<RichAnnotation>>> {
+    let mut chars = vec![];
+    // Fragments with the position of the character that follows them.
+    let mut fragments = vec![];
+    let mut is_preformat = false;
+
+    for element in elements {
+        match element {
+            Element::Text { text, annotations } => {
+                if chars.is_empty() && annotations.contains(&Annotation::Preformat) {
+                    is_preformat = true;
+                }
+                chars.extend(text.chars());
+            }
+            Element::Fragment { fragment } => fragments.push((chars.len(), fragment)),
+        }
+    }
+
+    let text: String = chars.iter().collect();
+
+    let prefix = if is_preformat {
+        String::new()
+    } else {
+        get_continuation_prefix(&text)
+    };
+
+    let ranges = if is_table_line(&text) {
+        vec![(0, chars.len())]
+    } else {
+        get_line_ranges(&chars, width, prefix.chars().count(), !is_preformat)
+    };
+
+    let mut rich_lines: Vec<TaggedLine<Vec<RichAnnotation>>> =
+        ranges.iter().map(|_| TaggedLine::new()).collect();
+
+    // Index of the line which contains a character at `position`. Positions in between lines
+    // belong to the next line, and the end of text belongs to the last line.
+    let get_line_index = |position: usize| {
+        ranges
+            .iter()
+            .position(|(_, end)| position < *end)
+            .unwrap_or(ranges.len() - 1)
+    };
+
+    for rich_line in rich_lines.iter_mut().skip(1) {
+        if !prefix.is_empty() {
+            rich_line.push_str(TaggedString {
+                s: prefix.clone(),
+                tag: vec![],
+            });
+        }
+    }
+
+    let mut position = 0;
+    let mut next_fragment = 0;
+
+    for element in elements {
+        let Element::Text { text, annotations } = element else {
+            continue;
+        };
+
+        for ch in text.chars() {
+            while let Some((fragment_position, fragment)) = fragments.get(next_fragment) {
+                if *fragment_position > position {
+                    break;
+                }
+                rich_lines[get_line_index(position)]
+                    .push(TaggedLineElement::FragmentStart(fragment.to_string()));
+                next_fragment += 1;
+            }
+
+            let line_index = get_line_index(position);
+            let (start, end) = ranges[line_index];
+
+            if position >= start && position < end {
+                let tag = annotations
+                    .iter()
+                    .map(|annotation| to_rich_annotation(annotation, line_index > 0))
+                    .collect();
+
+                rich_lines[line_index].push_str(TaggedString {
+                    s: ch.to_string(),
+                    tag,
+                });
+            }
+
+            position += 1;
+        }
+    }
+
+    for (_, fragment) in fragments.iter().skip(next_fragment) {
+        if let Some(rich_line) = rich_lines.last_mut() {
+            rich_line.push(TaggedLineElement::FragmentStart(fragment.to_string()));
+        }
+    }
+
+    rich_lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::TestDirectory;
+
+    // Lines as strings with their annotations, with neighbouring strings of the same annotations
+    // merged together, since `TaggedLine::push_str` merges them as well.
+    fn flatten(rich_page: &RichPage) -> Vec<Vec<(String, Vec<RichAnnotation>)>> {
+        rich_page
+            .iter()
+            .map(|rich_line| {
+                let mut line = TaggedLine::new();
+                for element in rich_line.iter() {
+                    line.push(element.clone());
+                }
+                line.iter()
+                    .map(|element| match element {
+                        TaggedLineElement::Str(tagged_string) => {
+                            (tagged_string.s.clone(), tagged_string.tag.clone())
+                        }
+                        TaggedLineElement::FragmentStart(fragment) => {
+                            (format!("#{fragment}"), vec![])
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reflow() {
+        let html = r#"<p>A paragraph with <a href="x">a link</a> that is long enough to wrap.</p>
+<ul><li>A list item which also wraps around</li></ul>
+<pre>let long_line_of_code = 1234567890;</pre>
+<ol><li>First item which is long enough</li><li>Second<ul><li>nested item which is long too</li></ul></li></ol>
+<blockquote>A quote which wraps over several lines here</blockquote>
+<h2>A heading which is rather long indeed</h2>
+<dl><dt>Term</dt><dd>Definition which wraps over lines</dd></dl>"#;
+
+        let unwrapped = html2text::from_read_rich(html.as_bytes(), 10000);
+
+        let reflown: RichPage = unwrapped
+            .iter()
+            .flat_map(|rich_line| reflow_line(&to_elements(rich_line), 20))
+            .collect();
+
+        let expected = html2text::from_read_rich(html.as_bytes(), 20);
+
+        assert_eq!(flatten(&reflown), flatten(&expected));
+    }
+
+    #[test]
+    fn test_prerendered_round_trip() {
+        let html = r#"<h1>Title</h1><p>Some <code>code</code> and <strong>bold</strong> text.</p>
+<h2 id="next">Next</h2><p><a href="../page#fragment">Link</a></p>"#;
+        let rich_page = html2text::from_read_rich(html.as_bytes(), 10000);
+
+        let test_directory = TestDirectory::new("prerender");
+        let path = test_directory.path.join("page.dmd");
+
+        std::fs::write(&path, serialize_prerendered_page(&rich_page).unwrap()).unwrap();
+        let read_page = read_prerendered_page(&test_directory.path, &path, 80);
+
+        assert_eq!(flatten(&read_page.unwrap()), flatten(&rich_page));
+    }
+}
@@ -1,11 +1,13 @@
 use std::fs::remove_dir_all;
+use std::io::ErrorKind;
 
 use toiletcli::flags;
 use toiletcli::flags::*;
 
 use crate::common::ResultS;
-use crate::common::{get_docset_path, get_flag_error, get_local_docsets, is_docset_downloaded};
-use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET, YELLOW};
+use crate::common::{find_docset_root, get_flag_error, get_root_docsets};
+use crate::common::{get_docset_roots, get_user_docsets_root, DocsetRoot};
+use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET};
 use crate::print_warning;
 
 fn show_remove_help() -> ResultS {
@@ -13,10 +15,12 @@ fn show_remove_help() -> ResultS {
         "\
 {GREEN}USAGE{RESET}
     {BOLD}{PROGRAM_NAME} remove{RESET} <docset1> [docset2, ...]
-    Delete a docset. Only docsets downloaded by {PROGRAM_NAME} can be removed.
+    Delete a docset. Only docsets downloaded by {PROGRAM_NAME} can be removed, and docsets in
+    system directories only by users who can write to them.
 
 {GREEN}OPTIONS{RESET}
-        --purge-all                 Remove all installed docsets.
+        --purge-all                 Remove all docsets in your own directory.
+    -s, --system                    With --purge-all, remove docsets in system directories too.
         --help                      Display help message."
     );
     Ok(())
@@ -46,27 +50,88 @@ fn is_name_allowed<S: AsRef<str>>(docset_name: &S) -> bool {
     !(has_slashes || starts_with_tilde || has_dollars || starts_with_dot || has_dots)
 }
 
+// Removes the docset from the first root that has it, like the one `search` and `open` use.
+fn remove_docset(docset_name: &str) -> ResultS {
+    let Some(root) = find_docset_root(docset_name)? else {
+        print_warning!("`{docset_name}` is not installed.");
+        return Ok(());
+    };
+
+    remove_docset_from_root(&root, docset_name)
+}
+
+fn remove_docset_from_root(root: &DocsetRoot, docset_name: &str) -> ResultS {
+    let read_only_warning = || {
+        print_warning!(
+            "`{docset_name}` is in read-only `{}`, so it can't be removed.",
+            root.path.display()
+        );
+    };
+
+    if !root.is_writable() {
+        read_only_warning();
+        return Ok(());
+    }
+
+    let docset_path = root.path.join(docset_name);
+
+    println!(
+        "Removing `{docset_name}` from `{}`...",
+        docset_path.display()
+    );
+
+    // Permissions don't tell about read-only mounts, so that only shows up here.
+    match remove_dir_all(&docset_path) {
+        Ok(()) => Ok(()),
+        Err(err) if is_read_only_error(&err) => {
+            read_only_warning();
+            Ok(())
+        }
+        Err(err) => Err(format!(
+            "Unable to remove `{}`: {err}",
+            docset_path.display()
+        )),
+    }
+}
+
+fn is_read_only_error(err: &std::io::Error) -> bool {
+    // EROFS, since `ErrorKind::ReadOnlyFilesystem` is too new.
+    #[cfg(target_family = "unix")]
+    const READ_ONLY_FILESYSTEM: i32 = 30;
+    #[cfg(not(target_family = "unix"))]
+    const READ_ONLY_FILESYSTEM: i32 = -1;
+
+    err.kind() == ErrorKind::PermissionDenied || err.raw_os_error() == Some(READ_ONLY_FILESYSTEM)
+}
+
 pub(crate) fn remove<Args>(mut args: Args) -> ResultS
 where
     Args: Iterator<Item = String>,
 {
     let mut flag_purge_all;
+    let mut flag_system;
     let mut flag_help;
 
     let mut flags = flags![
         flag_help: BoolFlag,      ["--help"],
-        flag_purge_all: BoolFlag, ["--purge-all"]
+        flag_purge_all: BoolFlag, ["--purge-all"],
+        flag_system: BoolFlag,    ["-s", "--system"]
     ];
 
     let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
 
     if flag_purge_all {
-        let local_docsets = get_local_docsets()?;
-        for docset in local_docsets {
-            let docset_path = get_docset_path(&docset)?;
-            println!("Removing `{docset}` from `{}`...", docset_path.display());
-            remove_dir_all(&docset_path)
-                .map_err(|err| format!("Unable to remove `{}`: {err}", docset_path.display()))?;
+        // Docsets of every user are only removed when asked for.
+        let roots = if flag_system {
+            get_docset_roots()?
+        } else {
+            vec![get_user_docsets_root()?]
+        };
+
+        for root in roots {
+            for docset in get_root_docsets(&root)? {
+                remove_docset_from_root(&root, &docset)?;
+            }
         }
         return Ok(());
     }
@@ -81,17 +146,7 @@ where
             continue;
         }
 
-        if is_docset_downloaded(docset)? {
-            let docset_path = get_docset_path(docset)?;
-            if docset_path.exists() {
-                println!("Removing `{docset}` from `{}`...", docset_path.display());
-                remove_dir_all(&docset_path).map_err(|err| {
-                    format!("Unable to remove `{}`: {err}", docset_path.display())
-                })?;
-            }
-        } else {
-            print_warning!("{YELLOW}WARNING{RESET}: `{docset}` is not installed.");
-        }
+        remove_docset(docset)?;
     }
 
     Ok(())
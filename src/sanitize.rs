@@ -0,0 +1,201 @@
+// Removes what's not needed to display pages from their HTML, to reduce size of docsets. Tags are
+// tokenized with `html::Tokenizer`, so quoted values with `>` or single quotes and attributes with
+// similar names are handled, and everything that's not a tag is copied as is.
+
+use crate::html::{Token, Tokenizer};
+
+// `id` and `href` are always kept, since fragments and links need them.
+const REQUIRED_ATTRIBUTES: &[&str] = &["id", "href"];
+
+// Only `Deny` is used for docsets for now.
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) enum AttributeRule<'a> {
+    // Only these attributes are kept.
+    Allow(&'a [&'a str]),
+    // Every attribute except these is kept.
+    Deny(&'a [&'a str]),
+}
+
+pub(crate) struct SanitizeRules<'a> {
+    pub(crate) attributes: AttributeRule<'a>,
+    // Tags that are removed with everything inside of them.
+    pub(crate) dropped_tags: &'a [&'a str],
+}
+
+pub(crate) const DEFAULT_SANITIZE_RULES: SanitizeRules<'static> = SanitizeRules {
+    // `data-language` is kept for highlighting of code blocks and for `open --markdown`.
+    attributes: AttributeRule::Deny(&["class", "title", "style"]),
+    dropped_tags: &["script", "style"],
+};
+
+impl SanitizeRules<'_> {
+    fn is_attribute_kept(&self, name: &str) -> bool {
+        let is_in = |names: &[&str]| names.iter().any(|kept| kept.eq_ignore_ascii_case(name));
+
+        if is_in(REQUIRED_ATTRIBUTES) {
+            return true;
+        }
+
+        match self.attributes {
+            AttributeRule::Allow(names) => is_in(names),
+            AttributeRule::Deny(names) => !is_in(names),
+        }
+    }
+
+    #[inline]
+    fn is_tag_dropped(&self, name: &str) -> bool {
+        self.dropped_tags
+            .iter()
+            .any(|dropped| dropped.eq_ignore_ascii_case(name))
+    }
+}
+
+pub(crate) fn sanitize_html(html: &str, rules: &SanitizeRules) -> String {
+    let mut sanitized = String::with_capacity(html.len());
+    let mut tokenizer = Tokenizer::new(html);
+
+    while let Some(token) = tokenizer.next() {
+        let tag = match token {
+            Token::Text(text) | Token::Markup(text) => {
+                sanitized.push_str(text);
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        if rules.is_tag_dropped(tag.name) {
+            if !tag.is_end && !tag.is_self_closing {
+                tokenizer.skip_element(tag.name);
+            }
+            continue;
+        }
+
+        let is_changed = !tag.is_end
+            && tag
+                .attributes
+                .iter()
+                .any(|(name, _)| !rules.is_attribute_kept(name));
+
+        // Tags are only rebuilt when attributes are removed, anything else is kept as written.
+        if !is_changed {
+            sanitized.push_str(tag.source);
+            continue;
+        }
+
+        sanitized.push('<');
+        sanitized.push_str(tag.name);
+
+        for (name, source) in tag.attributes.iter() {
+            if rules.is_attribute_kept(name) {
+                sanitized.push(' ');
+                sanitized.push_str(source);
+            }
+        }
+
+        if tag.is_self_closing {
+            sanitized.push_str(" /");
+        }
+
+        sanitized.push('>');
+    }
+
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_html() {
+        let html_text = r#"
+<summary>
+    <section id="method.new" class="method">
+        <span class="rightside">
+            <a class="srclink" href="https://doc.rust-lang.org/src/alloc/vec/mod.rs.html#420">source</a>
+            <span class="since" title="const since 1.39.0">
+                const: 1.39.0
+            </span>
+        </span>
+        <pre class="code-header" data-language="rust">
+            pub const fn new() -> Vec<T, Global>;
+        </pre>
+    </section>
+</summary>
+        "#;
+
+        let should_be = r#"
+<summary>
+    <section id="method.new">
+        <span>
+            <a href="https://doc.rust-lang.org/src/alloc/vec/mod.rs.html#420">source</a>
+            <span>
+                const: 1.39.0
+            </span>
+        </span>
+        <pre data-language="rust">
+            pub const fn new() -> Vec<T, Global>;
+        </pre>
+    </section>
+</summary>
+        "#;
+
+        let result = sanitize_html(html_text, &DEFAULT_SANITIZE_RULES);
+
+        assert_eq!(result, should_be);
+
+        let corpus = [
+            // Single quotes, and `>` inside of values.
+            (
+                "<a class='x > y' href='#new'>new</a>",
+                "<a href='#new'>new</a>",
+            ),
+            (r#"<span title="a > b">a > b</span>"#, "<span>a > b</span>"),
+            // Names that only end like denied ones.
+            (
+                r#"<div subclass="a" data-title="b" class="c">"#,
+                r#"<div subclass="a" data-title="b">"#,
+            ),
+            // Unquoted values, attributes without values, and odd spacing.
+            (
+                "<input CLASS=wide disabled  id = name>",
+                "<input disabled id = name>",
+            ),
+            ("<br class=\"x\"/>", "<br />"),
+            // Scripts and styles are removed with their contents.
+            (
+                "a<script type=\"text/javascript\">if (a < b) { x = '</div>'; }</script>b",
+                "ab",
+            ),
+            ("a<STYLE>p { color: red }</STYLE>b", "ab"),
+            ("a<script>unclosed", "a"),
+            // Text that looks like tags, comments and doctypes stay as they are.
+            ("1 < 2 and 3 <= 4", "1 < 2 and 3 <= 4"),
+            (
+                "<!-- <a class=\"x\"> --><!DOCTYPE html>",
+                "<!-- <a class=\"x\"> --><!DOCTYPE html>",
+            ),
+            ("<a class=\"unclosed", "<a class=\"unclosed"),
+            (
+                "<p>Привет, <b class=\"x\">мир</b></p>",
+                "<p>Привет, <b>мир</b></p>",
+            ),
+        ];
+
+        for (html, should_be) in corpus {
+            assert_eq!(sanitize_html(html, &DEFAULT_SANITIZE_RULES), should_be);
+        }
+
+        let rules = SanitizeRules {
+            attributes: AttributeRule::Allow(&["lang"]),
+            dropped_tags: &["iframe"],
+        };
+        assert_eq!(
+            sanitize_html(
+                r#"<pre lang="rust" class="x" id="a"></pre><iframe src="x"></iframe>"#,
+                &rules
+            ),
+            r#"<pre lang="rust" id="a"></pre>"#
+        );
+    }
+}
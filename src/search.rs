@@ -1,9 +1,12 @@
 #![allow(clippy::useless_format)]
 
 use std::borrow::Cow;
-use std::fs::{read_dir, File};
-use std::io::{BufRead, BufReader, BufWriter};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
 
 use serde::{Deserialize, Serialize};
 
@@ -12,29 +15,48 @@ use toiletcli::flags::*;
 
 use crate::common::ResultS;
 use crate::common::{
-    deserialize_docs_json, get_docset_path, get_flag_error, get_program_directory,
-    get_terminal_width, is_docs_json_exists, is_docset_downloaded,
-    is_docset_in_docs_or_print_warning, print_page_from_docset, split_to_item_and_fragment,
-};
-use crate::common::{
-    BOLD, DOC_PAGE_EXTENSION, GRAY, GRAYER, GRAYEST, GREEN, LIGHT_GRAY, PROGRAM_NAME, RESET,
+    convert_path_to_item, create_dir_all_or_error, deserialize_docs_json, get_cache_directory,
+    get_docset_pages_path, get_docset_path, get_flag_error, get_local_docsets, get_page_width,
+    is_docs_json_exists, is_docset_available, is_docset_in_docs,
+    is_docset_in_docs_or_print_warning, parse_follow_flag, parse_page_options,
+    print_page_from_docset, render_page_text, split_to_item_and_fragment, Docs, PageOptions,
+    SearchMatch,
 };
+use crate::common::{BOLD, DOC_PAGE_EXTENSION, GREEN, PROGRAM_NAME, RESET};
+use crate::config::get_config;
+use crate::fuzzy::fuzzy_score;
+use crate::index::{get_page_paths, has_fulltext_index, FullTextIndex};
+use crate::json::{is_json_output, print_json};
 use crate::print_warning;
+use crate::theme::get_theme;
+use crate::zeal::{get_zeal_docsets, is_zeal_docset, read_zeal_index};
 
 fn show_search_help() -> ResultS {
     println!(
         "\
 {GREEN}USAGE{RESET}
-    {BOLD}{PROGRAM_NAME} search{RESET} [-wipofc] <docset> <query>
-    List docset pages that match your query.
+    {BOLD}{PROGRAM_NAME} search{RESET} [-wisrpofmncta] <docset1[,docset2, ..]> <query>
+    List docset pages that match your query, best matches first. When `docsets` is set with
+    `config`, they are searched if only the query is given, unless it's the name of a docset.
 
 {GREEN}OPTIONS{RESET}
-    -w, --whole                     Search for the whole sentence.
+    -w, --whole                     Match only whole words.
     -i, --ignore-case               Ignore character case.
+    -s, --substring                 Match pages that contain the query instead of fuzzy matching.
+    -r, --regex                     Treat the query as a regular expression.
+    -t, --type <name>               Show only entries of this type, e.g. `Structs` or `Macros`.
+        --list-types                List entry types of the docset.
+    -a, --all-local                 Search every downloaded docset. Don't specify docsets with this.
     -p, --precise                   Look inside files (like `grep`).
     -o, --open <number>             Open n-th result.
     -f, --ignore-fragment           For --open: ignore the fragment and open the entire page.
+    -m, --markdown                  For --open: print the page as CommonMark.
+    -n, --line-numbers              For --open: print the number of each line.
+        --lines <a:b>               For --open: print only lines from A to B.
+        --grep <pattern>            For --open: print only lines that match a regular expression.
+        --context <number>          For --grep: print N lines around each match. Defaults to 2.
     -c, --columns <number>          For --open: make output N columns wide.
+        --follow <number>           For --open: open the page behind n-th link of the result.
         --help                      Display help message."
     );
     Ok(())
@@ -44,20 +66,37 @@ fn show_search_help() -> ResultS {
 struct ExactResult {
     item: String,
     fragment: Option<String>,
+    // Entry type from `index.json`, e.g. `Modules` or `Structs`. Empty for results from `--precise`.
+    #[serde(default)]
+    r#type: String,
+    docset: String,
+}
+
+// A piece of rendered text around a match. `start` and `end` are byte offsets of the match.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
+struct Context {
+    text: String,
+    start: usize,
+    end: usize,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 struct VagueResult {
     item: String,
-    contexts: Vec<String>,
+    contexts: Vec<Context>,
+    docset: String,
 }
 
 // Flags that change search result must be added here for cache to be updated.
+// Multiple docsets are stored in `SearchOptions::docset` separated by commas.
 #[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
 struct SearchFlags {
     case_insensitive: bool,
     precise: bool,
     whole: bool,
+    substring: bool,
+    regex: bool,
+    entry_type: String,
     ignore_fragment: bool,
 }
 
@@ -77,8 +116,8 @@ pub(crate) struct SearchCache<'a> {
 }
 
 pub(crate) fn try_use_cache<'a>(search_options: &SearchOptions) -> Option<SearchCache<'a>> {
-    let program_dir = get_program_directory().ok()?;
-    let cache_options_path = program_dir.join("search_cache_options.json");
+    let cache_dir = get_cache_directory().ok()?;
+    let cache_options_path = cache_dir.join("search_cache_options.json");
 
     {
         let cache_options_file = File::open(cache_options_path).ok()?;
@@ -92,7 +131,7 @@ pub(crate) fn try_use_cache<'a>(search_options: &SearchOptions) -> Option<Search
         }
     }
 
-    let cache_path = program_dir.join("search_cache.json");
+    let cache_path = cache_dir.join("search_cache.json");
 
     let cache_file = File::open(cache_path).ok()?;
     let cache_reader = BufReader::new(cache_file);
@@ -103,10 +142,11 @@ pub(crate) fn try_use_cache<'a>(search_options: &SearchOptions) -> Option<Search
 }
 
 fn cache_search_results(search_options: &SearchOptions, search_cache: &SearchCache) -> ResultS {
-    let program_dir = get_program_directory()?;
+    let cache_dir = get_cache_directory()?;
+    create_dir_all_or_error(&cache_dir)?;
 
     {
-        let cache_options_path = program_dir.join("search_cache_options.json");
+        let cache_options_path = cache_dir.join("search_cache_options.json");
         let cache_options_file = File::create(&cache_options_path).map_err(|err| {
             format!(
                 "Could not create cache options at `{}`: {err}",
@@ -125,7 +165,7 @@ fn cache_search_results(search_options: &SearchOptions, search_cache: &SearchCac
     }
 
     {
-        let cache_path = program_dir.join("search_cache.json");
+        let cache_path = cache_dir.join("search_cache.json");
         let cache_file = File::create(&cache_path).map_err(|err| {
             format!(
                 "Could not create cache at `{}`: {err}",
@@ -142,28 +182,28 @@ fn cache_search_results(search_options: &SearchOptions, search_cache: &SearchCac
     Ok(())
 }
 
-#[allow(dead_code)]
 #[derive(Deserialize, Default)]
-struct IndexEntry {
-    name: String,
-    path: String,
-    #[serde(skip)]
-    r#type: String,
+pub(crate) struct IndexEntry {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub r#type: String,
 }
 
 #[derive(Deserialize)]
-struct IndexJson {
-    entries: Vec<IndexEntry>,
+pub(crate) struct IndexJson {
+    pub entries: Vec<IndexEntry>,
 }
 
 type ExactMatches = Vec<ExactResult>;
 type VagueMatches = Vec<VagueResult>;
 
-fn search_docset_in_filenames(
-    docset_name: &str,
-    query: &str,
-    case_insensitive: bool,
-) -> Result<ExactMatches, String> {
+pub(crate) fn deserialize_index_json(docset_name: &str) -> Result<IndexJson, String> {
+    if is_zeal_docset(docset_name)? {
+        let entries = read_zeal_index(docset_name)?;
+        return Ok(IndexJson { entries });
+    }
+
     let docset_path = get_docset_path(docset_name)?;
     let index_json_path = docset_path.join("index.json");
 
@@ -194,169 +234,423 @@ Please redownload the docset with `download {docset_name} --force`."
         )
     })?;
 
-    let mut items = vec![];
+    Ok(index)
+}
+
+// Returns every entry type of a docset with the amount of entries, in order of appearance.
+fn get_docset_entry_types(docset_name: &str) -> Result<Vec<(String, usize)>, String> {
+    let index = deserialize_index_json(docset_name)?;
+
+    let mut types: Vec<(String, usize)> = vec![];
+
+    for entry in index.entries {
+        if let Some((_, count)) = types.iter_mut().find(|(name, _)| *name == entry.r#type) {
+            *count += 1;
+        } else {
+            types.push((entry.r#type, 1));
+        }
+    }
+
+    Ok(types)
+}
+
+// Moves results of the same type next to each other, so they can be printed under one heading.
+// Groups are ordered by their first result, which keeps the best matches on top.
+fn group_results_by_type(results: ExactMatches) -> ExactMatches {
+    let mut groups: Vec<(String, ExactMatches)> = vec![];
+
+    for result in results {
+        if let Some((_, group)) = groups.iter_mut().find(|(name, _)| *name == result.r#type) {
+            group.push(result);
+        } else {
+            groups.push((result.r#type.clone(), vec![result]));
+        }
+    }
+
+    groups.into_iter().flat_map(|(_, group)| group).collect()
+}
+
+// Returns results with their scores. Results of substring search all have the same score.
+fn search_docset_in_filenames(
+    docset_name: &str,
+    query: &str,
+    flags: &SearchFlags,
+) -> Result<Vec<(i64, ExactResult)>, String> {
+    let index = deserialize_index_json(docset_name)?;
 
-    if case_insensitive {
-        let query = query.to_lowercase();
+    let entries = index.entries.into_iter().filter(|entry| {
+        flags.entry_type.is_empty() || entry.r#type.eq_ignore_ascii_case(&flags.entry_type)
+    });
+
+    let mut items = vec![];
 
-        for entry in index.entries {
-            let lowercase_name = entry.name.to_lowercase();
-            let lowercase_path = entry.path.to_lowercase();
+    if flags.substring || flags.whole || flags.regex {
+        let matcher = QueryMatcher::new(query, flags)?;
 
-            if lowercase_name.contains(&query) || lowercase_path.contains(&query) {
+        for entry in entries {
+            if matcher.is_match(&entry.name) || matcher.is_match(&entry.path) {
                 let (item, fragment) = split_to_item_and_fragment(entry.path)?;
 
-                let exact_match = ExactResult { item, fragment };
+                let exact_match = ExactResult {
+                    item,
+                    fragment,
+                    r#type: entry.r#type,
+                    docset: docset_name.to_owned(),
+                };
 
                 items.push(exact_match);
             }
         }
+
+        items.sort_unstable();
+
+        Ok(items.into_iter().map(|result| (0, result)).collect())
     } else {
-        for entry in index.entries {
-            if entry.name.contains(query) || entry.path.contains(query) {
+        // Smart case: lowercase queries ignore case, queries with uppercase letters don't.
+        let case_insensitive = flags.case_insensitive || !query.chars().any(|ch| ch.is_uppercase());
+
+        let mut scored_items = vec![];
+
+        for entry in entries {
+            let name_score = fuzzy_score(query, &entry.name, case_insensitive);
+            let path_score = fuzzy_score(query, &entry.path, case_insensitive);
+
+            if let Some(score) = std::cmp::max(name_score, path_score) {
                 let (item, fragment) = split_to_item_and_fragment(entry.path)?;
 
-                let exact_match = ExactResult { item, fragment };
+                let exact_match = ExactResult {
+                    item,
+                    fragment,
+                    r#type: entry.r#type,
+                    docset: docset_name.to_owned(),
+                };
 
-                items.push(exact_match);
+                scored_items.push((score, exact_match));
             }
         }
+
+        Ok(scored_items)
     }
+}
+
+// Merges results from every docset into one list, best matches first.
+fn search_docsets_in_filenames(
+    docset_names: &[&str],
+    query: &str,
+    flags: &SearchFlags,
+) -> Result<ExactMatches, String> {
+    let mut scored_items = vec![];
 
-    items.sort_unstable();
+    for docset_name in docset_names {
+        scored_items.append(&mut search_docset_in_filenames(docset_name, query, flags)?);
+    }
 
-    Ok(items)
+    // Stable sort, to keep substring results in order of docsets.
+    if !(flags.substring || flags.whole || flags.regex) {
+        scored_items
+            .sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then_with(|| a.cmp(b)));
+    }
+
+    let items = scored_items.into_iter().map(|(_, result)| result).collect();
+
+    Ok(group_results_by_type(items))
 }
 
-fn get_context_around_query(html_line: &str, index: usize, query_len: usize) -> String {
+fn get_context_around_query(line: &str, index: usize, query_len: usize) -> Context {
     const BOUND_OFFSET: usize = (80 - 6 - 8) / 2; // (80 columns - ["...".len() * 2] - [TAB.len() * 2]) / 2 sides
 
     let lower_bound = index.saturating_sub(BOUND_OFFSET);
     let upper_bound = (index + query_len).saturating_add(BOUND_OFFSET);
     let word_end_index = index + query_len;
 
-    let start_pos = html_line
+    let start_pos = line
         .char_indices()
         .rev()
         .find(|&(idx, _)| idx <= lower_bound)
         .map_or(0, |(idx, _)| idx);
 
-    let end_pos = html_line
+    let end_pos = line
         .char_indices()
         .skip_while(|&(idx, _)| idx < word_end_index)
         .find(|&(idx, _)| idx >= upper_bound)
-        .map_or(html_line.len(), |(idx, _)| idx);
+        .map_or(line.len(), |(idx, _)| idx);
 
-    html_line[start_pos..end_pos].trim().to_owned()
-}
+    let context = &line[start_pos..end_pos];
+    let trimmed_start = context.len() - context.trim_start().len();
+    let text = context.trim().to_owned();
 
-// Item is a file path without a file extension which is relative to docset directory
-fn convert_path_to_item(path: PathBuf, docset_path: &PathBuf) -> Result<String, String> {
-    let item = path
-        .strip_prefix(docset_path)
-        .map_err(|err| err.to_string())?
-        .with_extension("")
-        .display()
-        .to_string();
+    let start = std::cmp::min(
+        (index - start_pos).saturating_sub(trimmed_start),
+        text.len(),
+    );
+    let end = std::cmp::min(start + query_len, text.len());
 
-    Ok(item)
+    Context { text, start, end }
 }
 
-fn search_docset_precisely(
-    docset_name: &str,
-    query: &str,
-    case_insensitive: bool,
-) -> Result<(ExactMatches, VagueMatches), String> {
-    let docset_path = get_docset_path(docset_name)?;
+// Returns byte range of the first occurrence of `query` in `line`. When `case_insensitive` is set,
+// `query` must be lowercase.
+fn find_query_in_line(line: &str, query: &str, case_insensitive: bool) -> Option<(usize, usize)> {
+    if !case_insensitive {
+        return line.find(query).map(|index| (index, index + query.len()));
+    }
 
-    let internal_query = if case_insensitive {
-        query.to_lowercase()
-    } else {
-        query.to_owned()
-    };
+    // Lowercase letters can be longer than uppercase ones, so offsets in a lowercase line are not
+    // always the offsets in the original line. Compare character by character instead.
+    let query_chars: Vec<char> = query.chars().collect();
+
+    for (start, _) in line.char_indices() {
+        let mut line_chars = line[start..].char_indices().flat_map(|(offset, ch)| {
+            ch.to_lowercase()
+                .map(move |lowercase| (offset + ch.len_utf8(), lowercase))
+        });
+
+        let mut end = start;
+        let mut is_match = true;
+
+        for query_char in query_chars.iter() {
+            match line_chars.next() {
+                Some((offset, ch)) if ch == *query_char => end = start + offset,
+                _ => {
+                    is_match = false;
+                    break;
+                }
+            }
+        }
+
+        if is_match {
+            return Some((start, end));
+        }
+    }
+
+    None
+}
 
-    fn visit_dir_with_query(
-        original_path: &PathBuf,
-        path: &PathBuf,
-        query: &String,
+enum QueryPattern {
+    // Query is lowercase if search is case-insensitive.
+    Literal {
+        query: String,
         case_insensitive: bool,
-    ) -> Result<(ExactMatches, VagueMatches), String> {
-        let mut exact_files = vec![];
-        let mut vague_results = vec![];
+    },
+    Regex(Regex),
+}
 
-        let dir = read_dir(path)
-            .map_err(|err| format!("Could not read `{}` directory: {err}", path.display()))?;
+struct QueryMatcher {
+    pattern: QueryPattern,
+    // Accept only matches that start and end on word boundaries.
+    whole: bool,
+}
+
+#[inline]
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
 
-        for entry in dir {
-            let entry = entry.map_err(|err| format!("Could not read file: {err}"))?;
+// Checks whether `start..end` of `text` is not glued to other word characters, e.g. `vec` in
+// `std::vec::Vec` is a whole word, but `vec` in `vector` isn't. Characters like `::`, `.`, `/`, `#`
+// or spaces separate words.
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let found = &text[start..end];
 
-            let os_file_name = entry.file_name();
+    let glued_start = match (text[..start].chars().next_back(), found.chars().next()) {
+        (Some(before), Some(first)) => is_word_char(before) && is_word_char(first),
+        _ => false,
+    };
 
-            let file_type = entry
-                .file_type()
-                .map_err(|err| format!("Could not read file type of {os_file_name:?}: {err}"))?;
+    let glued_end = match (found.chars().next_back(), text[end..].chars().next()) {
+        (Some(last), Some(after)) => is_word_char(last) && is_word_char(after),
+        _ => false,
+    };
 
-            if file_type.is_dir() {
-                let (mut exact, mut vague) =
-                    visit_dir_with_query(original_path, &entry.path(), query, case_insensitive)?;
+    !glued_start && !glued_end
+}
 
-                exact_files.append(&mut exact);
-                vague_results.append(&mut vague);
+impl QueryMatcher {
+    fn new(query: &str, flags: &SearchFlags) -> Result<Self, String> {
+        let pattern = if flags.regex {
+            let regex = RegexBuilder::new(query)
+                .case_insensitive(flags.case_insensitive)
+                .build()
+                .map_err(|err| format!("Invalid regular expression `{query}`: {err}"))?;
+
+            QueryPattern::Regex(regex)
+        } else if flags.case_insensitive {
+            QueryPattern::Literal {
+                query: query.to_lowercase(),
+                case_insensitive: true,
+            }
+        } else {
+            QueryPattern::Literal {
+                query: query.to_owned(),
+                case_insensitive: false,
             }
+        };
 
-            let mut file_name = os_file_name.to_string_lossy().to_string();
+        Ok(Self {
+            pattern,
+            whole: flags.whole,
+        })
+    }
 
-            if !file_name.ends_with(DOC_PAGE_EXTENSION) {
-                continue;
-            }
+    fn find_from(&self, text: &str, position: usize) -> Option<(usize, usize)> {
+        match &self.pattern {
+            QueryPattern::Literal {
+                query,
+                case_insensitive,
+            } => find_query_in_line(&text[position..], query, *case_insensitive)
+                .map(|(start, end)| (start + position, end + position)),
+            QueryPattern::Regex(regex) => regex
+                .find_at(text, position)
+                .map(|found| (found.start(), found.end())),
+        }
+    }
+
+    // Returns byte range of the first match in `text`.
+    fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let mut position = 0;
 
-            if case_insensitive {
-                file_name.make_ascii_lowercase();
+        while position <= text.len() {
+            let (start, end) = self.find_from(text, position)?;
+
+            if !self.whole || is_whole_word(text, start, end) {
+                return Some((start, end));
             }
 
-            let file_path = entry.path();
+            position = start + text[start..].chars().next()?.len_utf8();
+        }
+
+        None
+    }
+
+    #[inline]
+    fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
 
-            if file_name.contains(query) {
-                let item = convert_path_to_item(file_path, original_path)?;
+    // Full-text index can only look up literal queries.
+    fn get_literal_query(&self) -> Option<&str> {
+        match &self.pattern {
+            QueryPattern::Literal { query, .. } => Some(query),
+            QueryPattern::Regex(_) => None,
+        }
+    }
+}
+
+// Returns contexts around every line of the page that contains the query. If `lines` are
+// specified, only those lines are checked.
+fn find_contexts_in_page(
+    docset_path: &Path,
+    file_path: &Path,
+    matcher: &QueryMatcher,
+    lines: Option<&BTreeSet<u32>>,
+) -> Result<Vec<Context>, String> {
+    let page_lines = render_page_text(docset_path, file_path)?;
+
+    let mut contexts = vec![];
+
+    for (line_number, line) in page_lines.iter().enumerate() {
+        if !lines.map_or(true, |lines| lines.contains(&(line_number as u32))) {
+            continue;
+        }
+
+        if let Some((start, end)) = matcher.find(line) {
+            let context = get_context_around_query(line, start, end - start);
+
+            contexts.push(context);
+        }
+    }
+
+    Ok(contexts)
+}
+
+fn search_docset_precisely(
+    docset_name: &str,
+    query: &str,
+    flags: &SearchFlags,
+) -> Result<(ExactMatches, VagueMatches), String> {
+    let docset_path = get_docset_pages_path(docset_name)?;
+
+    let matcher = QueryMatcher::new(query, flags)?;
+
+    fn search_all_pages(
+        docset_path: &Path,
+        matcher: &QueryMatcher,
+    ) -> Result<(ExactMatches, VagueMatches), String> {
+        let mut exact_files = vec![];
+        let mut vague_results = vec![];
+
+        for file_path in get_page_paths(docset_path)? {
+            let file_name = file_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if matcher.is_match(&file_name) {
+                let item = convert_path_to_item(file_path, docset_path)?;
                 let exact_match = ExactResult {
                     item,
                     fragment: None,
+                    r#type: String::new(),
+                    docset: String::new(),
                 };
                 exact_files.push(exact_match);
             } else {
-                let file = File::open(&file_path)
-                    .map_err(|err| format!("Could not open `{}`: {err}", file_path.display()))?;
-
-                let query_len = query.len();
+                let contexts = find_contexts_in_page(docset_path, &file_path, matcher, None)?;
 
-                let mut contexts = vec![];
+                if !contexts.is_empty() {
+                    let item = convert_path_to_item(file_path, docset_path)?;
+                    let vague_result = VagueResult {
+                        item,
+                        contexts,
+                        docset: String::new(),
+                    };
+                    vague_results.push(vague_result);
+                }
+            }
+        }
 
-                let mut reader = BufReader::new(file);
-                let mut string_buffer = String::new();
+        Ok((exact_files, vague_results))
+    }
 
-                while let Ok(size) = reader.read_line(&mut string_buffer) {
-                    if size == 0 {
-                        break;
-                    }
+    // Full-text index tells which lines of which pages may contain the query, so only those have
+    // to be read.
+    fn search_pages_from_index(
+        docset_path: &Path,
+        pages: &[String],
+        candidates: &BTreeMap<u32, BTreeSet<u32>>,
+        matcher: &QueryMatcher,
+    ) -> Result<(ExactMatches, VagueMatches), String> {
+        let mut exact_files = vec![];
+        let mut vague_results = vec![];
 
-                    let display_context = if case_insensitive {
-                        Cow::Owned(string_buffer.to_lowercase())
-                    } else {
-                        Cow::Borrowed(&string_buffer)
-                    };
+        for (page_index, item) in pages.iter().enumerate() {
+            let file_name = Path::new(item)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
 
-                    if let Some(index) = display_context.find(query) {
-                        let context = get_context_around_query(&string_buffer, index, query_len);
+            let file_name = format!("{file_name}.{DOC_PAGE_EXTENSION}");
 
-                        contexts.push(context);
-                    }
+            if matcher.is_match(&file_name) {
+                let exact_match = ExactResult {
+                    item: item.to_owned(),
+                    fragment: None,
+                    r#type: String::new(),
+                    docset: String::new(),
+                };
+                exact_files.push(exact_match);
+            } else if let Some(lines) = candidates.get(&(page_index as u32)) {
+                let file_path = docset_path.join(format!("{item}.{DOC_PAGE_EXTENSION}"));
 
-                    string_buffer.clear();
-                }
+                let contexts =
+                    find_contexts_in_page(docset_path, &file_path, matcher, Some(lines))?;
 
                 if !contexts.is_empty() {
-                    let item = convert_path_to_item(file_path, original_path)?;
-                    let vague_result = VagueResult { item, contexts };
+                    let vague_result = VagueResult {
+                        item: item.to_owned(),
+                        contexts,
+                        docset: String::new(),
+                    };
                     vague_results.push(vague_result);
                 }
             }
@@ -365,12 +659,26 @@ fn search_docset_precisely(
         Ok((exact_files, vague_results))
     }
 
-    let (mut exact_files, mut vague_results) = visit_dir_with_query(
-        &docset_path,
-        &docset_path,
-        &internal_query,
-        case_insensitive,
-    )?;
+    let mut index = FullTextIndex::open(docset_name)?;
+
+    let candidates = match (index.as_mut(), matcher.get_literal_query()) {
+        (Some(index), Some(query)) => index.find_candidates(query)?,
+        _ => None,
+    };
+
+    let (mut exact_files, mut vague_results) = match (index, candidates) {
+        (Some(index), Some(candidates)) => {
+            search_pages_from_index(&docset_path, &index.pages, &candidates, &matcher)?
+        }
+        _ => search_all_pages(&docset_path, &matcher)?,
+    };
+
+    for result in exact_files.iter_mut() {
+        result.docset = docset_name.to_owned();
+    }
+    for result in vague_results.iter_mut() {
+        result.docset = docset_name.to_owned();
+    }
 
     exact_files.sort_unstable();
     vague_results.sort_unstable();
@@ -383,18 +691,44 @@ fn search_docset_precisely(
 const TAB: &str = "    ";
 const HALF_TAB: &str = "  ";
 
-fn print_vague_search_results(search_results: &[VagueResult], mut start_index: usize) -> ResultS {
+#[inline]
+fn get_docset_prefix(docset: &str, show_docset: bool) -> String {
+    if show_docset {
+        format!("{}{docset}{RESET} ", get_theme().faint)
+    } else {
+        String::new()
+    }
+}
+
+fn print_vague_search_results(
+    search_results: &[VagueResult],
+    mut start_index: usize,
+    show_docset: bool,
+) -> ResultS {
+    let theme = get_theme();
+
+    let secondary = theme.secondary;
+    let faint = theme.faint;
+    let matched = theme.matched;
+    let context_color = theme.code_block;
+    let background = theme.code_block_background.bg();
+
     for result in search_results {
         println!(
-            "{GRAY}{start_index:>4}{RESET}{HALF_TAB}{}{GRAY}",
+            "{secondary}{start_index:>4}{RESET}{HALF_TAB}{}{}{secondary}",
+            get_docset_prefix(&result.docset, show_docset),
             result.item
         );
 
         for context in &result.contexts {
+            let Context { text, start, end } = context;
+
             println!(
-                "{TAB}{TAB}{GRAYER}...{RESET}{LIGHT_GRAY}{}{}{RESET}{GRAYER}...{RESET}",
-                GRAYEST.bg(),
-                context
+                "{TAB}{TAB}{faint}...{RESET}{context_color}{background}{}{BOLD}{matched}{}{RESET}\
+                {context_color}{background}{}{RESET}{faint}...{RESET}",
+                &text[..*start],
+                &text[*start..*end],
+                &text[*end..],
             );
         }
 
@@ -404,40 +738,138 @@ fn print_vague_search_results(search_results: &[VagueResult], mut start_index: u
     Ok(())
 }
 
-fn print_search_results(search_results: &[ExactResult], mut start_index: usize) -> ResultS {
+fn print_search_results(
+    search_results: &[ExactResult],
+    mut start_index: usize,
+    show_docset: bool,
+) -> ResultS {
     let mut prev_item = "";
+    let mut prev_docset = "";
+    let mut prev_type = "";
+
+    let theme = get_theme();
 
-    // Group fragments by an item.
+    let secondary = theme.secondary;
+    let faint = theme.faint;
+    let heading = theme.heading;
+
+    // Group fragments by an item, and items by their type.
     for result in search_results {
+        if result.r#type != prev_type {
+            println!("{HALF_TAB}{heading}{}{RESET}", result.r#type);
+            prev_item = "";
+        }
+
+        let docset_prefix = get_docset_prefix(&result.docset, show_docset);
+
         if let Some(fragment) = &result.fragment {
-            if result.item == prev_item {
+            if result.item == prev_item && result.docset == prev_docset {
                 println!(
-                    "{TAB}{HALF_TAB}{GRAYER}{start_index:>4}{HALF_TAB}{GRAY}#{}{RESET}",
+                    "{TAB}{HALF_TAB}{faint}{start_index:>4}{HALF_TAB}{secondary}#{}{RESET}",
                     fragment
                 );
             } else {
                 println!(
-                    "{GRAY}{start_index:>4}{RESET}{HALF_TAB}{}{GRAY}, #{}{RESET}",
+                    "{secondary}{start_index:>4}{RESET}{HALF_TAB}{docset_prefix}{}{secondary}, #{}{RESET}",
                     result.item, fragment
                 );
             }
         } else {
-            println!("{GRAY}{start_index:>4}{RESET}{HALF_TAB}{}", result.item);
+            println!(
+                "{secondary}{start_index:>4}{RESET}{HALF_TAB}{docset_prefix}{}",
+                result.item
+            );
         }
 
         prev_item = &result.item;
+        prev_docset = &result.docset;
+        prev_type = &result.r#type;
         start_index += 1;
     }
 
     Ok(())
 }
 
+// A result with the number that `--open` takes, for JSON output. The schema is in `src/json.rs`.
+#[derive(Serialize)]
+struct NumberedResult<'a, T> {
+    index: usize,
+    #[serde(flatten)]
+    result: &'a T,
+}
+
+#[derive(Serialize)]
+struct SearchResultsJson<'a> {
+    query: &'a str,
+    docsets: &'a [&'a str],
+    exact: Vec<NumberedResult<'a, ExactResult>>,
+    vague: Vec<NumberedResult<'a, VagueResult>>,
+}
+
+#[derive(Serialize)]
+struct EntryTypeJson<'a> {
+    docset: &'a str,
+    r#type: String,
+    count: usize,
+}
+
+fn get_search_results_json<'a>(
+    query: &'a str,
+    docsets: &'a [&'a str],
+    exact_results: &'a [ExactResult],
+    vague_results: &'a [VagueResult],
+) -> SearchResultsJson<'a> {
+    let numbered_exact = exact_results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| NumberedResult {
+            index: i + 1,
+            result,
+        });
+
+    let numbered_vague = vague_results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| NumberedResult {
+            index: exact_results.len() + i + 1,
+            result,
+        });
+
+    SearchResultsJson {
+        query,
+        docsets,
+        exact: numbered_exact.collect(),
+        vague: numbered_vague.collect(),
+    }
+}
+
+fn print_docset_entry_types(docset_name: &str) -> ResultS {
+    let types = get_docset_entry_types(docset_name)?;
+
+    if types.is_empty() {
+        println!("{BOLD}No entry types in `{docset_name}`{RESET}.");
+        return Ok(());
+    }
+
+    println!("{BOLD}Entry types in `{docset_name}`{RESET}:");
+
+    let secondary = get_theme().secondary;
+
+    for (name, count) in types {
+        println!("{secondary}{count:>6}{RESET}{HALF_TAB}{name}");
+    }
+
+    Ok(())
+}
+
 fn search_impl(
     search_options: SearchOptions,
     // Passing this as a String is needed to check if output was not numeric
     // before parsing it as number
     flag_open: String,
     flag_columns: String,
+    follow_link: Option<usize>,
+    page_options: &PageOptions,
 ) -> Result<Vec<String>, String> {
     let mut warnings = vec![];
 
@@ -448,7 +880,7 @@ fn search_impl(
     } = search_options;
 
     let open_number = flag_open.parse::<usize>().ok();
-    let mut width = get_terminal_width();
+    let mut width = get_page_width(page_options)?;
 
     let maybe_columns = flag_columns.parse::<usize>().ok();
     if let Some(col_number) = maybe_columns {
@@ -461,16 +893,37 @@ fn search_impl(
         warnings.push("Invalid number of columns.".to_string());
     }
 
-    if open_number.is_none() {
+    if open_number.is_none() && !is_json_output() {
         // This lets you know whether flag messed up your query
         println!("Searching for `{}`...", search_options.query);
     }
 
+    let docset_names: Vec<&str> = docset.split(',').collect();
+    let show_docset = docset_names.len() > 1;
+    let docsets_list = docset_names.join("`, `");
+
     if flags.precise {
         let (exact_results, vague_results) = if let Some(cache) = try_use_cache(&search_options) {
             (cache.exact_results, cache.vague_results)
         } else {
-            let (exact, vague) = search_docset_precisely(docset, query, flags.case_insensitive)?;
+            let mut exact = vec![];
+            let mut vague = vec![];
+
+            for docset_name in &docset_names {
+                // Zeal docsets can't be indexed.
+                if !has_fulltext_index(docset_name)? && !is_zeal_docset(docset_name)? {
+                    warnings.push(format!(
+                        "`{docset_name}` has no full-text index, which makes `--precise` slow. \
+                        Run `index {docset_name}` to build it."
+                    ));
+                }
+
+                let (mut docset_exact, mut docset_vague) =
+                    search_docset_precisely(docset_name, query, flags)?;
+
+                exact.append(&mut docset_exact);
+                vague.append(&mut docset_vague);
+            }
 
             let search_cache = SearchCache {
                 exact_results: Cow::Borrowed(&exact),
@@ -498,12 +951,26 @@ fn search_impl(
                     } else {
                         result.fragment.as_ref()
                     };
-                    print_page_from_docset(docset, &result.item, fragment, width)?;
+                    print_page_from_docset(
+                        &result.docset,
+                        &result.item,
+                        fragment,
+                        width,
+                        follow_link,
+                        page_options,
+                    )?;
                     return Ok(warnings);
                 }
                 Some(n) => {
                     let result = &vague_results[n - exact_results_offset - 1];
-                    print_page_from_docset(docset, &result.item, None, width)?;
+                    print_page_from_docset(
+                        &result.docset,
+                        &result.item,
+                        None,
+                        width,
+                        follow_link,
+                        page_options,
+                    )?;
                     return Ok(warnings);
                 }
                 _ => {
@@ -512,18 +979,28 @@ fn search_impl(
             }
         }
 
+        if is_json_output() {
+            print_json(&get_search_results_json(
+                query,
+                &docset_names,
+                &exact_results,
+                &vague_results,
+            ))?;
+            return Ok(warnings);
+        }
+
         if !exact_results.is_empty() {
-            println!("{BOLD}Exact matches in `{docset}`{RESET}:");
-            print_search_results(&exact_results, 1)?;
+            println!("{BOLD}Exact matches in `{docsets_list}`{RESET}:");
+            print_search_results(&exact_results, 1, show_docset)?;
         } else {
-            println!("{BOLD}No exact matches in `{docset}`{RESET}.");
+            println!("{BOLD}No exact matches in `{docsets_list}`{RESET}.");
         }
 
         if !vague_results.is_empty() {
-            println!("{BOLD}Mentions in other files from `{docset}`{RESET}:");
-            print_vague_search_results(&vague_results, exact_results_offset + 1)?;
+            println!("{BOLD}Mentions in other files from `{docsets_list}`{RESET}:");
+            print_vague_search_results(&vague_results, exact_results_offset + 1, show_docset)?;
         } else {
-            println!("{BOLD}No mentions in other files from `{docset}`{RESET}.");
+            println!("{BOLD}No mentions in other files from `{docsets_list}`{RESET}.");
         }
 
         Ok(warnings)
@@ -531,7 +1008,7 @@ fn search_impl(
         let results = if let Some(cache) = try_use_cache(&search_options) {
             cache.exact_results
         } else {
-            let exact = search_docset_in_filenames(docset, query, flags.case_insensitive)?;
+            let exact = search_docsets_in_filenames(&docset_names, query, flags)?;
 
             let search_cache = SearchCache {
                 exact_results: Cow::Borrowed(&exact),
@@ -557,7 +1034,14 @@ fn search_impl(
                     } else {
                         result.fragment.as_ref()
                     };
-                    print_page_from_docset(docset, &result.item, fragment, width)?;
+                    print_page_from_docset(
+                        &result.docset,
+                        &result.item,
+                        fragment,
+                        width,
+                        follow_link,
+                        page_options,
+                    )?;
                     return Ok(warnings);
                 }
                 _ => {
@@ -566,17 +1050,44 @@ fn search_impl(
             }
         }
 
+        if is_json_output() {
+            print_json(&get_search_results_json(
+                query,
+                &docset_names,
+                &results,
+                &[],
+            ))?;
+            return Ok(warnings);
+        }
+
         if !results.is_empty() {
-            println!("{BOLD}Exact matches in `{docset}`{RESET}:");
-            print_search_results(&results, 1)?;
+            println!("{BOLD}Exact matches in `{docsets_list}`{RESET}:");
+            print_search_results(&results, 1, show_docset)?;
         } else {
-            println!("{BOLD}No exact matches in `{docset}`{RESET}.");
+            println!("{BOLD}No exact matches in `{docsets_list}`{RESET}.");
         }
 
         Ok(warnings)
     }
 }
 
+// Whether `argument` names docsets, like `rust,tokio`, instead of being a query.
+fn is_docset_list(argument: &str, docs: &[Docs]) -> Result<bool, String> {
+    for docset_name in argument.split(',').filter(|name| !name.is_empty()) {
+        let is_known = is_docset_available(docset_name)?
+            || matches!(
+                is_docset_in_docs(&docset_name.to_string(), docs),
+                SearchMatch::Exact
+            );
+
+        if !is_known {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
 pub(crate) fn search<Args>(mut args: Args) -> ResultS
 where
     Args: Iterator<Item = String>,
@@ -586,7 +1097,18 @@ where
     let mut flag_precise;
     let mut flag_open;
     let mut flag_case_insensitive;
+    let mut flag_substring;
+    let mut flag_regex;
+    let mut flag_type;
+    let mut flag_list_types;
+    let mut flag_all_local;
     let mut flag_ignore_fragment;
+    let mut flag_markdown;
+    let mut flag_line_numbers;
+    let mut flag_lines;
+    let mut flag_grep;
+    let mut flag_context;
+    let mut flag_follow;
     let mut flag_help;
 
     let mut flags = flags![
@@ -595,7 +1117,18 @@ where
         flag_precise: BoolFlag,          ["-p", "--precise"],
         flag_open: StringFlag,           ["-o", "--open"],
         flag_case_insensitive: BoolFlag, ["-i", "--ignore-case"],
+        flag_substring: BoolFlag,        ["-s", "--substring"],
+        flag_regex: BoolFlag,            ["-r", "--regex"],
+        flag_type: StringFlag,           ["-t", "--type"],
+        flag_list_types: BoolFlag,       ["--list-types"],
+        flag_all_local: BoolFlag,        ["-a", "--all-local"],
         flag_ignore_fragment: BoolFlag,  ["-f", "--ignore-fragment"],
+        flag_markdown: BoolFlag,         ["-m", "--markdown"],
+        flag_line_numbers: BoolFlag,     ["-n", "--line-numbers"],
+        flag_lines: StringFlag,          ["--lines"],
+        flag_grep: StringFlag,           ["--grep"],
+        flag_context: StringFlag,        ["--context"],
+        flag_follow: StringFlag,         ["--follow"],
         flag_help: BoolFlag,             ["--help"]
     ];
 
@@ -611,39 +1144,118 @@ The list of available documents has not yet been downloaded. Please run `fetch`
             .to_string());
     }
 
+    let docs = deserialize_docs_json()?;
+
+    // With only a query, search docsets from the config, unless the argument is a docset.
+    let default_docsets = match (args.as_slice(), flag_list_types) {
+        ([argument], false) if !is_docset_list(argument, &docs)? => get_config()?.docsets,
+        _ => None,
+    };
+
     let mut args = args.into_iter();
 
-    let docset = if let Some(docset_name) = args.next() {
-        docset_name
+    let requested_docsets = if flag_all_local {
+        let mut local_docsets = get_local_docsets()?;
+        local_docsets.append(&mut get_zeal_docsets()?);
+        local_docsets
+    } else if let Some(default_docsets) = default_docsets {
+        default_docsets
+    } else if let Some(docset_names) = args.next() {
+        docset_names
+            .split(',')
+            .filter(|docset_name| !docset_name.is_empty())
+            .map(|docset_name| docset_name.to_owned())
+            .collect()
     } else {
         return show_search_help();
     };
 
-    let docs = deserialize_docs_json()?;
+    let mut docsets = vec![];
 
-    if !is_docset_downloaded(&docset)? {
-        if is_docset_in_docs_or_print_warning(&docset, &docs) {
+    for docset in requested_docsets {
+        if is_docset_available(&docset)? {
+            docsets.push(docset);
+        } else if is_docset_in_docs_or_print_warning(&docset, &docs) {
             print_warning!("Docset `{docset}` is not downloaded. Try running `download {docset}`.");
         }
+    }
+
+    if docsets.is_empty() {
+        if flag_all_local {
+            print_warning!("There are no downloaded docsets.");
+        }
+        if is_json_output() {
+            let query = args.collect::<Vec<String>>().join(" ");
+            return print_json(&get_search_results_json(&query, &[], &[], &[]));
+        }
         return Ok(());
     }
 
-    let query = {
-        let mut merged_args = args.collect::<Vec<String>>().join(" ");
+    if flag_list_types && is_json_output() {
+        let mut entry_types = vec![];
 
-        if flag_whole {
-            merged_args.insert(0, ' ');
-            merged_args.push(' ');
-            merged_args
-        } else {
-            merged_args
+        for docset in docsets.iter() {
+            for (name, count) in get_docset_entry_types(docset)? {
+                entry_types.push(EntryTypeJson {
+                    docset,
+                    r#type: name,
+                    count,
+                });
+            }
         }
-    };
+
+        return print_json(&entry_types);
+    }
+
+    if flag_list_types {
+        for docset in docsets.iter() {
+            print_docset_entry_types(docset)?;
+        }
+        return Ok(());
+    }
+
+    if args.len() == 0 {
+        return Err("No query specified. Try `search --help` for more information.".to_string());
+    }
+
+    let docset = docsets.join(",");
+
+    if flag_precise && !flag_type.is_empty() {
+        print_warning!("`--type` has no effect with `--precise`.");
+        flag_type.clear();
+    }
+
+    let follow_link = parse_follow_flag(&flag_follow)?;
+
+    if follow_link.is_some() && flag_open.is_empty() {
+        print_warning!("`--follow` has no effect without `--open`.");
+    }
+
+    let page_options = parse_page_options(
+        flag_markdown,
+        flag_line_numbers,
+        &flag_lines,
+        &flag_grep,
+        &flag_context,
+    )?;
+
+    if flag_markdown && flag_open.is_empty() {
+        print_warning!("`--markdown` has no effect without `--open`.");
+    }
+
+    if page_options.has_line_options() && flag_open.is_empty() {
+        print_warning!("`--line-numbers`, `--lines` and `--grep` have no effect without `--open`.");
+    }
+
+    let query = args.collect::<Vec<String>>().join(" ");
 
     let search_flags = SearchFlags {
         precise: flag_precise,
         case_insensitive: flag_case_insensitive,
         whole: flag_whole,
+        substring: flag_substring,
+        regex: flag_regex,
+        entry_type: flag_type,
         ignore_fragment: flag_ignore_fragment,
     };
 
@@ -654,10 +1266,104 @@ The list of available documents has not yet been downloaded. Please run `fetch`
     };
 
     // Print warnings only after search results
-    let warnings = search_impl(search_options, flag_open, flag_columns)?;
+    let warnings = search_impl(
+        search_options,
+        flag_open,
+        flag_columns,
+        follow_link,
+        &page_options,
+    )?;
     for warning in warnings {
         print_warning!("{}", warning);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_matcher(query: &str, whole: bool, case_insensitive: bool) -> QueryMatcher {
+        let flags = SearchFlags {
+            whole,
+            case_insensitive,
+            ..Default::default()
+        };
+        QueryMatcher::new(query, &flags).unwrap()
+    }
+
+    #[test]
+    fn test_find_whole_words() {
+        let matcher = create_matcher("vec", true, false);
+
+        assert_eq!(matcher.find("vec"), Some((0, 3)));
+        assert_eq!(matcher.find("std::vec"), Some((5, 8)));
+        assert_eq!(matcher.find("std/vec/struct.vec#method.push"), Some((4, 7)));
+        assert_eq!(matcher.find("a vector, then vec."), Some((15, 18)));
+        assert_eq!(matcher.find("vector"), None);
+        assert_eq!(matcher.find("smallvec"), None);
+        assert_eq!(matcher.find("vec_deque"), None);
+
+        let matcher = create_matcher("::new", true, false);
+
+        assert_eq!(matcher.find("Vec::new()"), Some((3, 8)));
+        assert_eq!(matcher.find("Vec::new_in()"), None);
+    }
+
+    #[test]
+    fn test_search_results_json() {
+        let exact = [ExactResult {
+            item: "std/vec/struct.vec".to_string(),
+            fragment: Some("method.push".to_string()),
+            r#type: "Structs".to_string(),
+            docset: "rust".to_string(),
+        }];
+        let vague = [VagueResult {
+            item: "std/index".to_string(),
+            contexts: vec![Context {
+                text: "use Vec::push".to_string(),
+                start: 4,
+                end: 13,
+            }],
+            docset: "rust".to_string(),
+        }];
+
+        let json = serde_json::to_value(get_search_results_json("push", &["rust"], &exact, &vague))
+            .unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "query": "push",
+                "docsets": ["rust"],
+                "exact": [{
+                    "index": 1,
+                    "item": "std/vec/struct.vec",
+                    "fragment": "method.push",
+                    "type": "Structs",
+                    "docset": "rust"
+                }],
+                "vague": [{
+                    "index": 2,
+                    "item": "std/index",
+                    "contexts": [{"text": "use Vec::push", "start": 4, "end": 13}],
+                    "docset": "rust"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_ignore_case() {
+        let matcher = create_matcher("straße", false, true);
+
+        assert_eq!(matcher.find("STRAßE"), Some((0, 7)));
+        assert_eq!(matcher.find("İ straße"), Some((3, 10)));
+
+        let matcher = create_matcher("größe", true, true);
+
+        assert_eq!(matcher.find("Die GRÖßE."), Some((4, 11)));
+        assert_eq!(matcher.find("Größenordnung"), None);
+    }
+}
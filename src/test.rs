@@ -6,7 +6,7 @@ use std::io::BufReader;
 use std::vec::IntoIter;
 
 use crate::common::ResultS;
-use crate::common::{get_flag_error, get_program_directory};
+use crate::common::{get_cache_directory, get_flag_error, get_program_directory};
 use crate::common::{BOLD, GREEN, PROGRAM_NAME, RED, RESET};
 use crate::debug_println;
 
@@ -38,10 +38,11 @@ fn reset_state_and_cache() {
     debug_println!("Removing cache...");
 
     let program_directory = get_program_directory().unwrap();
+    let cache_directory = get_cache_directory().unwrap();
 
     let _ = remove_file(program_directory.join("docs.json"));
-    let _ = remove_file(program_directory.join("search_cache_options.json"));
-    let _ = remove_file(program_directory.join("search_cache.json"));
+    let _ = remove_file(cache_directory.join("search_cache_options.json"));
+    let _ = remove_file(cache_directory.join("search_cache.json"));
 }
 
 fn create_args(args: &str) -> IntoIter<String> {
@@ -69,13 +70,13 @@ fn run_with_args<O>(
 }
 
 fn test_search_should_use_cache(args: &str) {
-    let program_directory = get_program_directory().unwrap();
-    let _ = remove_file(program_directory.join("search_cache_options.json"));
+    let cache_directory = get_cache_directory().unwrap();
+    let _ = remove_file(cache_directory.join("search_cache_options.json"));
 
     run_with_args(search, args, "print search results");
 
     {
-        let cache_options_path = program_directory.join("search_cache_options.json");
+        let cache_options_path = cache_directory.join("search_cache_options.json");
         let cache_options_file = File::open(cache_options_path).unwrap();
         let cache_options_reader = BufReader::new(cache_options_file);
 
@@ -0,0 +1,302 @@
+// Colors of pages, search results and messages. `dark` is the default, `light` is for terminals
+// with a light background, and `none` only keeps styles like bold. Other themes are TOML files in
+// `themes` of the config directory, which change some colors of a built-in theme:
+//
+//     # Theme to start from, `dark` if it's not set.
+//     base = "light"
+//     # Colors are names like `blue` or `bright-blue`, numbers of the 256 colors, `#rrggbb` or
+//     # `none`.
+//     link = "#0969da"
+//     code_block_background = 255
+//     # One of `SYNTAX_THEMES`, or `none` to not highlight code.
+//     syntax_theme = "InspiredGitHub"
+//
+// Names of colors are the same as fields of `Theme`.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use toiletcli::colors::Color;
+
+use crate::common::{get_config_directory, get_terminal_color};
+
+pub(crate) const THEMES_DIRECTORY_NAME: &str = "themes";
+
+// Themes for highlighting that are built into `syntect`.
+pub(crate) const SYNTAX_THEMES: &[&str] = &[
+    "base16-ocean.dark",
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "base16-ocean.light",
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Theme {
+    // Links and images of pages.
+    pub(crate) link: Color,
+    pub(crate) image: Color,
+    // Inline code of pages.
+    pub(crate) code: Color,
+    // Code blocks of pages, and contexts of `search --precise`.
+    pub(crate) code_block: Color,
+    pub(crate) code_block_background: Color,
+    pub(crate) syntax_theme: Option<&'static str>,
+    // Numbers of results and links, and fragments of results.
+    pub(crate) secondary: Color,
+    // Docsets of results, and `...` around fragments and contexts.
+    pub(crate) faint: Color,
+    // Types of results.
+    pub(crate) heading: Color,
+    // Matches in contexts of `search --precise`.
+    pub(crate) matched: Color,
+    pub(crate) warning: Color,
+    pub(crate) error: Color,
+}
+
+pub(crate) const DARK_THEME: Theme = Theme {
+    link: Color::Blue,
+    image: Color::BrightBlue,
+    code: Color::BrightBlack,
+    code_block: Color::Byte(248),
+    code_block_background: Color::Byte(234),
+    syntax_theme: Some("base16-ocean.dark"),
+    secondary: Color::BrightBlack,
+    faint: Color::Byte(240),
+    heading: Color::Green,
+    matched: Color::Yellow,
+    warning: Color::Yellow,
+    error: Color::Red,
+};
+
+pub(crate) const LIGHT_THEME: Theme = Theme {
+    link: Color::Blue,
+    image: Color::Purple,
+    code: Color::Byte(242),
+    code_block: Color::Byte(236),
+    code_block_background: Color::Byte(254),
+    syntax_theme: Some("base16-ocean.light"),
+    secondary: Color::Byte(243),
+    faint: Color::Byte(246),
+    heading: Color::Byte(28),
+    matched: Color::Byte(166),
+    warning: Color::Byte(130),
+    error: Color::Red,
+};
+
+pub(crate) const NO_COLOR_THEME: Theme = Theme {
+    link: Color::None,
+    image: Color::None,
+    code: Color::None,
+    code_block: Color::None,
+    code_block_background: Color::None,
+    syntax_theme: None,
+    secondary: Color::None,
+    faint: Color::None,
+    heading: Color::None,
+    matched: Color::None,
+    warning: Color::None,
+    error: Color::None,
+};
+
+static THEME: Mutex<Option<Theme>> = Mutex::new(None);
+
+#[inline]
+pub(crate) fn set_theme(theme: Theme) {
+    if let Ok(mut current_theme) = THEME.lock() {
+        *current_theme = Some(theme);
+    }
+}
+
+// Falls back to the default theme, so that it can be used before the config is read, e.g. in
+// `print_warning!`.
+pub(crate) fn get_theme() -> Theme {
+    THEME
+        .lock()
+        .ok()
+        .and_then(|theme| *theme)
+        .unwrap_or_else(get_default_theme)
+}
+
+// Some terminals tell their colors in `COLORFGBG`, like `0;15` for black on white.
+fn get_default_theme() -> Theme {
+    let background = std::env::var("COLORFGBG")
+        .ok()
+        .and_then(|colors| colors.rsplit(';').next()?.parse::<u8>().ok());
+
+    match background {
+        Some(7 | 9..=15) => LIGHT_THEME,
+        _ => DARK_THEME,
+    }
+}
+
+impl Theme {
+    fn get_color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        let color = match name {
+            "link" => &mut self.link,
+            "image" => &mut self.image,
+            "code" => &mut self.code,
+            "code_block" => &mut self.code_block,
+            "code_block_background" => &mut self.code_block_background,
+            "secondary" => &mut self.secondary,
+            "faint" => &mut self.faint,
+            "heading" => &mut self.heading,
+            "matched" => &mut self.matched,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
+fn parse_color(value: &toml::Value) -> Result<Color, String> {
+    let value = match value {
+        toml::Value::Integer(number) => number.to_string(),
+        toml::Value::String(string) => string.to_lowercase(),
+        other => return Err(format!("`{other}` is not a color")),
+    };
+
+    if value == "none" {
+        return Ok(Color::None);
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(get_terminal_color(r, g, b)),
+            _ => Err(format!("`{value}` is not a `#rrggbb` color")),
+        };
+    }
+
+    value
+        .parse::<Color>()
+        .map_err(|_| format!("Unknown color `{value}`"))
+}
+
+fn parse_syntax_theme(value: &toml::Value) -> Result<Option<&'static str>, String> {
+    match value.as_str() {
+        Some("none") => Ok(None),
+        Some(name) => SYNTAX_THEMES
+            .iter()
+            .find(|syntax_theme| **syntax_theme == name)
+            .map(|syntax_theme| Some(*syntax_theme))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown syntax theme `{name}`, it can be `{}` or `none`",
+                    SYNTAX_THEMES.join("`, `")
+                )
+            }),
+        None => Err(format!("`{value}` is not a syntax theme")),
+    }
+}
+
+fn parse_theme(contents: &str) -> Result<Theme, String> {
+    let table: toml::Table = toml::from_str(contents).map_err(|err| err.to_string())?;
+
+    let mut theme = match table.get("base") {
+        Some(base) => base
+            .as_str()
+            .and_then(get_builtin_theme)
+            .ok_or_else(|| format!("`base` can be `dark`, `light` or `none`, got `{base}`"))?,
+        None => DARK_THEME,
+    };
+
+    for (key, value) in table.iter() {
+        if key == "base" {
+            continue;
+        }
+
+        if key == "syntax_theme" {
+            theme.syntax_theme = parse_syntax_theme(value)?;
+            continue;
+        }
+
+        let color = theme
+            .get_color_mut(key)
+            .ok_or_else(|| format!("Unknown key `{key}`"))?;
+
+        *color = parse_color(value).map_err(|err| format!("{err} in `{key}`"))?;
+    }
+
+    Ok(theme)
+}
+
+fn get_builtin_theme(name: &str) -> Option<Theme> {
+    match name {
+        "default" => Some(get_default_theme()),
+        "dark" => Some(DARK_THEME),
+        "light" => Some(LIGHT_THEME),
+        "none" => Some(NO_COLOR_THEME),
+        _ => None,
+    }
+}
+
+// Themes are looked up by name in the themes directory, and `.toml` files can be anywhere.
+fn get_theme_path(name: &str) -> Result<PathBuf, String> {
+    let themes_directory = get_config_directory()?.join(THEMES_DIRECTORY_NAME);
+
+    if name.ends_with(".toml") {
+        Ok(themes_directory.join(name))
+    } else {
+        Ok(themes_directory.join(format!("{name}.toml")))
+    }
+}
+
+pub(crate) fn read_theme(name: &str) -> Result<Theme, String> {
+    if let Some(theme) = get_builtin_theme(name) {
+        return Ok(theme);
+    }
+
+    let theme_path = get_theme_path(name)?;
+
+    if !theme_path.exists() {
+        return Err(format!(
+            "Unknown theme `{name}`. Themes are `dark`, `light`, `none`, or files in `{}`.",
+            theme_path.parent().unwrap_or(&theme_path).display()
+        ));
+    }
+
+    let contents = std::fs::read_to_string(&theme_path)
+        .map_err(|err| format!("Could not read `{}`: {err}", theme_path.display()))?;
+
+    parse_theme(&contents)
+        .map_err(|err| format!("Could not parse `{}`: {err}.", theme_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = parse_theme(
+            r##"
+base = "light"
+link = "bright-blue"
+code_block_background = 255
+heading = "none"
+syntax_theme = "InspiredGitHub"
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.link, Color::BrightBlue);
+        assert_eq!(theme.code_block_background, Color::Byte(255));
+        assert_eq!(theme.heading, Color::None);
+        assert_eq!(theme.syntax_theme, Some("InspiredGitHub"));
+        assert_eq!(theme.code_block, LIGHT_THEME.code_block);
+
+        assert_eq!(parse_theme("").unwrap(), DARK_THEME);
+        assert!(parse_theme("link = \"#12345\"").is_err());
+        assert!(parse_theme("links = \"blue\"").is_err());
+        assert!(parse_theme("base = \"sepia\"").is_err());
+        assert!(parse_theme("syntax_theme = \"Monokai\"").is_err());
+    }
+}
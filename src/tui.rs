@@ -0,0 +1,965 @@
+use std::io::{stdout, Stdout, Write};
+use std::path::{Path, PathBuf};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
+    EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::tty::IsTty;
+use crossterm::{execute, queue};
+
+use toiletcli::flags;
+use toiletcli::flags::*;
+
+use crate::common::ResultS;
+use crate::common::{
+    deserialize_docs_json, format_rich_line, get_docset_pages_path, get_flag_error,
+    get_fragment_bounds, get_fragment_lines, get_page_links, get_page_path, get_width_for_terminal,
+    is_docs_json_exists, is_docset_available, is_docset_in_docs_or_print_warning,
+    render_docset_file, resolve_link, split_to_item_and_fragment, PageLink, RichPage,
+};
+use crate::common::{BOLD, GREEN, PROGRAM_NAME, RESET};
+use crate::config::{get_config, Config};
+use crate::fuzzy::fuzzy_score;
+use crate::highlight::highlight_page;
+use crate::pack::is_docset_file;
+use crate::print_warning;
+use crate::search::{deserialize_index_json, IndexEntry};
+use crate::theme::get_theme;
+
+fn show_tui_help() -> ResultS {
+    println!(
+        "\
+{GREEN}USAGE{RESET}
+    {BOLD}{PROGRAM_NAME} tui{RESET} [-ic] <docset> [query]
+    Interactively search a docset and read its pages.
+
+{GREEN}OPTIONS{RESET}
+    -i, --ignore-case               Ignore character case.
+    -c, --columns <number>          Make pages N columns wide.
+        --help                      Display help message.
+
+{GREEN}SEARCH KEYS{RESET}
+    Up, Down, PgUp, PgDn            Select a result.
+    Enter                           Open selected result.
+    Ctrl-U                          Clear the query.
+    Esc                             Return to the page, or quit.
+
+{GREEN}PAGE KEYS{RESET}
+    j, k, Space, b, d, u, g, G      Scroll.
+    Tab, Shift-Tab                  Select next or previous link.
+    Enter                           Follow selected link.
+    Backspace, h                    Go back.
+    ], [                            Jump to next or previous section.
+    /, Esc                          Return to search.
+    q                               Quit."
+    );
+    Ok(())
+}
+
+// Restores the terminal when the interface exits, including on errors.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter(out: &mut Stdout) -> Result<Self, String> {
+        enable_raw_mode().map_err(|err| format!("Could not enable raw mode: {err}"))?;
+
+        let guard = TerminalGuard;
+
+        execute!(out, EnterAlternateScreen, DisableLineWrap, Hide)
+            .map_err(|err| format!("Could not set up the terminal: {err}"))?;
+
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, EnableLineWrap, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+struct Page {
+    item: String,
+    path: PathBuf,
+    rich_page: RichPage,
+    links: Vec<PageLink>,
+    fragment_lines: Vec<usize>,
+    scroll: usize,
+    selected_link: Option<usize>,
+}
+
+impl Page {
+    fn open(
+        docset: &str,
+        pages_path: &Path,
+        item: String,
+        path: PathBuf,
+        width: usize,
+    ) -> Result<Self, String> {
+        let mut rich_page = render_docset_file(pages_path, &path, width)?;
+        highlight_page(&mut rich_page, pages_path, &path, Some(docset));
+
+        let links = get_page_links(&rich_page);
+        let fragment_lines = get_fragment_lines(&rich_page);
+
+        Ok(Page {
+            item,
+            path,
+            rich_page,
+            links,
+            fragment_lines,
+            scroll: 0,
+            selected_link: None,
+        })
+    }
+
+    fn scroll_to_fragment(&mut self, fragment: &str) -> bool {
+        if let (Some(line), _) = get_fragment_bounds(&self.rich_page, fragment) {
+            self.scroll = line;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+enum Action {
+    Continue,
+    Quit,
+}
+
+struct Tui {
+    docset: String,
+    pages_path: PathBuf,
+    entries: Vec<IndexEntry>,
+    case_insensitive: bool,
+    columns: Option<usize>,
+    // For `width` and `max_width`.
+    config: Config,
+
+    terminal_width: usize,
+    terminal_height: usize,
+
+    is_searching: bool,
+    query: String,
+    results: Vec<usize>,
+    selected_result: usize,
+    results_scroll: usize,
+
+    page: Option<Page>,
+    // Pages that were open before the current one, with their scroll positions.
+    history: Vec<(String, PathBuf, usize)>,
+
+    message: String,
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+impl Tui {
+    fn view_height(&self) -> usize {
+        self.terminal_height.saturating_sub(1).max(1)
+    }
+
+    fn page_width(&self) -> usize {
+        self.columns
+            .unwrap_or_else(|| get_width_for_terminal(&self.config, self.terminal_width))
+    }
+
+    fn update_results(&mut self) {
+        self.selected_result = 0;
+        self.results_scroll = 0;
+
+        if self.query.trim().is_empty() {
+            self.results = (0..self.entries.len()).collect();
+            return;
+        }
+
+        // Smart case, same as `search`.
+        let case_insensitive =
+            self.case_insensitive || !self.query.chars().any(|ch| ch.is_uppercase());
+
+        let mut scored_results: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                let name_score = fuzzy_score(&self.query, &entry.name, case_insensitive);
+                let path_score = fuzzy_score(&self.query, &entry.path, case_insensitive);
+
+                std::cmp::max(name_score, path_score).map(|score| (score, i))
+            })
+            .collect();
+
+        scored_results.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.cmp(b)));
+
+        self.results = scored_results.into_iter().map(|(_, i)| i).collect();
+    }
+
+    fn select_result(&mut self, selected: usize) {
+        let view_height = self.view_height().saturating_sub(1).max(1);
+
+        self.selected_result = std::cmp::min(selected, self.results.len().saturating_sub(1));
+
+        if self.selected_result < self.results_scroll {
+            self.results_scroll = self.selected_result;
+        } else if self.selected_result >= self.results_scroll + view_height {
+            self.results_scroll = self.selected_result + 1 - view_height;
+        }
+    }
+
+    fn open_page(&mut self, item: String, fragment: Option<String>) {
+        let path = match get_page_path(&self.docset, &item) {
+            Ok(path) => path,
+            Err(err) => {
+                self.message = err;
+                return;
+            }
+        };
+
+        if !is_docset_file(&self.pages_path, &path) {
+            self.message = format!("No page matching `{item}`.");
+            return;
+        }
+
+        let mut page = match Page::open(
+            &self.docset,
+            &self.pages_path,
+            item,
+            path,
+            self.page_width(),
+        ) {
+            Ok(page) => page,
+            Err(err) => {
+                self.message = err;
+                return;
+            }
+        };
+
+        if let Some(fragment) = fragment {
+            if !page.scroll_to_fragment(&fragment) {
+                self.message = format!("No section `#{fragment}` in this page.");
+            }
+        }
+
+        if let Some(previous) = self.page.take() {
+            self.history
+                .push((previous.item, previous.path, previous.scroll));
+        }
+
+        self.page = Some(page);
+        self.is_searching = false;
+
+        self.clamp_scroll();
+    }
+
+    fn open_selected_result(&mut self) {
+        let Some(&entry_index) = self.results.get(self.selected_result) else {
+            return;
+        };
+
+        let path = self.entries[entry_index].path.clone();
+
+        match split_to_item_and_fragment(path) {
+            Ok((item, fragment)) => self.open_page(item, fragment),
+            Err(err) => self.message = err,
+        }
+    }
+
+    fn go_back(&mut self) {
+        let Some((item, path, scroll)) = self.history.pop() else {
+            self.is_searching = true;
+            return;
+        };
+
+        match Page::open(
+            &self.docset,
+            &self.pages_path,
+            item,
+            path,
+            self.page_width(),
+        ) {
+            Ok(mut page) => {
+                page.scroll = scroll;
+                self.page = Some(page);
+                self.clamp_scroll();
+            }
+            Err(err) => self.message = err,
+        }
+    }
+
+    fn follow_selected_link(&mut self) {
+        let Some(page) = &mut self.page else {
+            return;
+        };
+
+        let Some(link) = page.selected_link.and_then(|i| page.links.get(i)) else {
+            self.message = "No link is selected. Press Tab to select one.".to_string();
+            return;
+        };
+
+        match resolve_link(&page.item, &link.href) {
+            Some((item, fragment)) if item == page.item => {
+                let Some(fragment) = fragment else {
+                    return;
+                };
+
+                let previous_scroll = page.scroll;
+
+                if page.scroll_to_fragment(&fragment) {
+                    page.selected_link = None;
+                    self.history
+                        .push((page.item.clone(), page.path.clone(), previous_scroll));
+                    self.clamp_scroll();
+                } else {
+                    self.message = format!("No section `#{fragment}` in this page.");
+                }
+            }
+            Some((item, fragment)) => self.open_page(item, fragment),
+            None => self.message = format!("External link: {}", link.href),
+        }
+    }
+
+    fn clamp_scroll(&mut self) {
+        let view_height = self.view_height();
+
+        if let Some(page) = &mut self.page {
+            let max_scroll = page.rich_page.len().saturating_sub(view_height);
+            page.scroll = std::cmp::min(page.scroll, max_scroll);
+        }
+    }
+
+    fn scroll_by(&mut self, amount: isize) {
+        if let Some(page) = &mut self.page {
+            page.scroll = page.scroll.saturating_add_signed(amount);
+        }
+        self.clamp_scroll();
+    }
+
+    // Selects the next (or previous) link, starting from the visible part of the page.
+    fn select_link(&mut self, forward: bool) {
+        let view_height = self.view_height();
+
+        let Some(page) = &mut self.page else {
+            return;
+        };
+
+        let view_end = page.scroll + view_height;
+        let is_visible =
+            |link: &PageLink| link.last_line >= page.scroll && link.first_line < view_end;
+
+        let selected_visible = page.selected_link.filter(|&i| is_visible(&page.links[i]));
+
+        let next = if forward {
+            match selected_visible {
+                Some(i) => (i + 1 < page.links.len()).then_some(i + 1),
+                None => page
+                    .links
+                    .iter()
+                    .position(|link| link.first_line >= page.scroll),
+            }
+        } else {
+            match selected_visible {
+                Some(i) => i.checked_sub(1),
+                None => page
+                    .links
+                    .iter()
+                    .rposition(|link| link.first_line < view_end),
+            }
+        };
+
+        let Some(next) = next else {
+            self.message = "No more links.".to_string();
+            return;
+        };
+
+        let link = &page.links[next];
+
+        if link.first_line < page.scroll {
+            page.scroll = link.first_line;
+        } else if link.last_line >= view_end {
+            page.scroll = link.last_line + 1 - view_height;
+        }
+
+        page.selected_link = Some(next);
+    }
+
+    fn jump_to_fragment(&mut self, forward: bool) {
+        let Some(page) = &mut self.page else {
+            return;
+        };
+
+        let line = if forward {
+            page.fragment_lines.iter().find(|&&line| line > page.scroll)
+        } else {
+            page.fragment_lines
+                .iter()
+                .rev()
+                .find(|&&line| line < page.scroll)
+        };
+
+        if let Some(&line) = line {
+            page.scroll = line;
+            page.selected_link = None;
+        } else {
+            self.message = "No more sections.".to_string();
+        }
+
+        self.clamp_scroll();
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        self.terminal_width = width as usize;
+        self.terminal_height = height as usize;
+
+        let page_width = self.page_width();
+
+        if let Some(page) = &mut self.page {
+            match Page::open(
+                &self.docset,
+                &self.pages_path,
+                page.item.clone(),
+                page.path.clone(),
+                page_width,
+            ) {
+                Ok(mut resized_page) => {
+                    resized_page.scroll = page.scroll;
+                    *page = resized_page;
+                }
+                Err(err) => self.message = err,
+            }
+        }
+
+        self.clamp_scroll();
+        self.select_result(self.selected_result);
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) -> Action {
+        let page_size = self.view_height().saturating_sub(1).max(1);
+        let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('c') if is_control => return Action::Quit,
+            KeyCode::Esc => {
+                if self.page.is_some() {
+                    self.is_searching = false;
+                } else {
+                    return Action::Quit;
+                }
+            }
+            KeyCode::Enter => self.open_selected_result(),
+            KeyCode::Up => self.select_result(self.selected_result.saturating_sub(1)),
+            KeyCode::Char('p') if is_control => {
+                self.select_result(self.selected_result.saturating_sub(1))
+            }
+            KeyCode::Down => self.select_result(self.selected_result + 1),
+            KeyCode::Char('n') if is_control => self.select_result(self.selected_result + 1),
+            KeyCode::PageUp => self.select_result(self.selected_result.saturating_sub(page_size)),
+            KeyCode::PageDown => self.select_result(self.selected_result + page_size),
+            KeyCode::Char('u') if is_control => {
+                self.query.clear();
+                self.update_results();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_results();
+            }
+            KeyCode::Char(ch) if !is_control => {
+                self.query.push(ch);
+                self.update_results();
+            }
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    fn handle_page_key(&mut self, key: KeyEvent) -> Action {
+        let page_size = self.view_height() as isize;
+        let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('c') if is_control => return Action::Quit,
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Esc | KeyCode::Char('/') => self.is_searching = true,
+            KeyCode::Char('f') if is_control => self.scroll_by(page_size),
+            KeyCode::Char('b') if is_control => self.scroll_by(-page_size),
+            KeyCode::Char('d') if is_control => self.scroll_by(page_size / 2),
+            KeyCode::Char('u') if is_control => self.scroll_by(-page_size / 2),
+            KeyCode::Char('j') | KeyCode::Down => self.scroll_by(1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll_by(-1),
+            KeyCode::Char(' ') | KeyCode::PageDown => self.scroll_by(page_size),
+            KeyCode::Char('b') | KeyCode::PageUp => self.scroll_by(-page_size),
+            KeyCode::Char('d') => self.scroll_by(page_size / 2),
+            KeyCode::Char('u') => self.scroll_by(-page_size / 2),
+            KeyCode::Char('g') | KeyCode::Home => self.scroll_by(isize::MIN),
+            KeyCode::Char('G') | KeyCode::End => self.scroll_by(isize::MAX),
+            KeyCode::Tab => self.select_link(true),
+            KeyCode::BackTab => self.select_link(false),
+            KeyCode::Enter => self.follow_selected_link(),
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => self.go_back(),
+            KeyCode::Char(']') => self.jump_to_fragment(true),
+            KeyCode::Char('[') => self.jump_to_fragment(false),
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        self.message.clear();
+
+        if self.is_searching || self.page.is_none() {
+            self.handle_search_key(key)
+        } else {
+            self.handle_page_key(key)
+        }
+    }
+
+    fn draw_status_bar(&self, out: &mut Stdout, status: &str) -> std::io::Result<()> {
+        let status = if self.message.is_empty() {
+            status.to_owned()
+        } else {
+            format!("{status}  {}", self.message)
+        };
+
+        let status = format!(
+            "{:<width$}",
+            truncate(&status, self.terminal_width),
+            width = self.terminal_width
+        );
+
+        queue!(
+            out,
+            MoveTo(0, self.view_height() as u16),
+            Print(Attribute::Reverse),
+            Print(status),
+            Print(Attribute::Reset)
+        )
+    }
+
+    fn draw_search(&self, out: &mut Stdout) -> std::io::Result<()> {
+        let prompt = format!("{}> ", self.docset);
+
+        queue!(
+            out,
+            MoveTo(0, 0),
+            Print(format!("{BOLD}{prompt}{RESET}")),
+            Print(truncate(
+                &self.query,
+                self.terminal_width.saturating_sub(prompt.len())
+            )),
+            Clear(ClearType::UntilNewLine)
+        )?;
+
+        for row in 1..self.view_height() {
+            queue!(out, MoveTo(0, row as u16))?;
+
+            let result_index = self.results_scroll + row - 1;
+
+            if let Some(&entry_index) = self.results.get(result_index) {
+                let entry = &self.entries[entry_index];
+                let name = truncate(&entry.name, self.terminal_width.saturating_sub(1));
+                let entry_type = truncate(
+                    &entry.r#type,
+                    self.terminal_width.saturating_sub(name.chars().count() + 2),
+                );
+
+                if result_index == self.selected_result {
+                    queue!(
+                        out,
+                        Print(Attribute::Reverse),
+                        Print(name),
+                        Print(Attribute::Reset)
+                    )?;
+                } else {
+                    queue!(out, Print(name))?;
+                }
+
+                let secondary = get_theme().secondary;
+                queue!(out, Print(format!(" {secondary}{entry_type}{RESET}")))?;
+            }
+
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+
+        let status = format!(
+            " {}/{} results. Enter to open, Esc to {}.",
+            self.results.len(),
+            self.entries.len(),
+            if self.page.is_some() {
+                "go back"
+            } else {
+                "quit"
+            }
+        );
+
+        self.draw_status_bar(out, &status)?;
+
+        let cursor_column = prompt.len() + self.query.chars().count();
+
+        queue!(
+            out,
+            MoveTo(std::cmp::min(cursor_column, self.terminal_width) as u16, 0),
+            Show
+        )
+    }
+
+    fn draw_page(&self, out: &mut Stdout, page: &Page) -> std::io::Result<()> {
+        let page_width = self.page_width();
+        let selected_link = page.selected_link.and_then(|i| page.links.get(i));
+
+        for row in 0..self.view_height() {
+            queue!(out, MoveTo(0, row as u16))?;
+
+            let line_number = page.scroll + row;
+
+            if let Some(rich_line) = page.rich_page.get(line_number) {
+                let highlighted_href = selected_link
+                    .filter(|link| (link.first_line..=link.last_line).contains(&line_number))
+                    .map(|link| link.href.as_str());
+
+                let (line, _) = format_rich_line(rich_line, page_width, highlighted_href, &[]);
+
+                queue!(out, Print(line))?;
+            }
+
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+
+        let total_lines = page.rich_page.len();
+        let last_visible_line = std::cmp::min(page.scroll + self.view_height(), total_lines);
+
+        let mut status = format!(
+            " {}: {}  {last_visible_line}/{total_lines}",
+            self.docset, page.item
+        );
+
+        if let Some(link) = selected_link {
+            status += &format!("  -> {}", link.href);
+        }
+
+        self.draw_status_bar(out, &status)
+    }
+
+    fn draw(&self, out: &mut Stdout) -> std::io::Result<()> {
+        queue!(out, Hide)?;
+
+        match &self.page {
+            Some(page) if !self.is_searching => self.draw_page(out, page)?,
+            _ => self.draw_search(out)?,
+        }
+
+        out.flush()
+    }
+
+    fn run(&mut self, out: &mut Stdout) -> std::io::Result<()> {
+        loop {
+            self.draw(out)?;
+
+            match read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    if let Action::Quit = self.handle_key(key) {
+                        return Ok(());
+                    }
+                }
+                Event::Resize(width, height) => self.resize(width, height),
+                _ => {}
+            }
+        }
+    }
+}
+
+pub(crate) fn tui<Args>(mut args: Args) -> ResultS
+where
+    Args: Iterator<Item = String>,
+{
+    let mut flag_ignore_case;
+    let mut flag_columns;
+    let mut flag_help;
+
+    let mut flags = flags![
+        flag_ignore_case: BoolFlag, ["-i", "--ignore-case"],
+        flag_columns: StringFlag,   ["-c", "--columns"],
+        flag_help: BoolFlag,        ["--help"]
+    ];
+
+    let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
+    if flag_help || args.is_empty() {
+        return show_tui_help();
+    }
+
+    let mut columns = None;
+
+    let maybe_columns = flag_columns.parse::<usize>().ok();
+    if let Some(col_number) = maybe_columns {
+        if col_number == 0 {
+            columns = Some(999);
+        } else if col_number > 10 {
+            columns = Some(col_number);
+        }
+    } else if !flag_columns.is_empty() {
+        print_warning!("Invalid number of columns.");
+    }
+
+    if !is_docs_json_exists()? {
+        return Err("The list of available documents has not yet been downloaded. Please run `fetch` first.".to_string());
+    }
+
+    let mut args = args.into_iter();
+
+    let docset = if let Some(docset_name) = args.next() {
+        docset_name
+    } else {
+        return show_tui_help();
+    };
+
+    if !is_docset_available(&docset)? {
+        let docs = deserialize_docs_json()?;
+
+        if is_docset_in_docs_or_print_warning(&docset, &docs) {
+            return Err(format!(
+                "Docset `{docset}` is not downloaded. Try running `download {docset}`."
+            ));
+        }
+        return Ok(());
+    }
+
+    let mut out = stdout();
+
+    if !out.is_tty() {
+        return Err("`tui` can only be used in a terminal.".to_string());
+    }
+
+    let entries = deserialize_index_json(&docset)?.entries;
+
+    let (terminal_width, terminal_height) =
+        size().map_err(|err| format!("Could not get terminal size: {err}"))?;
+
+    let mut tui = Tui {
+        pages_path: get_docset_pages_path(&docset)?,
+        docset,
+        entries,
+        case_insensitive: flag_ignore_case,
+        columns,
+        config: get_config()?,
+        terminal_width: terminal_width as usize,
+        terminal_height: terminal_height as usize,
+        is_searching: true,
+        query: args.collect::<Vec<String>>().join(" "),
+        results: vec![],
+        selected_result: 0,
+        results_scroll: 0,
+        page: None,
+        history: vec![],
+        message: String::new(),
+    };
+
+    tui.update_results();
+
+    let guard = TerminalGuard::enter(&mut out)?;
+    let result = tui.run(&mut out);
+    drop(guard);
+
+    result.map_err(|err| format!("Terminal error: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_HTML: &str = r##"<p><a href="#second">To the second section</a></p>
+<p>1</p><p>2</p><p>3</p><p>4</p><p>5</p><p>6</p><p>7</p><p>8</p>
+<h2 id="first">First</h2>
+<p><a href="https://www.rust-lang.org/">External</a></p>
+<p>1</p><p>2</p><p>3</p><p>4</p><p>5</p><p>6</p><p>7</p><p>8</p>
+<p><a href="struct.Vec">Vec</a></p>
+<p>1</p><p>2</p><p>3</p><p>4</p>
+<h2 id="second">Second</h2>
+<p>End</p>"##;
+
+    fn test_tui(entries: &[(&str, &str)]) -> Tui {
+        Tui {
+            docset: "rust".to_string(),
+            pages_path: PathBuf::new(),
+            entries: entries
+                .iter()
+                .map(|(name, path)| IndexEntry {
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    r#type: String::new(),
+                })
+                .collect(),
+            case_insensitive: false,
+            columns: None,
+            config: Config::default(),
+            terminal_width: 80,
+            terminal_height: 6,
+            is_searching: true,
+            query: String::new(),
+            results: vec![],
+            selected_result: 0,
+            results_scroll: 0,
+            page: None,
+            history: vec![],
+            message: String::new(),
+        }
+    }
+
+    fn test_tui_with_page() -> Tui {
+        let rich_page = html2text::from_read_rich(PAGE_HTML.as_bytes(), 80);
+
+        let mut tui = test_tui(&[]);
+        tui.is_searching = false;
+        tui.page = Some(Page {
+            item: "std/index".to_string(),
+            path: PathBuf::from("std/index.html"),
+            links: get_page_links(&rich_page),
+            fragment_lines: get_fragment_lines(&rich_page),
+            rich_page,
+            scroll: 0,
+            selected_link: None,
+        });
+        tui
+    }
+
+    #[test]
+    fn test_update_results() {
+        let mut tui = test_tui(&[
+            ("Vec", "std/vec/struct.Vec"),
+            ("vec!", "std/macro.vec"),
+            ("HashMap", "std/collections/struct.HashMap"),
+        ]);
+
+        tui.update_results();
+        assert_eq!(tui.results, [0, 1, 2]);
+
+        tui.select_result(2);
+        tui.results_scroll = 1;
+
+        tui.query = "vec".to_string();
+        tui.update_results();
+
+        let mut results = tui.results.clone();
+        results.sort();
+        assert_eq!(results, [0, 1]);
+        assert_eq!(tui.selected_result, 0);
+        assert_eq!(tui.results_scroll, 0);
+
+        // Smart case.
+        tui.query = "Vec".to_string();
+        tui.update_results();
+        assert_eq!(tui.results, [0]);
+    }
+
+    #[test]
+    fn test_clamp_scroll() {
+        let mut tui = test_tui_with_page();
+        let max_scroll = tui.page.as_ref().unwrap().rich_page.len() - tui.view_height();
+
+        tui.scroll_by(-3);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, 0);
+
+        tui.scroll_by(1000);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, max_scroll);
+
+        tui.scroll_by(-3);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, max_scroll - 3);
+
+        tui.terminal_height = 1000;
+        tui.clamp_scroll();
+        assert_eq!(tui.page.as_ref().unwrap().scroll, 0);
+    }
+
+    #[test]
+    fn test_select_link() {
+        let mut tui = test_tui_with_page();
+        let view_height = tui.view_height();
+
+        tui.select_link(false);
+        assert_eq!(tui.page.as_ref().unwrap().selected_link, Some(0));
+
+        tui.select_link(false);
+        assert_eq!(tui.message, "No more links.");
+        tui.message.clear();
+
+        // Links below the visible part of the page are scrolled to.
+        for i in 1..3 {
+            tui.select_link(true);
+
+            let page = tui.page.as_ref().unwrap();
+            let link = &page.links[i];
+            assert_eq!(page.selected_link, Some(i));
+            assert!(link.first_line >= page.scroll);
+            assert!(link.last_line < page.scroll + view_height);
+        }
+
+        tui.select_link(true);
+        assert_eq!(tui.message, "No more links.");
+
+        // Selection starts from the visible part when the selected link was scrolled away from.
+        tui.scroll_by(-1000);
+        tui.select_link(true);
+        assert_eq!(tui.page.as_ref().unwrap().selected_link, Some(0));
+    }
+
+    #[test]
+    fn test_jump_to_fragment() {
+        let mut tui = test_tui_with_page();
+        let page = tui.page.as_ref().unwrap();
+        let fragment_lines = page.fragment_lines.clone();
+        let max_scroll = page.rich_page.len() - tui.view_height();
+
+        assert_eq!(fragment_lines.len(), 2);
+
+        tui.jump_to_fragment(true);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, fragment_lines[0]);
+
+        tui.jump_to_fragment(true);
+        assert_eq!(
+            tui.page.as_ref().unwrap().scroll,
+            fragment_lines[1].min(max_scroll)
+        );
+
+        tui.jump_to_fragment(false);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, fragment_lines[0]);
+
+        tui.jump_to_fragment(false);
+        assert_eq!(tui.page.as_ref().unwrap().scroll, fragment_lines[0]);
+        assert_eq!(tui.message, "No more sections.");
+    }
+
+    #[test]
+    fn test_follow_selected_link() {
+        let mut tui = test_tui_with_page();
+        let page = tui.page.as_ref().unwrap();
+        let second_line = page.fragment_lines[1];
+        let max_scroll = page.rich_page.len() - tui.view_height();
+
+        tui.follow_selected_link();
+        assert_eq!(tui.message, "No link is selected. Press Tab to select one.");
+
+        // Links to a section of the same page only scroll, and can be gone back from.
+        tui.select_link(true);
+        tui.follow_selected_link();
+
+        let page = tui.page.as_ref().unwrap();
+        assert_eq!(page.scroll, second_line.min(max_scroll));
+        assert_eq!(page.selected_link, None);
+        assert_eq!(
+            tui.history,
+            [("std/index".to_string(), PathBuf::from("std/index.html"), 0)]
+        );
+
+        tui.page.as_mut().unwrap().selected_link = Some(1);
+        tui.follow_selected_link();
+        assert_eq!(tui.message, "External link: https://www.rust-lang.org/");
+        assert_eq!(tui.history.len(), 1);
+    }
+}
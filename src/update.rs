@@ -0,0 +1,144 @@
+use toiletcli::flags;
+use toiletcli::flags::*;
+
+use crate::common::ResultS;
+use crate::common::{
+    deserialize_docs_json, find_docset_root, format_docset_version, get_docs_json_max_age,
+    get_flag_error, get_local_docsets, is_docs_json_exists, is_docs_json_old, read_docset_manifest,
+};
+use crate::common::{BOLD, GRAY, GREEN, PROGRAM_NAME, RESET, YELLOW};
+use crate::download::{install_docsets, DocsetInstall};
+use crate::mirror::Mirror;
+use crate::print_warning;
+
+fn show_update_help() -> ResultS {
+    println!(
+        "\
+{GREEN}USAGE{RESET}
+    {BOLD}{PROGRAM_NAME} update{RESET} [-cm] [docset1, docset2, ..]
+    Download newer versions of docsets. Updates every local docset when none are specified.
+    Versions are taken from the list of available docsets, which is refreshed with `fetch`.
+
+{GREEN}OPTIONS{RESET}
+    -c, --check                     Only show which docsets are outdated.
+    -m, --mirror <url>              Download from a DevDocs mirror or a local directory.
+        --help                      Display help message."
+    );
+    Ok(())
+}
+
+pub(crate) fn update<Args>(mut args: Args) -> ResultS
+where
+    Args: Iterator<Item = String>,
+{
+    let mut flag_check;
+    let mut flag_mirror;
+    let mut flag_help;
+
+    let mut flags = flags![
+        flag_check: BoolFlag,    ["-c", "--check"],
+        flag_mirror: StringFlag, ["-m", "--mirror"],
+        flag_help: BoolFlag,     ["--help"]
+    ];
+
+    let args = parse_flags(&mut args, &mut flags).map_err(|err| get_flag_error(&err))?;
+    if flag_help {
+        return show_update_help();
+    }
+
+    if !is_docs_json_exists()? {
+        return Err("The list of available documents has not yet been downloaded. Please run `fetch` first.".to_string());
+    }
+
+    if is_docs_json_old()? {
+        print_warning!(
+            "The list of available docsets is more than {} days old. Run `fetch` to see the latest versions.",
+            get_docs_json_max_age()?
+        );
+    }
+
+    let docsets = if args.is_empty() {
+        get_local_docsets()?
+    } else {
+        args
+    };
+
+    let docs = deserialize_docs_json()?;
+
+    let mut outdated_docsets = vec![];
+
+    for docset in docsets {
+        let Some(root) = find_docset_root(&docset)? else {
+            print_warning!("`{docset}` is not installed. Try running `download {docset}`.");
+            continue;
+        };
+
+        let Some(entry) = docs.iter().find(|entry| entry.slug == docset) else {
+            print_warning!("`{docset}` is no longer available, so it can't be updated.");
+            continue;
+        };
+
+        let available_version = format_docset_version(&entry.version, &entry.release);
+
+        match read_docset_manifest(&docset)? {
+            Some(manifest) if !manifest.is_outdated(entry) => {
+                println!(
+                    "{GREEN}{docset}{RESET} is up to date {GRAY}({available_version}){RESET}."
+                );
+            }
+            Some(manifest) => {
+                let installed_version = format_docset_version(&manifest.version, &manifest.release);
+                println!(
+                    "{YELLOW}{docset}{RESET} is outdated {GRAY}({installed_version} -> {available_version}){RESET}."
+                );
+                outdated_docsets.push((docset, root, manifest.prerendered, manifest.packed));
+            }
+            // Docsets downloaded before manifests were introduced.
+            None => {
+                println!(
+                    "{YELLOW}{docset}{RESET} has an unknown version {GRAY}(-> {available_version}){RESET}."
+                );
+                outdated_docsets.push((docset, root, false, false));
+            }
+        }
+    }
+
+    if flag_check || outdated_docsets.is_empty() {
+        return Ok(());
+    }
+
+    let mirror = Mirror::resolve(&flag_mirror)?;
+
+    let mut installs = vec![];
+
+    // Update docsets where they are, and keep pages pre-rendered and packed if they were.
+    for (docset, root, prerendered, packed) in outdated_docsets {
+        if !root.is_writable() {
+            print_warning!(
+                "`{docset}` is in read-only `{}`, so it can't be updated.",
+                root.path.display()
+            );
+            continue;
+        }
+
+        installs.push(DocsetInstall {
+            docset_name: docset,
+            root,
+            prerender: prerendered,
+            pack: packed,
+        });
+    }
+
+    if installs.is_empty() {
+        return Ok(());
+    }
+
+    install_docsets(&installs, &docs, &mirror)?;
+
+    match installs.len() {
+        1 => println!("{BOLD}Update has successfully finished{RESET}."),
+        n => println!("{BOLD}{n} items were successfully updated{RESET}."),
+    }
+
+    Ok(())
+}
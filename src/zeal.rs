@@ -0,0 +1,323 @@
+// Docsets of Zeal and Dash, which are `.docset` bundles:
+//
+//     Name.docset/Contents/Resources/docSet.dsidx    SQLite index of entries
+//     Name.docset/Contents/Resources/Documents/      HTML pages
+//
+// Bundles are looked up in `$DEDOC_ZEAL_DIR`, `zeal_dir` from the config, or in the docsets
+// directory of Zeal. They are named after the bundle in lowercase, e.g. `python_3` for
+// `Python 3.docset`. Downloaded docsets take precedence over bundles with the same name.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::common::{is_docset_downloaded, DOC_PAGE_EXTENSION};
+use crate::config::get_config;
+use crate::search::IndexEntry;
+
+const ZEAL_DIR_ENV: &str = "DEDOC_ZEAL_DIR";
+
+const BUNDLE_EXTENSION: &str = "docset";
+
+pub(crate) fn get_zeal_directory() -> Result<Option<PathBuf>, String> {
+    match std::env::var(ZEAL_DIR_ENV) {
+        Ok(directory) if !directory.is_empty() => return Ok(Some(PathBuf::from(directory))),
+        _ => {}
+    }
+
+    if let Some(directory) = get_config()?.zeal_dir {
+        return Ok(Some(PathBuf::from(directory)));
+    }
+
+    let default_directory =
+        dirs::data_dir().map(|data_dir| data_dir.join("Zeal").join("Zeal").join("docsets"));
+
+    Ok(default_directory)
+}
+
+fn get_bundle_name(bundle_path: &Path) -> Option<String> {
+    let stem = bundle_path.file_stem()?.to_string_lossy();
+    Some(stem.to_lowercase().replace(' ', "_"))
+}
+
+#[inline]
+fn is_bundle(path: &Path) -> bool {
+    path.is_dir() && matches!(path.extension(), Some(extension) if extension == BUNDLE_EXTENSION)
+}
+
+// Returns names and paths of bundles. Dash keeps each bundle in a directory of its own, so
+// directories are searched one level deep.
+fn find_bundles() -> Result<Vec<(String, PathBuf)>, String> {
+    let mut bundles = vec![];
+
+    // Without SQLite, bundles can't be read.
+    if cfg!(not(feature = "zeal")) {
+        return Ok(bundles);
+    }
+
+    let Some(zeal_directory) = get_zeal_directory()? else {
+        return Ok(bundles);
+    };
+
+    if !zeal_directory.is_dir() {
+        return Ok(bundles);
+    }
+
+    let read_directory = |directory: &Path| -> Result<Vec<PathBuf>, String> {
+        let entries = std::fs::read_dir(directory)
+            .map_err(|err| format!("Could not read `{}`: {err}", directory.display()))?;
+
+        let mut paths = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|err| err.to_string())?;
+            paths.push(entry.path());
+        }
+
+        Ok(paths)
+    };
+
+    for path in read_directory(&zeal_directory)? {
+        if is_bundle(&path) {
+            if let Some(name) = get_bundle_name(&path) {
+                bundles.push((name, path));
+            }
+        } else if path.is_dir() {
+            for nested_path in read_directory(&path)? {
+                if is_bundle(&nested_path) {
+                    if let Some(name) = get_bundle_name(&nested_path) {
+                        bundles.push((name, nested_path));
+                    }
+                }
+            }
+        }
+    }
+
+    bundles.sort();
+
+    Ok(bundles)
+}
+
+// Bundles are looked up for every page and search, and the directory doesn't change while the
+// program runs, so it's scanned once.
+static BUNDLES: Mutex<Option<Vec<(String, PathBuf)>>> = Mutex::new(None);
+
+fn get_bundles() -> Result<Vec<(String, PathBuf)>, String> {
+    let mut cached_bundles = BUNDLES.lock().map_err(|err| err.to_string())?;
+
+    if let Some(bundles) = cached_bundles.as_ref() {
+        return Ok(bundles.clone());
+    }
+
+    let bundles = find_bundles()?;
+    *cached_bundles = Some(bundles.clone());
+
+    Ok(bundles)
+}
+
+// Names of bundles that are not shadowed by downloaded docsets.
+pub(crate) fn get_zeal_docsets() -> Result<Vec<String>, String> {
+    let mut docsets = vec![];
+
+    for (name, _) in get_bundles()? {
+        if !is_docset_downloaded(&name)? && !docsets.contains(&name) {
+            docsets.push(name);
+        }
+    }
+
+    Ok(docsets)
+}
+
+fn get_bundle_path(docset_name: &str) -> Result<Option<PathBuf>, String> {
+    if is_docset_downloaded(docset_name)? {
+        return Ok(None);
+    }
+
+    let bundle = get_bundles()?
+        .into_iter()
+        .find(|(name, _)| name == docset_name)
+        .map(|(_, path)| path);
+
+    Ok(bundle)
+}
+
+#[inline]
+pub(crate) fn is_zeal_docset(docset_name: &str) -> Result<bool, String> {
+    Ok(get_bundle_path(docset_name)?.is_some())
+}
+
+// Directory with HTML pages of the bundle, if `docset_name` is a Zeal docset.
+pub(crate) fn get_zeal_documents_path(docset_name: &str) -> Result<Option<PathBuf>, String> {
+    let documents_path = get_bundle_path(docset_name)?
+        .map(|bundle| bundle.join("Contents").join("Resources").join("Documents"));
+
+    Ok(documents_path)
+}
+
+// Converts a path from the index of a bundle into a page path, like the ones in `index.json`.
+// Paths can be prefixed with metadata, e.g. `<dash_entry_name=Vec>std/vec/struct.Vec.html#new`.
+// Returns `None` for entries that link to websites.
+#[cfg_attr(not(feature = "zeal"), allow(dead_code))]
+fn convert_bundle_path(path: &str) -> Option<String> {
+    let mut path = path;
+
+    while path.starts_with('<') {
+        let end = path.find('>')?;
+        path = &path[end + 1..];
+    }
+
+    if path.contains("://") || path.is_empty() {
+        return None;
+    }
+
+    let (page, fragment) = match path.split_once('#') {
+        Some((page, fragment)) => (page, Some(fragment)),
+        None => (path, None),
+    };
+
+    let page = page
+        .strip_suffix(&format!(".{DOC_PAGE_EXTENSION}"))
+        .unwrap_or(page);
+
+    match fragment {
+        Some(fragment) if !fragment.is_empty() => Some(format!("{page}#{fragment}")),
+        _ => Some(page.to_owned()),
+    }
+}
+
+#[cfg(feature = "zeal")]
+fn read_bundle_index(index_path: &Path) -> Result<Vec<IndexEntry>, String> {
+    use rusqlite::{Connection, OpenFlags};
+
+    let sqlite_error =
+        |err: rusqlite::Error| format!("Could not read `{}`: {err}", index_path.display());
+
+    let connection = Connection::open_with_flags(
+        index_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(sqlite_error)?;
+
+    let has_search_index: bool = connection
+        .query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'searchIndex'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(sqlite_error)?;
+
+    // Bundles made by Dash's generator have `searchIndex`, bundles made with Apple's docsetutil
+    // store entries in Core Data tables.
+    let query = if has_search_index {
+        "SELECT name, type, path, NULL FROM searchIndex"
+    } else {
+        "SELECT ZTOKEN.ZTOKENNAME, ZTOKENTYPE.ZTYPENAME, ZFILEPATH.ZPATH, ZTOKENMETAINFORMATION.ZANCHOR \
+        FROM ZTOKEN \
+        JOIN ZTOKENTYPE ON ZTOKEN.ZTOKENTYPE = ZTOKENTYPE.Z_PK \
+        JOIN ZTOKENMETAINFORMATION ON ZTOKEN.ZMETAINFORMATION = ZTOKENMETAINFORMATION.Z_PK \
+        JOIN ZFILEPATH ON ZTOKENMETAINFORMATION.ZFILE = ZFILEPATH.Z_PK"
+    };
+
+    let mut statement = connection.prepare(query).map_err(sqlite_error)?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(sqlite_error)?;
+
+    let mut entries = vec![];
+
+    for row in rows {
+        let (name, entry_type, path, anchor) = row.map_err(sqlite_error)?;
+
+        let path = match anchor {
+            Some(anchor) if !anchor.is_empty() => format!("{path}#{anchor}"),
+            _ => path,
+        };
+
+        if let Some(path) = convert_bundle_path(&path) {
+            entries.push(IndexEntry {
+                name,
+                path,
+                r#type: entry_type.unwrap_or_default(),
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(not(feature = "zeal"))]
+fn read_bundle_index(_index_path: &Path) -> Result<Vec<IndexEntry>, String> {
+    Err("This build of dedoc does not support Zeal docsets.".to_string())
+}
+
+pub(crate) fn read_zeal_index(docset_name: &str) -> Result<Vec<IndexEntry>, String> {
+    let Some(bundle_path) = get_bundle_path(docset_name)? else {
+        return Err(format!("`{docset_name}` is not a Zeal docset."));
+    };
+
+    let index_path = bundle_path
+        .join("Contents")
+        .join("Resources")
+        .join("docSet.dsidx");
+
+    read_bundle_index(&index_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "zeal")]
+    use crate::common::TestDirectory;
+
+    #[test]
+    fn test_convert_bundle_path() {
+        assert_eq!(
+            convert_bundle_path("std/vec/struct.Vec.html#method.push"),
+            Some("std/vec/struct.Vec#method.push".to_string())
+        );
+        assert_eq!(
+            convert_bundle_path(
+                "<dash_entry_name=Vec><dash_entry_menuDescription=std>std/vec/struct.Vec.html"
+            ),
+            Some("std/vec/struct.Vec".to_string())
+        );
+        assert_eq!(
+            convert_bundle_path("library/os.htm#os.getcwd"),
+            Some("library/os.htm#os.getcwd".to_string())
+        );
+        assert_eq!(convert_bundle_path("https://example.com/page.html"), None);
+    }
+
+    #[cfg(feature = "zeal")]
+    #[test]
+    fn test_read_bundle_index() {
+        let test_directory = TestDirectory::new("zeal");
+        let index_path = test_directory.path.join("docSet.dsidx");
+
+        {
+            let connection = rusqlite::Connection::open(&index_path).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
+                    INSERT INTO searchIndex(name, type, path) VALUES
+                        ('Vec', 'Struct', 'std/vec/struct.Vec.html'),
+                        ('Vec::push', 'Method', 'std/vec/struct.Vec.html#method.push'),
+                        ('Rust website', 'Guide', 'https://www.rust-lang.org');",
+                )
+                .unwrap();
+        }
+
+        let entries = read_bundle_index(&index_path).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "Vec::push");
+        assert_eq!(entries[1].path, "std/vec/struct.Vec#method.push");
+        assert_eq!(entries[1].r#type, "Method");
+    }
+}